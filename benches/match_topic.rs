@@ -0,0 +1,54 @@
+use aws_iot_device_sdk::{defender, jobs, shadow, tunneling};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+// Validating the names byte by byte rather than char by char, and looking
+// for empty levels with a byte scan rather than splitting the topic, took
+// (per iteration, on an x86-64 laptop):
+//
+// | bench     | before | after  |
+// |-----------|--------|--------|
+// | shadow    | 287 ns | 175 ns |
+// | jobs      | 250 ns | 165 ns |
+// | jobs_ref  | 243 ns | 154 ns |
+// | defender  | 171 ns | 101 ns |
+// | tunneling |  44 ns |  27 ns |
+
+// Representative topics of each subsystem, as received by a device.
+const SHADOW_TOPICS: [&str; 3] = [
+    "$aws/things/chloe/shadow/update/delta",
+    "$aws/things/chloe/shadow/get/accepted",
+    "$aws/things/chloe/shadow/name/common/update/documents",
+];
+const JOBS_TOPICS: [&str; 3] = [
+    "$aws/things/chloe/jobs/notify-next",
+    "$aws/things/chloe/jobs/start-next/accepted",
+    "$aws/things/chloe/jobs/example-job-01/update/rejected",
+];
+const DEFENDER_TOPICS: [&str; 2] = [
+    "$aws/things/chloe/defender/metrics/json/accepted",
+    "$aws/things/chloe/defender/metrics/cbor/rejected",
+];
+const TUNNELING_TOPIC: &str = "$aws/things/chloe/tunnels/notify";
+
+fn bench_match_topic(c: &mut Criterion) {
+    let mut group = c.benchmark_group("match_topic");
+    group.bench_function("shadow", |b| {
+        b.iter(|| SHADOW_TOPICS.map(|t| shadow::match_topic(black_box(t)).is_ok()))
+    });
+    group.bench_function("jobs", |b| {
+        b.iter(|| JOBS_TOPICS.map(|t| jobs::match_topic(black_box(t)).is_ok()))
+    });
+    group.bench_function("jobs_ref", |b| {
+        b.iter(|| JOBS_TOPICS.map(|t| jobs::match_topic_ref(black_box(t)).is_ok()))
+    });
+    group.bench_function("defender", |b| {
+        b.iter(|| DEFENDER_TOPICS.map(|t| defender::match_topic(black_box(t)).is_ok()))
+    });
+    group.bench_function("tunneling", |b| {
+        b.iter(|| tunneling::match_topic(black_box(TUNNELING_TOPIC)).is_ok())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_match_topic);
+criterion_main!(benches);
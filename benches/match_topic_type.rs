@@ -0,0 +1,45 @@
+use aws_iot_device_sdk::{defender, jobs, match_topic_type, shadow, tunneling, Error, TopicType};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const TOPICS: [&str; 6] = [
+    "$aws/things/chloe/shadow/name/common/update/delta",
+    "$aws/things/chloe/shadow/update/accepted",
+    "$aws/things/chloe/jobs/example-job-01/update/accepted",
+    "$aws/things/chloe/defender/metrics/json/accepted",
+    "$aws/things/chloe/tunnels/notify",
+    "$aws/things/chloe/streams/get",
+];
+
+/// Trying every module's matcher in turn.
+fn every_module(topic: &str) -> Result<TopicType, Error> {
+    if let Ok(shadow) = shadow::match_topic(topic) {
+        return Ok(match shadow.shadow_name {
+            Some(_) => TopicType::NamedShadow,
+            None => TopicType::Shadow,
+        });
+    }
+    if jobs::match_topic(topic).is_ok() {
+        return Ok(TopicType::Jobs);
+    }
+    if defender::match_topic(topic).is_ok() {
+        return Ok(TopicType::Defender);
+    }
+    if tunneling::match_topic(topic).is_ok() {
+        return Ok(TopicType::Tunneling);
+    }
+    Err(Error::NoMatch)
+}
+
+fn bench_match_topic_type(c: &mut Criterion) {
+    let mut group = c.benchmark_group("match_topic_type");
+    group.bench_function("match_topic_type", |b| {
+        b.iter(|| TOPICS.map(|t| match_topic_type(black_box(t))))
+    });
+    group.bench_function("every_module", |b| {
+        b.iter(|| TOPICS.map(|t| every_module(black_box(t))))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_match_topic_type);
+criterion_main!(benches);
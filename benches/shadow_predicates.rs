@@ -0,0 +1,28 @@
+use aws_iot_device_sdk::shadow;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const TOPICS: [&str; 4] = [
+    "$aws/things/chloe/shadow/name/common/update/delta",
+    "$aws/things/chloe/shadow/update/accepted",
+    "$aws/things/chloe/shadow/update/delta",
+    "$aws/things/chloe/jobs/notify",
+];
+
+fn bench_is_delta_topic(c: &mut Criterion) {
+    let mut group = c.benchmark_group("is_delta_topic");
+    group.bench_function("is_delta_topic", |b| {
+        b.iter(|| TOPICS.map(|t| shadow::is_delta_topic(black_box(t))))
+    });
+    group.bench_function("match_topic", |b| {
+        b.iter(|| {
+            TOPICS.map(|t| {
+                shadow::match_topic(black_box(t))
+                    .is_ok_and(|s| s.shadow_op == shadow::Topic::UpdateDelta)
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_is_delta_topic);
+criterion_main!(benches);
@@ -1,49 +1,608 @@
+use core::time::Duration;
+
 /// This library uses the "Full Jitter" strategy for the exponential backoff with jitter algorithm.
-/// More information about the algorithm can be seen in the 
-/// [Exponential Backoff and Jitter](https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/) 
+/// More information about the algorithm can be seen in the
+/// [Exponential Backoff and Jitter](https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/)
 /// AWS blog.
+///
+/// With the `serde` feature the whole state serializes, including the
+/// current base and the attempt counters, so that a device can persist it
+/// and resume the backoff after a reboot instead of starting over.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BackoffAlgorithm {
     /// The maximum backoff base (in milliseconds) between consecutive retry attempts.
     pub max: usize,
-    /// The total number of retry attempts completed.
-    /// This value is incremented on every call to #BackoffAlgorithm_GetNextBackoff API.
-    /// pub attemptsDone: usize,
     /// The maximum backoff value (in milliseconds) for the next retry attempt.
     pub base: usize,
-    /// The maximum number of retry attempts.
-    /// pub maxRetryAttempts: usize,
+    /// The maximum number of retry attempts, `None` to only stop once the
+    /// base exceeds `max`. Set with [`BackoffBuilder::max_attempts`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    max_attempts: Option<usize>,
+    /// The minimum backoff value (in milliseconds); jittered values are
+    /// raised to at least this floor.
+    pub min: usize,
+    /// The growth factor applied to the backoff base after every attempt.
+    pub multiplier: f64,
     power: usize,
     pub value: usize,
     pub rand: Option<usize>,
+    /// The total number of retry attempts completed.
+    /// This value is incremented on every backoff value returned by `next`.
+    attempts_done: usize,
+    /// The sum (in milliseconds) of every backoff value returned by `next`.
+    total_backoff_ms: usize,
 }
 
 impl BackoffAlgorithm {
     /// Initializes the context for using backoff algorithm.
+    ///
+    /// See [`BackoffAlgorithm::builder`] to set the parameters by name.
     pub fn new(base: usize, max: usize, rand: Option<usize>) -> BackoffAlgorithm {
         BackoffAlgorithm {
             base,
             max,
+            min: 0,
+            multiplier: 2.0,
+            max_attempts: None,
             power: base,
             value: base,
             rand,
+            attempts_done: 0,
+            total_backoff_ms: 0,
         }
     }
+    /// Sets the minimum backoff value (in milliseconds), so that computed
+    /// delays are clamped into `[min, max]`.
+    #[must_use = "this returns the configured backoff, leaving the original unchanged"]
+    pub fn with_min(mut self, min: usize) -> BackoffAlgorithm {
+        self.min = min;
+        self
+    }
+    /// Sets the growth factor of the backoff base, 2.0 by default.
+    ///
+    /// The base always grows by at least 1ms per attempt, so that the backoff
+    /// still ends with a multiplier of 1.0 or less.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::backoff_algo::BackoffAlgorithm;
+    ///
+    /// let bfa = BackoffAlgorithm::new(1, 9, None).with_multiplier(3.0);
+    /// assert_eq!(bfa.collect::<Vec<_>>(), [1, 3, 9]);
+    /// ```
+    #[must_use = "this returns the configured backoff, leaving the original unchanged"]
+    pub fn with_multiplier(mut self, multiplier: f64) -> BackoffAlgorithm {
+        self.multiplier = multiplier;
+        self
+    }
     /// Simple exponential backoff and jitter function that provides the
     /// delay value for the next retry attempt.
+    #[must_use]
     pub fn get(&self) -> usize {
         self.value
     }
+    /// The maximum number of backoff values, if the builder set one.
+    pub fn max_attempts(&self) -> Option<usize> {
+        self.max_attempts
+    }
+    /// The number of backoff values returned so far.
+    pub fn attempts_done(&self) -> usize {
+        self.attempts_done
+    }
+    /// The sum (in milliseconds) of the backoff values returned so far.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::backoff_algo::BackoffAlgorithm;
+    ///
+    /// let mut bfa = BackoffAlgorithm::new(1000, 16000, None);
+    /// while bfa.next().is_some() {}
+    /// assert_eq!(bfa.attempts_done(), 5);
+    /// assert_eq!(bfa.total_backoff_ms(), 31000);
+    /// ```
+    pub fn total_backoff_ms(&self) -> usize {
+        self.total_backoff_ms
+    }
+    /// Starts the backoff over, e.g. after a successful reconnect.
+    pub fn reset(&mut self) {
+        self.power = self.base;
+        self.value = self.base;
+        self.attempts_done = 0;
+        self.total_backoff_ms = 0;
+    }
+    /// Turns the backoff into an iterator of delays, for callers that
+    /// sleep on their own runtime (e.g. an async executor).
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::backoff_algo::BackoffAlgorithm;
+    /// use core::time::Duration;
+    ///
+    /// let mut delays = BackoffAlgorithm::new(100, 400, None).delays();
+    /// assert_eq!(delays.next(), Some(Duration::from_millis(100)));
+    /// assert_eq!(delays.next(), Some(Duration::from_millis(200)));
+    /// ```
+    #[must_use = "iterators are lazy and do nothing unless consumed"]
+    pub fn delays(self) -> Delays {
+        Delays { backoff: self }
+    }
+    /// Calls `op` until it succeeds, sleeping for the next backoff value
+    /// after every failure. Once the backoff is exhausted the last error is
+    /// returned.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::backoff_algo::BackoffAlgorithm;
+    ///
+    /// let mut attempts = 0;
+    /// let res: Result<usize, ()> = BackoffAlgorithm::new(1, 4, None).retry(|| {
+    ///     attempts += 1;
+    ///     Err(())
+    /// });
+    /// assert_eq!(res, Err(()));
+    /// assert_eq!(attempts, 4);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn retry<F, T, E>(mut self, op: F) -> Result<T, E>
+    where
+        F: FnMut() -> Result<T, E>,
+    {
+        retry(&mut self, op)
+    }
+    /// Same as [`BackoffAlgorithm::retry`], but also gives up once the next
+    /// attempt would start after `deadline`. See [`retry_until`].
+    #[cfg(feature = "std")]
+    pub fn retry_until<F, T, E>(mut self, deadline: std::time::Instant, op: F) -> Result<T, E>
+    where
+        F: FnMut() -> Result<T, E>,
+    {
+        retry_until(&mut self, deadline, op)
+    }
+}
+
+impl BackoffAlgorithm {
+    /// A [`BackoffBuilder`], starting from the defaults.
+    pub fn builder() -> BackoffBuilder {
+        BackoffBuilder::default()
+    }
+}
+
+/// The defaults of [`BackoffBuilder`].
+impl Default for BackoffAlgorithm {
+    fn default() -> Self {
+        BackoffBuilder::default().build()
+    }
+}
+
+/// How the backoff values are randomized, see [`BackoffBuilder::jitter`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Jitter {
+    /// Every value is the backoff base itself.
+    #[default]
+    None,
+    /// Full jitter, from a random value drawn by the caller, as this crate
+    /// has no random source of its own.
+    Full(usize),
+}
+
+/// Named parameters for a [`BackoffAlgorithm`], all in milliseconds.
+///
+/// The defaults follow the AWS recommendations: a base of 1s, doubling up to
+/// 32s, with no limit on the number of attempts. They can't include the
+/// recommended full jitter, which needs a random value from the caller.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::backoff_algo::{BackoffAlgorithm, Jitter};
+///
+/// let bfa = BackoffAlgorithm::builder()
+///     .base(500)
+///     .max(4000)
+///     .jitter(Jitter::Full(rand::random()))
+///     .max_attempts(3)
+///     .build();
+/// assert_eq!(bfa.count(), 3);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackoffBuilder {
+    base: usize,
+    max: usize,
+    min: usize,
+    multiplier: f64,
+    jitter: Jitter,
+    max_attempts: Option<usize>,
+}
+
+impl Default for BackoffBuilder {
+    fn default() -> Self {
+        BackoffBuilder {
+            base: 1000,
+            max: 32000,
+            min: 0,
+            multiplier: 2.0,
+            jitter: Jitter::None,
+            max_attempts: None,
+        }
+    }
+}
+
+impl BackoffBuilder {
+    /// The first backoff value.
+    #[must_use = "this returns the configured builder, leaving the original unchanged"]
+    pub fn base(mut self, base: usize) -> Self {
+        self.base = base;
+        self
+    }
+    /// The largest backoff base; the backoff ends once the base exceeds it.
+    #[must_use = "this returns the configured builder, leaving the original unchanged"]
+    pub fn max(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+    /// See [`BackoffAlgorithm::with_min`].
+    #[must_use = "this returns the configured builder, leaving the original unchanged"]
+    pub fn min(mut self, min: usize) -> Self {
+        self.min = min;
+        self
+    }
+    /// See [`BackoffAlgorithm::with_multiplier`].
+    #[must_use = "this returns the configured builder, leaving the original unchanged"]
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+    #[must_use = "this returns the configured builder, leaving the original unchanged"]
+    pub fn jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+    /// Stop after `max_attempts` backoff values, even if the base is still
+    /// below the maximum.
+    #[must_use = "this returns the configured builder, leaving the original unchanged"]
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+    #[must_use]
+    pub fn build(self) -> BackoffAlgorithm {
+        let rand = match self.jitter {
+            Jitter::None => None,
+            Jitter::Full(rand) => Some(rand),
+        };
+        BackoffAlgorithm {
+            max_attempts: self.max_attempts,
+            ..BackoffAlgorithm::new(self.base, self.max, rand)
+                .with_min(self.min)
+                .with_multiplier(self.multiplier)
+        }
+    }
+}
+
+/// A source of delays between consecutive retry attempts.
+///
+/// Implemented by [`BackoffAlgorithm`], [`FixedDelay`] and
+/// [`FibonacciBackoff`], and accepted by [`retry`].
+pub trait RetryPolicy {
+    /// The delay to wait after failed attempt number `attempt`, counting
+    /// from 0, or `None` to give up.
+    fn next_delay(&mut self, attempt: usize) -> Option<Duration>;
+}
+
+impl RetryPolicy for BackoffAlgorithm {
+    fn next_delay(&mut self, _attempt: usize) -> Option<Duration> {
+        self.next().map(|ms| Duration::from_millis(ms as u64))
+    }
+}
+
+/// The same delay between every attempt, for a bounded number of retries.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::backoff_algo::{FixedDelay, RetryPolicy};
+/// use core::time::Duration;
+///
+/// let mut policy = FixedDelay::new(Duration::from_millis(500), 2);
+/// assert_eq!(policy.next_delay(0), Some(Duration::from_millis(500)));
+/// assert_eq!(policy.next_delay(1), Some(Duration::from_millis(500)));
+/// assert_eq!(policy.next_delay(2), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedDelay {
+    pub delay: Duration,
+    /// The maximum number of retry attempts.
+    pub max_attempts: usize,
+}
+
+impl FixedDelay {
+    pub fn new(delay: Duration, max_attempts: usize) -> FixedDelay {
+        FixedDelay {
+            delay,
+            max_attempts,
+        }
+    }
+}
+
+impl RetryPolicy for FixedDelay {
+    fn next_delay(&mut self, attempt: usize) -> Option<Duration> {
+        (attempt < self.max_attempts).then_some(self.delay)
+    }
+}
+
+/// Delays growing along the Fibonacci sequence, `base`, `base`, `2 * base`,
+/// `3 * base`, `5 * base` and so on, until they would exceed `max`.
+///
+/// This grows slower than doubling, which keeps the delays shorter for
+/// failures which are expected to clear up quickly.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::backoff_algo::{FibonacciBackoff, RetryPolicy};
+/// use core::time::Duration;
+///
+/// let mut policy = FibonacciBackoff::new(100, 300);
+/// let delays: Vec<_> = (0..).map_while(|attempt| policy.next_delay(attempt)).collect();
+/// assert_eq!(delays, [100, 100, 200, 300].map(Duration::from_millis));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FibonacciBackoff {
+    /// The first backoff value (in milliseconds).
+    pub base: usize,
+    /// The maximum backoff value (in milliseconds).
+    pub max: usize,
+    previous: usize,
+    current: usize,
+}
+
+impl FibonacciBackoff {
+    pub fn new(base: usize, max: usize) -> FibonacciBackoff {
+        FibonacciBackoff {
+            base,
+            max,
+            previous: 0,
+            current: base,
+        }
+    }
+}
+
+impl RetryPolicy for FibonacciBackoff {
+    fn next_delay(&mut self, _attempt: usize) -> Option<Duration> {
+        let value = self.current;
+        if value > self.max || value == 0 {
+            return None;
+        }
+        self.current = self.previous.saturating_add(value);
+        self.previous = value;
+        Some(Duration::from_millis(value as u64))
+    }
+}
+
+/// Calls `op` until it succeeds, sleeping for the delay `policy` provides
+/// after every failure. Once the policy gives up the last error is returned.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::backoff_algo::{retry, FixedDelay};
+/// use core::time::Duration;
+///
+/// let mut attempts = 0;
+/// let mut policy = FixedDelay::new(Duration::from_millis(1), 3);
+/// let res: Result<(), &str> = retry(&mut policy, || {
+///     attempts += 1;
+///     Err("offline")
+/// });
+/// assert_eq!(res, Err("offline"));
+/// assert_eq!(attempts, 4);
+/// ```
+#[cfg(feature = "std")]
+pub fn retry<P, F, T, E>(policy: &mut P, op: F) -> Result<T, E>
+where
+    P: RetryPolicy + ?Sized,
+    F: FnMut() -> Result<T, E>,
+{
+    retry_until_with(policy, u64::MAX, || 0, std::thread::sleep, op)
+}
+
+/// Same as [`retry`], but also gives up once the next attempt would start
+/// after `deadline`, even if the policy has retries left.
+///
+/// The attempt count alone doesn't bound the total time when the delays
+/// are large, the deadline does.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::backoff_algo::{retry_until, FixedDelay};
+/// use std::time::{Duration, Instant};
+///
+/// let mut policy = FixedDelay::new(Duration::from_millis(10), 1000);
+/// let deadline = Instant::now() + Duration::from_millis(35);
+/// let res: Result<(), &str> = retry_until(&mut policy, deadline, || Err("offline"));
+/// assert_eq!(res, Err("offline"));
+/// ```
+///
+/// The timing itself is shown with a fake clock in [`retry_until_with`].
+#[cfg(feature = "std")]
+pub fn retry_until<P, F, T, E>(policy: &mut P, deadline: std::time::Instant, op: F) -> Result<T, E>
+where
+    P: RetryPolicy + ?Sized,
+    F: FnMut() -> Result<T, E>,
+{
+    let start = std::time::Instant::now();
+    let deadline_ms = as_millis(deadline.saturating_duration_since(start));
+    let now = || as_millis(start.elapsed());
+    retry_until_with(policy, deadline_ms, now, std::thread::sleep, op)
+}
+
+/// The core of [`retry`] and [`retry_until`], available without `std`.
+///
+/// `sleep` waits for the given delay, and `now` reads a monotonic clock in
+/// milliseconds. Once `now` plus the next delay passes `deadline_ms`, the
+/// last error is returned instead of sleeping.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::backoff_algo::{retry_until_with, BackoffAlgorithm};
+/// use core::cell::Cell;
+///
+/// let clock = Cell::new(0);
+/// let mut attempts = 0;
+/// let res: Result<(), ()> = retry_until_with(
+///     &mut BackoffAlgorithm::new(100, 6400, None),
+///     1000,
+///     || clock.get(),
+///     |delay| clock.set(clock.get() + delay.as_millis() as u64),
+///     || {
+///         attempts += 1;
+///         Err(())
+///     },
+/// );
+/// // Retried after 100, 200 and 400ms; waiting another 800ms would pass the deadline.
+/// assert_eq!(attempts, 4);
+/// assert_eq!(clock.get(), 700);
+/// ```
+pub fn retry_until_with<P, N, S, F, T, E>(
+    policy: &mut P,
+    deadline_ms: u64,
+    mut now: N,
+    mut sleep: S,
+    mut op: F,
+) -> Result<T, E>
+where
+    P: RetryPolicy + ?Sized,
+    N: FnMut() -> u64,
+    S: FnMut(Duration),
+    F: FnMut() -> Result<T, E>,
+{
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Ok(t) => return Ok(t),
+            Err(e) => match policy.next_delay(attempt) {
+                Some(delay) if now().saturating_add(as_millis(delay)) <= deadline_ms => {
+                    sleep(delay)
+                }
+                _ => return Err(e),
+            },
+        }
+        attempt += 1;
+    }
+}
+
+/// The async counterpart of [`retry`]: awaits `connect` until it succeeds,
+/// sleeping on the tokio timer for the next delay of `policy` after every
+/// failure. Once the policy is exhausted the last error is returned.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::backoff_algo::{connect_with_backoff, BackoffAlgorithm};
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+/// let mut attempts = 0;
+/// let res: Result<&str, &str> = runtime.block_on(connect_with_backoff(
+///     &mut BackoffAlgorithm::new(1, 8, None),
+///     || {
+///         attempts += 1;
+///         let res = if attempts < 3 { Err("offline") } else { Ok("connected") };
+///         async move { res }
+///     },
+/// ));
+/// assert_eq!(res, Ok("connected"));
+/// assert_eq!(attempts, 3);
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn connect_with_backoff<P, F, Fut, C, E>(policy: &mut P, connect: F) -> Result<C, E>
+where
+    P: RetryPolicy + ?Sized,
+    F: FnMut() -> Fut,
+    Fut: core::future::Future<Output = Result<C, E>>,
+{
+    connect_with_backoff_inner(policy, None, connect).await
+}
+
+/// Same as [`connect_with_backoff`], but also gives up once the next attempt
+/// would start after `deadline`, even if the policy has retries left.
+#[cfg(feature = "tokio")]
+pub async fn connect_with_backoff_until<P, F, Fut, C, E>(
+    policy: &mut P,
+    deadline: tokio::time::Instant,
+    connect: F,
+) -> Result<C, E>
+where
+    P: RetryPolicy + ?Sized,
+    F: FnMut() -> Fut,
+    Fut: core::future::Future<Output = Result<C, E>>,
+{
+    connect_with_backoff_inner(policy, Some(deadline), connect).await
+}
+
+#[cfg(feature = "tokio")]
+async fn connect_with_backoff_inner<P, F, Fut, C, E>(
+    policy: &mut P,
+    deadline: Option<tokio::time::Instant>,
+    mut connect: F,
+) -> Result<C, E>
+where
+    P: RetryPolicy + ?Sized,
+    F: FnMut() -> Fut,
+    Fut: core::future::Future<Output = Result<C, E>>,
+{
+    let mut attempt = 0;
+    loop {
+        match connect().await {
+            Ok(c) => return Ok(c),
+            Err(e) => match policy.next_delay(attempt) {
+                Some(delay)
+                    if deadline.is_none_or(|d| tokio::time::Instant::now() + delay <= d) =>
+                {
+                    tokio::time::sleep(delay).await
+                }
+                _ => return Err(e),
+            },
+        }
+        attempt += 1;
+    }
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Iterator over the delays (in milliseconds) of a [`BackoffAlgorithm`].
+///
+/// Returned by [`BackoffAlgorithm::delays`].
+#[derive(Debug)]
+pub struct Delays {
+    backoff: BackoffAlgorithm,
+}
+
+impl Iterator for Delays {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.backoff
+            .next()
+            .map(|ms| Duration::from_millis(ms as u64))
+    }
 }
 
 impl Iterator for BackoffAlgorithm {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.value = self.power + self.rand.unwrap_or_default() % self.power;
-        self.power += self.power;
+        let power = self.power;
+        self.value = power.saturating_add(self.rand.unwrap_or_default() % power);
+        // The cast saturates, so a huge multiplier ends the backoff rather
+        // than overflowing, and f64 keeps large bases exact.
+        let grown = (power as f64 * self.multiplier) as usize;
+        self.power = grown.max(power.saturating_add(1));
 
-        if self.value <= self.max {
+        let exhausted = self
+            .max_attempts
+            .is_some_and(|max| self.attempts_done >= max);
+        if power <= self.max && !exhausted {
+            self.value = self.value.max(self.min).min(self.max);
+            self.attempts_done += 1;
+            self.total_backoff_ms = self.total_backoff_ms.saturating_add(self.value);
             Some(self.value)
         } else {
             None
@@ -53,6 +612,7 @@ impl Iterator for BackoffAlgorithm {
 
 #[cfg(test)]
 mod tests {
+    use core::time::Duration;
     use rand::random;
 
     use crate::backoff_algo;
@@ -72,6 +632,39 @@ mod tests {
         assert_eq!(bfa.get(), 32);
     }
     #[test]
+    fn builder() {
+        use backoff_algo::{BackoffAlgorithm, Jitter};
+        let bfa = BackoffAlgorithm::builder()
+            .base(100)
+            .max(800)
+            .min(50)
+            .multiplier(3.0)
+            .jitter(Jitter::Full(7))
+            .build();
+        assert_eq!(
+            bfa,
+            BackoffAlgorithm::new(100, 800, Some(7))
+                .with_min(50)
+                .with_multiplier(3.0)
+        );
+
+        let bfa = BackoffAlgorithm::default();
+        assert_eq!(bfa, BackoffAlgorithm::new(1000, 32000, None));
+        assert_eq!(
+            bfa.collect::<Vec<_>>(),
+            [1000, 2000, 4000, 8000, 16000, 32000]
+        );
+
+        let mut bfa = BackoffAlgorithm::builder().max_attempts(2).build();
+        assert_eq!(bfa.max_attempts(), Some(2));
+        assert_eq!(bfa.next(), Some(1000));
+        assert_eq!(bfa.next(), Some(2000));
+        assert_eq!(bfa.next(), None);
+        // Resetting gives the attempts back.
+        bfa.reset();
+        assert_eq!(bfa.next(), Some(1000));
+    }
+    #[test]
     fn next_with_random_test() {
         let mut bfa = backoff_algo::BackoffAlgorithm::new(8, 64, random());
         println!("{}", bfa.get());
@@ -82,4 +675,247 @@ mod tests {
         assert!(bfa.next() <= Some(64));
         println!("{}", bfa.get());
     }
+    #[test]
+    fn next_with_multiplier_test() {
+        let bfa = backoff_algo::BackoffAlgorithm::new(1, 16, None).with_multiplier(2.0);
+        assert_eq!(bfa.collect::<Vec<_>>(), [1, 2, 4, 8, 16]);
+        let bfa = backoff_algo::BackoffAlgorithm::new(1, 100, None).with_multiplier(3.0);
+        assert_eq!(bfa.collect::<Vec<_>>(), [1, 3, 9, 27, 81]);
+    }
+    #[test]
+    fn next_with_many_attempts_test() {
+        // With the 1ms minimum growth, a million attempts before the end.
+        let mut bfa = backoff_algo::BackoffAlgorithm::builder()
+            .base(1)
+            .max(1_000_000)
+            .multiplier(1.0)
+            .build();
+        let mut last = 0;
+        for expected in 1..=1_000_000 {
+            last = bfa.next().unwrap();
+            assert_eq!(last, expected);
+        }
+        assert_eq!((last, bfa.next()), (1_000_000, None));
+        assert_eq!(bfa.attempts_done(), 1_000_000);
+
+        // Bases past what an f32 holds exactly still grow exactly.
+        let bfa = backoff_algo::BackoffAlgorithm::new(16_777_217, usize::MAX, None);
+        assert_eq!(bfa.take(2).collect::<Vec<_>>(), [16_777_217, 33_554_434]);
+
+        // A huge multiplier is clamped to `max`, then ends the backoff.
+        let bfa = backoff_algo::BackoffAlgorithm::new(3, 4, Some(2)).with_multiplier(1e300);
+        assert_eq!(bfa.collect::<Vec<_>>(), [4]);
+        let mut bfa =
+            backoff_algo::BackoffAlgorithm::new(3, usize::MAX - 1, None).with_multiplier(1e300);
+        assert_eq!(bfa.next(), Some(3));
+        assert_eq!(bfa.next(), None);
+    }
+    #[test]
+    fn next_with_min_test() {
+        let bfa = backoff_algo::BackoffAlgorithm::new(1, 16, None).with_min(5);
+        assert_eq!(bfa.collect::<Vec<_>>(), [5, 5, 5, 8, 16]);
+    }
+    #[test]
+    fn next_with_random_capped_test() {
+        let mut bfa = backoff_algo::BackoffAlgorithm::new(8, 20, Some(7));
+        assert_eq!(bfa.next(), Some(15));
+        assert_eq!(bfa.next(), Some(20));
+        assert_eq!(bfa.next(), None);
+    }
+    #[test]
+    fn total_backoff_test() {
+        let mut bfa = backoff_algo::BackoffAlgorithm::new(1, 16, None);
+        assert_eq!(bfa.total_backoff_ms(), 0);
+        while bfa.next().is_some() {}
+        assert_eq!(bfa.attempts_done(), 5);
+        assert_eq!(bfa.total_backoff_ms(), 1 + 2 + 4 + 8 + 16);
+        bfa.reset();
+        assert_eq!(bfa.attempts_done(), 0);
+        assert_eq!(bfa.total_backoff_ms(), 0);
+        assert_eq!(bfa.next(), Some(1));
+        assert_eq!(bfa.total_backoff_ms(), 1);
+    }
+    #[test]
+    fn delays_test() {
+        let delays = backoff_algo::BackoffAlgorithm::new(1, 4, None).delays();
+        let v: Vec<Duration> = delays.collect();
+        assert_eq!(
+            v,
+            [
+                Duration::from_millis(1),
+                Duration::from_millis(2),
+                Duration::from_millis(4)
+            ]
+        );
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn retry_test() {
+        let mut calls = 0;
+        let bfa = backoff_algo::BackoffAlgorithm::new(1, 16, None);
+        let res: Result<&str, usize> = bfa.retry(|| {
+            calls += 1;
+            if calls < 3 {
+                Err(calls)
+            } else {
+                Ok("connected")
+            }
+        });
+        assert_eq!(res, Ok("connected"));
+        assert_eq!(calls, 3);
+    }
+    #[test]
+    fn retry_policy_test() {
+        use backoff_algo::RetryPolicy;
+
+        let mut bfa = backoff_algo::BackoffAlgorithm::new(1, 2, None);
+        assert_eq!(bfa.next_delay(0), Some(Duration::from_millis(1)));
+        assert_eq!(bfa.next_delay(1), Some(Duration::from_millis(2)));
+        assert_eq!(bfa.next_delay(2), None);
+
+        let mut fixed = backoff_algo::FixedDelay::new(Duration::from_millis(5), 1);
+        assert_eq!(fixed.next_delay(0), Some(Duration::from_millis(5)));
+        assert_eq!(fixed.next_delay(1), None);
+
+        let mut fib = backoff_algo::FibonacciBackoff::new(1, 20);
+        let v: Vec<_> = (0..).map_while(|i| fib.next_delay(i)).collect();
+        assert_eq!(v, [1, 1, 2, 3, 5, 8, 13].map(Duration::from_millis));
+        // A zero base would never grow.
+        let mut fib = backoff_algo::FibonacciBackoff::new(0, 20);
+        assert_eq!(fib.next_delay(0), None);
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn retry_with_policy_test() {
+        let mut calls = 0;
+        let mut fib = backoff_algo::FibonacciBackoff::new(1, 2);
+        let res: Result<(), usize> = backoff_algo::retry(&mut fib, || {
+            calls += 1;
+            Err(calls)
+        });
+        // One first attempt, then a retry after each of the 1, 1 and 2ms delays.
+        assert_eq!(res, Err(4));
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_test() {
+        let mut bfa = backoff_algo::BackoffAlgorithm::new(1, 16, Some(0)).with_min(1);
+        bfa.next();
+        bfa.next();
+        let json = serde_json::to_string(&bfa).unwrap();
+        let mut restored: backoff_algo::BackoffAlgorithm = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, bfa);
+        assert_eq!(restored.attempts_done(), 2);
+        assert_eq!(restored.total_backoff_ms(), 3);
+        // The backoff resumes where it was saved.
+        assert_eq!(restored.next(), Some(4));
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn retry_until_deadline_test() {
+        use core::cell::Cell;
+        use std::time::Instant;
+
+        // Real sleeps can overshoot, so only the outcome is checked here.
+        let mut calls = 0;
+        let bfa = backoff_algo::BackoffAlgorithm::new(20, 10_000, None);
+        let deadline = Instant::now() + Duration::from_millis(100);
+        let res: Result<(), usize> = bfa.retry_until(deadline, || {
+            calls += 1;
+            Err(calls)
+        });
+        assert!(res.is_err());
+
+        // Slept for 20, 40 then gave up rather than waiting another 80ms,
+        // long before the attempts ran out.
+        let clock = Cell::new(0u64);
+        let mut calls = 0;
+        let res: Result<(), usize> = backoff_algo::retry_until_with(
+            &mut backoff_algo::BackoffAlgorithm::new(20, 10_000, None),
+            100,
+            || clock.get(),
+            |d| clock.set(clock.get() + d.as_millis() as u64),
+            || {
+                calls += 1;
+                Err(calls)
+            },
+        );
+        assert_eq!(res, Err(3));
+        assert_eq!(clock.get(), 60);
+    }
+    #[test]
+    fn retry_until_with_clock_test() {
+        use core::cell::Cell;
+
+        let clock = Cell::new(0u64);
+        let mut fixed = backoff_algo::FixedDelay::new(Duration::from_millis(10), 3);
+        let mut calls = 0;
+        let res: Result<usize, ()> = backoff_algo::retry_until_with(
+            &mut fixed,
+            1_000,
+            || clock.get(),
+            |d| clock.set(clock.get() + d.as_millis() as u64),
+            || {
+                calls += 1;
+                if calls == 3 {
+                    Ok(calls)
+                } else {
+                    Err(())
+                }
+            },
+        );
+        assert_eq!(res, Ok(3));
+        assert_eq!(clock.get(), 20);
+
+        // A deadline already passed allows the first attempt only.
+        let mut calls = 0;
+        let res: Result<(), ()> = backoff_algo::retry_until_with(
+            &mut fixed,
+            0,
+            || 5,
+            |_| unreachable!(),
+            || {
+                calls += 1;
+                Err(())
+            },
+        );
+        assert_eq!((res, calls), (Err(()), 1));
+    }
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn connect_with_backoff_sleeps_between_attempts() {
+        use backoff_algo::{connect_with_backoff, connect_with_backoff_until, FixedDelay};
+        use tokio::time::Instant;
+
+        let start = Instant::now();
+        let mut attempts = 0;
+        let res: Result<(), ()> = connect_with_backoff(
+            &mut backoff_algo::BackoffAlgorithm::new(100, 400, None),
+            || {
+                attempts += 1;
+                async { Err(()) }
+            },
+        )
+        .await;
+        assert_eq!(res, Err(()));
+        assert_eq!(attempts, 4);
+        // Slept 100, 200 and 400ms on the paused clock.
+        assert_eq!(start.elapsed(), Duration::from_millis(700));
+
+        let start = Instant::now();
+        let mut attempts = 0;
+        let deadline = start + Duration::from_millis(250);
+        let res: Result<(), ()> = connect_with_backoff_until(
+            &mut FixedDelay::new(Duration::from_millis(100), 10),
+            deadline,
+            || {
+                attempts += 1;
+                async { Err(()) }
+            },
+        )
+        .await;
+        assert_eq!(res, Err(()));
+        assert_eq!(attempts, 3);
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+    }
 }
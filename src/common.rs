@@ -1,18 +1,41 @@
+use arrayvec::{ArrayString, ArrayVec};
 use thiserror_no_std::Error;
 
-// Limit imposed by the mqtt spec
+use crate::TopicType;
+
+// Limit imposed by the mqtt spec, in bytes of UTF-8 and inclusive
 pub const MQTT_TOPIC_LENGTH_MAX: usize = 65535;
 
+// AWS limits for names, in bytes and inclusive. The names are ASCII only,
+// so that is also their length in characters.
 pub const THINGNAME_MAX_LENGTH: usize = 128;
 pub const SHADOW_NAME_LENGTH_MAX: usize = 64;
 pub const JOBID_MAX_LENGTH: usize = 64;
+pub const CLIENT_TOKEN_MAX_LENGTH: usize = 64;
+pub(crate) const CLIENT_TOKEN: &str = "clientToken";
+/// The prefix of the topics and names reserved by AWS IoT.
+const RESERVED_PREFIX: &str = "$aws";
+/// The pseudo job id for the next pending job execution.
+pub(crate) const JOBID_NEXT: &str = "$next";
 
+// The fixed parts of the longest topic of each kind (prefix, bridge,
+// operation and suffix) must fit in the extra room on top of the names.
 pub const TUNNEL_TOPIC_MAX_LENGTH: usize = THINGNAME_MAX_LENGTH + 32;
-pub const DEFENDER_TOPIC_MAX_LENGTH: usize = THINGNAME_MAX_LENGTH + 32;
-pub const JOBS_TOPIC_MAX_LENGTH: usize = THINGNAME_MAX_LENGTH + JOBID_MAX_LENGTH + 32;
-pub const SHADOW_TOPIC_MAX_LENGTH: usize = THINGNAME_MAX_LENGTH + SHADOW_NAME_LENGTH_MAX + 32;
+pub const DEFENDER_TOPIC_MAX_LENGTH: usize = THINGNAME_MAX_LENGTH + 64;
+pub const JOBS_TOPIC_MAX_LENGTH: usize = THINGNAME_MAX_LENGTH + JOBID_MAX_LENGTH + 64;
+pub const SHADOW_TOPIC_MAX_LENGTH: usize = THINGNAME_MAX_LENGTH + SHADOW_NAME_LENGTH_MAX + 64;
+
+// `{"clientToken":""}` around the longest token.
+pub const TOKEN_PAYLOAD_MAX_LENGTH: usize = CLIENT_TOKEN_MAX_LENGTH + 18;
 
+pub const SHARE_NAME_MAX_LENGTH: usize = 128;
+pub const SHARED_FILTER_MAX_LENGTH: usize = SHARE_NAME_MAX_LENGTH + SHADOW_TOPIC_MAX_LENGTH + 8;
+
+pub const AWS_RESERVED_PREFIX: &str = "$aws/";
 pub const AWS_THINGS_PREFIX: &str = "$aws/things/";
+pub const AWS_RULES_PREFIX: &str = "$aws/rules/";
+pub const AWS_EVENTS_PREFIX: &str = "$aws/events/";
+pub const SHARED_SUBSCRIPTION_PREFIX: &str = "$share/";
 
 pub const DEFENDER_API_BRIDGE: &str = "/defender/metrics/";
 pub const JOBS_API_BRIDGE: &str = "/jobs/";
@@ -22,16 +45,36 @@ pub const TUNNELS_API_BRIDGE: &str = "/tunnels/";
 
 pub const SUFFIX_ACCEPTED: &str = "/accepted";
 pub const SUFFIX_REJECTED: &str = "/rejected";
+pub const SUFFIX_WILDCARD: &str = "/+";
 
 pub const ACCEPTED: &str = "accepted";
 pub const REJECTED: &str = "rejected";
 
-#[derive(Error, Debug)]
+/// The errors of the whole crate.
+///
+/// New variants may be added in a minor release, so a `match` on it needs a
+/// `_ =>` arm. [`Error::kind`] groups the variants for callers that only
+/// care whether the input was garbage or meant for another subsystem.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{shadow, Error};
+///
+/// match shadow::match_topic("$aws/things/chloe/jobs/notify") {
+///     Ok(shadow) => println!("{:?}", shadow.shadow_op),
+///     Err(Error::NoMatch | Error::RootParseFailed) => {}
+///     Err(err) => println!("dropped a malformed topic: {err}"),
+/// }
+/// ```
+#[derive(Error, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     #[error("function encountered error.")]
     FAIL,
     #[error("Input mqtt topic is invalid.")]
     MqttTopicFailed,
+    #[error("The mqtt topic is longer than 65535 bytes.")]
+    MqttTopicTooLong,
     #[error("Could not parse the thing name.")]
     ThingnameParseFailed,
     #[error("Could not parse the type.")]
@@ -44,15 +87,641 @@ pub enum Error {
     JobsIdParseFailed,
     #[error("The provided topic does not match any defender topic.")]
     NoMatch,
+    #[error("The topic is not a well-formed AWS IoT topic.")]
+    MalformedTopic,
+    #[error("Could not parse the message payload.")]
+    PayloadParseFailed,
+    #[error("Could not parse the share name of a shared subscription.")]
+    ShareNameParseFailed,
+    #[error("Could not parse the client token.")]
+    ClientTokenParseFailed,
+    #[error("Could not parse the AWS region.")]
+    RegionParseFailed,
+    #[error("The name is reserved by AWS IoT.")]
+    ReservedName,
+    #[error("The payload is not in the expected JSON or CBOR encoding.")]
+    FormatMismatch,
+    #[error("The Device Defender report is invalid: {0}.")]
+    InvalidReport(ReportError),
+}
+
+/// Why [`Report::validate`](crate::defender::Report::validate) rejected a
+/// Device Defender report.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReportError {
+    #[error("the header has no version")]
+    MissingVersion,
+    #[error("the report has no metric section")]
+    NoMetrics,
+    #[error("a port is 0")]
+    ZeroPort,
+    #[error("a network counter exceeds i64::MAX")]
+    CounterOverflow,
+    #[error("an interface or an address is empty or needs JSON escapes")]
+    InvalidString,
+}
+
+/// Coarse groups of [`Error`] variants.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorKind {
+    /// A name, topic or buffer failed validation.
+    Validation,
+    /// The topic or payload could not be parsed.
+    Parse,
+    /// The topic is well-formed but belongs to another subsystem.
+    NoMatch,
+}
+
+impl Error {
+    /// The group this error belongs to.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::{jobs, ErrorKind};
+    ///
+    /// let err = jobs::match_topic("$aws/things/chloe/shadow/get").err().unwrap();
+    /// assert_eq!(err.kind(), ErrorKind::NoMatch);
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::FAIL
+            | Error::MqttTopicFailed
+            | Error::MqttTopicTooLong
+            | Error::ThingnameParseFailed
+            | Error::ShadownameParseFailed
+            | Error::JobsIdParseFailed
+            | Error::ShareNameParseFailed
+            | Error::ClientTokenParseFailed
+            | Error::RegionParseFailed
+            | Error::ReservedName
+            | Error::InvalidReport(_) => ErrorKind::Validation,
+            Error::MessageTypeParseFailed
+            | Error::MalformedTopic
+            | Error::PayloadParseFailed
+            | Error::FormatMismatch => ErrorKind::Parse,
+            Error::RootParseFailed | Error::NoMatch => ErrorKind::NoMatch,
+        }
+    }
+    /// The input is garbage, as opposed to valid but not for this subsystem.
+    pub fn is_malformed(&self) -> bool {
+        self.kind() != ErrorKind::NoMatch
+    }
+    /// The input is valid but not for this subsystem.
+    pub fn is_no_match(&self) -> bool {
+        self.kind() == ErrorKind::NoMatch
+    }
+}
+
+impl From<&Error> for ErrorKind {
+    fn from(err: &Error) -> ErrorKind {
+        err.kind()
+    }
+}
+
+/// Build the topic of a given kind for a thing, implemented by the `Topic`
+/// enum of every subsystem so that generic code can build any topic.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{defender, jobs, shadow, TopicBuilder};
+///
+/// fn subscribe_all<B: TopicBuilder>(topics: &[B], thing_name: &str) -> usize {
+///     topics.iter().filter(|t| t.topic(thing_name).is_ok()).count()
+/// }
+///
+/// assert_eq!(subscribe_all(&[shadow::Topic::UpdateDelta], "chloe"), 1);
+/// assert_eq!(subscribe_all(&[jobs::Topic::JobsChanged, jobs::Topic::NextJobChanged], "chloe"), 2);
+/// let topic = defender::Topic::JsonReportAccepted.topic("chloe").unwrap();
+/// assert_eq!(&topic[..], "$aws/things/chloe/defender/metrics/json/accepted");
+/// ```
+pub trait TopicBuilder {
+    /// The built topic, a string of a capacity fitting the subsystem.
+    type Output: core::ops::Deref<Target = str>;
+
+    /// Populate the topic for `thing_name`, like the `assemble_topic` of
+    /// the subsystem.
+    fn topic(&self, thing_name: &str) -> Result<Self::Output, Error>;
+}
+
+/// Match an incoming topic, implemented by the result struct of every
+/// subsystem, like [`ThingShadow`](crate::shadow::ThingShadow).
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{jobs::ThingJobs, shadow::ThingShadow, TopicMatcher};
+///
+/// fn count<'a, M: TopicMatcher<'a>>(topics: &[&'a str]) -> usize {
+///     topics.iter().filter(|t| M::match_topic(t).is_ok()).count()
+/// }
+///
+/// let topics = ["$aws/things/chloe/shadow/get", "$aws/things/chloe/jobs/notify"];
+/// assert_eq!(count::<ThingShadow>(&topics), 1);
+/// assert_eq!(count::<ThingJobs>(&topics), 1);
+/// ```
+pub trait TopicMatcher<'a>: Sized {
+    /// Same as the `match_topic` of the subsystem.
+    fn match_topic(topic: &'a str) -> Result<Self, Error>;
+}
+
+/// Which way a topic flows, seen from the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopicDirection {
+    /// Published by the device, like `shadow/update`.
+    Request,
+    /// Published by AWS IoT in reply to a request, like
+    /// `shadow/update/accepted`.
+    Response,
+    /// Published by AWS IoT on its own, like `shadow/update/delta` or
+    /// `jobs/notify`.
+    Notification,
+}
+
+///
+/// Classify a device topic by direction, across every subsystem.
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{topic_direction, TopicDirection};
+///
+/// let direction = topic_direction("$aws/things/chloe/shadow/name/common/update/delta");
+/// assert_eq!(direction, Ok(TopicDirection::Notification));
+/// let direction = topic_direction("$aws/things/chloe/jobs/start-next");
+/// assert_eq!(direction, Ok(TopicDirection::Request));
+/// ```
+pub fn topic_direction(topic: &str) -> Result<TopicDirection, Error> {
+    match crate::match_topic_type(topic)? {
+        TopicType::Shadow | TopicType::NamedShadow => {
+            Ok(crate::shadow::match_topic(topic)?.shadow_op.direction())
+        }
+        TopicType::Jobs => Ok(crate::jobs::match_topic(topic)?.api.direction()),
+        TopicType::Defender => Ok(crate::defender::match_topic(topic)?.api.direction()),
+        TopicType::Tunneling => {
+            crate::tunneling::match_topic(topic)?;
+            Ok(TopicDirection::Notification)
+        }
+        TopicType::Other => Err(Error::NoMatch),
+    }
+}
+
+/// The encoding of a message payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    Json,
+    Cbor,
+}
+
+/// Guess the encoding of a payload from its first byte which isn't
+/// whitespace: `{` or `[` is JSON, anything else CBOR.
+///
+/// This is a heuristic for handlers receiving both encodings, which holds
+/// for Device Defender reports and service responses as they are always an
+/// object. It would take a JSON scalar like `"on"` or `42`, or an empty
+/// payload, for CBOR, and a CBOR payload starting with the bytes of `{` or
+/// `[` (`0x7b`, a text string, or `0x5b`, a byte string) for JSON.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{detect_format, PayloadFormat};
+///
+/// assert_eq!(detect_format(br#" {"state":{}}"#), PayloadFormat::Json);
+/// assert_eq!(detect_format(&[0xa1, 0x61, 0x61, 0x01]), PayloadFormat::Cbor);
+/// ```
+pub fn detect_format(payload: &[u8]) -> PayloadFormat {
+    match payload.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{' | b'[') => PayloadFormat::Json,
+        _ => PayloadFormat::Cbor,
+    }
+}
+
+/// The QoS level to use when subscribing to, or publishing on, a topic of
+/// the given type.
+///
+/// AWS IoT Core supports QoS 0 and 1 only
+/// (<https://docs.aws.amazon.com/iot/latest/developerguide/mqtt.html#mqtt-qos>).
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{recommended_qos, TopicType};
+///
+/// assert_eq!(recommended_qos(&TopicType::Shadow), 1);
+/// ```
+pub fn recommended_qos(topic_type: &TopicType) -> u8 {
+    match topic_type {
+        // The shadow service only publishes a delta once, a device that
+        // misses it stays out of sync until the next update, so use QoS 1.
+        TopicType::Shadow | TopicType::NamedShadow => 1,
+        // Job notifications are not re-sent either, and a missed
+        // notify-next leaves a job queued, so use QoS 1.
+        TopicType::Jobs => 1,
+        // A tunnel notification carries the access tokens, which are only
+        // delivered once, so use QoS 1.
+        TopicType::Tunneling => 1,
+        // Defender reports are sent periodically and the next one supersedes
+        // a lost one, so QoS 0 is enough.
+        TopicType::Defender => 0,
+        TopicType::Other => 0,
+    }
+}
+
+/// The error codes AWS IoT puts in the `code` of a rejected shadow response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceErrorCode {
+    /// 400, the request or its document is invalid.
+    BadRequest,
+    /// 401, the client isn't authenticated.
+    Unauthorized,
+    /// 403, the policy of the client doesn't allow the request.
+    Forbidden,
+    /// 404, the thing or the shadow doesn't exist.
+    NotFound,
+    /// 409, the `version` of the request is not the current one.
+    VersionConflict,
+    /// 413, the payload exceeds the maximum size.
+    PayloadTooLarge,
+    /// 415, the document isn't UTF-8 encoded JSON.
+    UnsupportedMediaType,
+    /// 422, the request is well-formed but can't be processed.
+    UnprocessableEntity,
+    /// 429, too many requests are in flight.
+    TooManyRequests,
+    /// 500, the service failed.
+    InternalError,
+}
+
+impl ServiceErrorCode {
+    /// Map a numeric code, `None` for a code not listed above.
+    pub fn from_u32(code: u32) -> Option<Self> {
+        match code {
+            400 => Some(ServiceErrorCode::BadRequest),
+            401 => Some(ServiceErrorCode::Unauthorized),
+            403 => Some(ServiceErrorCode::Forbidden),
+            404 => Some(ServiceErrorCode::NotFound),
+            409 => Some(ServiceErrorCode::VersionConflict),
+            413 => Some(ServiceErrorCode::PayloadTooLarge),
+            415 => Some(ServiceErrorCode::UnsupportedMediaType),
+            422 => Some(ServiceErrorCode::UnprocessableEntity),
+            429 => Some(ServiceErrorCode::TooManyRequests),
+            500 => Some(ServiceErrorCode::InternalError),
+            _ => None,
+        }
+    }
+    pub fn as_u32(&self) -> u32 {
+        match self {
+            ServiceErrorCode::BadRequest => 400,
+            ServiceErrorCode::Unauthorized => 401,
+            ServiceErrorCode::Forbidden => 403,
+            ServiceErrorCode::NotFound => 404,
+            ServiceErrorCode::VersionConflict => 409,
+            ServiceErrorCode::PayloadTooLarge => 413,
+            ServiceErrorCode::UnsupportedMediaType => 415,
+            ServiceErrorCode::UnprocessableEntity => 422,
+            ServiceErrorCode::TooManyRequests => 429,
+            ServiceErrorCode::InternalError => 500,
+        }
+    }
+    /// Whether the same request may succeed when sent again after a
+    /// backoff. A version conflict isn't retryable as is, the shadow has to
+    /// be fetched again first; the other codes need the request or the
+    /// device setup to change.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::ServiceErrorCode;
+    ///
+    /// assert!(ServiceErrorCode::from_u32(429).unwrap().retryable());
+    /// assert!(!ServiceErrorCode::from_u32(403).unwrap().retryable());
+    /// ```
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            ServiceErrorCode::TooManyRequests | ServiceErrorCode::InternalError
+        )
+    }
+}
+
+///
+/// valid client token?
+/// Client tokens are echoed back in the responses to match them with their
+/// request. AWS IoT allows up to 64 bytes, and payload builders write them
+/// unescaped.
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::is_valid_client_token;
+///
+/// assert!(is_valid_client_token("request-0001").is_ok());
+/// assert!(is_valid_client_token("say \"hi\"").is_err());
+/// ```
+pub fn is_valid_client_token(client_token: &str) -> Result<(), Error> {
+    if client_token.len() > CLIENT_TOKEN_MAX_LENGTH {
+        return Err(Error::ClientTokenParseFailed);
+    }
+    is_valid_json_str(client_token).map_err(|_| Error::ClientTokenParseFailed)
+}
+
+/// A payload carrying nothing but an optional client token, `{}` or
+/// `{"clientToken":"..."}`.
+pub(crate) fn token_payload(
+    client_token: Option<&str>,
+) -> Result<ArrayString<TOKEN_PAYLOAD_MAX_LENGTH>, Error> {
+    let mut s = ArrayString::new();
+    match client_token {
+        None => push_str(&mut s, "{}")?,
+        Some(token) => {
+            is_valid_client_token(token)?;
+            push_str(&mut s, r#"{"clientToken":""#)?;
+            push_str(&mut s, token)?;
+            push_str(&mut s, r#""}"#)?;
+        }
+    }
+    Ok(s)
+}
+
+///
+/// Whether the `clientToken` of a response payload is `expected`, to pair
+/// the response with its request when the responses of several requests
+/// arrive on the same topic.
+///
+/// Only the top-level member is compared, so a payload without a token, or
+/// with a token written with JSON escapes, never matches.
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::client_token_matches;
+///
+/// let payload = br#"{"state":{"reported":{"color":"red"}},"clientToken":"token-1","version":2}"#;
+/// assert!(client_token_matches(payload, "token-1"));
+/// assert!(!client_token_matches(payload, "token-2"));
+/// ```
+pub fn client_token_matches(payload: &[u8], expected: &str) -> bool {
+    json_str_field(payload, CLIENT_TOKEN) == Some(expected)
+}
+
+/// The MQTT5 properties of an incoming message, as handed over by the MQTT
+/// client, for the parsers which take them alongside the topic and the
+/// payload. Everything is borrowed, and a default context means an MQTT
+/// 3.1.1 message.
+///
+/// The AWS IoT services correlate their responses through the
+/// `clientToken` of the payload, whatever the protocol version; the
+/// correlation data is only set when the requester set it, e.g. on a
+/// request published through the MQTT5 request/response pattern.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::MessageContext;
+///
+/// let properties = [("source", "gateway-1")];
+/// let context = MessageContext {
+///     correlation_data: Some(b"token-1"),
+///     user_properties: &properties,
+///     ..Default::default()
+/// };
+/// assert_eq!(context.user_property("source"), Some("gateway-1"));
+/// assert!(context.correlates(br#"{"version":2}"#, "token-1"));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MessageContext<'a> {
+    pub correlation_data: Option<&'a [u8]>,
+    pub response_topic: Option<&'a str>,
+    /// The user properties, in the order of the message; a key may repeat.
+    pub user_properties: &'a [(&'a str, &'a str)],
+}
+
+impl<'a> MessageContext<'a> {
+    /// The value of the first user property named `key`.
+    pub fn user_property(&self, key: &str) -> Option<&'a str> {
+        self.user_properties
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+    }
+    /// Whether the message answers the request made with `client_token`:
+    /// its correlation data is the token, or else the `clientToken` of the
+    /// payload is, as with [`client_token_matches`].
+    pub fn correlates(&self, payload: &[u8], client_token: &str) -> bool {
+        match self.correlation_data {
+            Some(data) => data == client_token.as_bytes(),
+            None => client_token_matches(payload, client_token),
+        }
+    }
+}
+
+/// Payload builders write strings as is, so they must not need escaping.
+pub(crate) fn is_valid_json_str(s: &str) -> Result<(), Error> {
+    if s.is_empty() || s.chars().any(|c| c == '"' || c == '\\' || c.is_control()) {
+        return Err(Error::FAIL);
+    }
+    Ok(())
+}
+
+/// Find the member `key` of the JSON object in `json`, and return the raw
+/// bytes of its value, e.g. `{"a":1}` for the member `"doc":{"a":1}`.
+///
+/// Only the members of the outer object are compared, nested values are
+/// skipped, which only needs a scan over the bytes rather than a full parse.
+/// Keys written with JSON escapes never match.
+pub(crate) fn json_field<'a>(json: &'a [u8], key: &str) -> Result<Option<&'a [u8]>, Error> {
+    let mut scanner = JsonScanner { json, pos: 0 };
+    scanner.expect(b'{')?;
+    if scanner.peek() == Some(b'}') {
+        return Ok(None);
+    }
+    loop {
+        let name = scanner.string()?;
+        scanner.expect(b':')?;
+        let value = scanner.value()?;
+        if name == key.as_bytes() {
+            return Ok(Some(value));
+        }
+        match scanner.next() {
+            Some(b',') => continue,
+            Some(b'}') => return Ok(None),
+            _ => return Err(Error::PayloadParseFailed),
+        }
+    }
+}
+
+/// Same as [`json_field`] for a string member, without the quotes. `None`
+/// if the member is missing or not a string, or if it holds JSON escapes.
+pub(crate) fn json_str_field<'a>(json: &'a [u8], key: &str) -> Option<&'a str> {
+    let value = json_field(json, key).ok()??;
+    let s = value.strip_prefix(b"\"")?.strip_suffix(b"\"")?;
+    if s.contains(&b'\\') {
+        return None;
+    }
+    core::str::from_utf8(s).ok()
+}
+
+struct JsonScanner<'a> {
+    json: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> JsonScanner<'a> {
+    /// The next byte which isn't whitespace, without consuming it.
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.json.get(self.pos) {
+            self.pos += 1;
+        }
+        self.json.get(self.pos).copied()
+    }
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Some(b)
+    }
+    fn expect(&mut self, b: u8) -> Result<(), Error> {
+        match self.next() {
+            Some(c) if c == b => Ok(()),
+            _ => Err(Error::PayloadParseFailed),
+        }
+    }
+    /// The raw content of a string, between the quotes.
+    fn string(&mut self) -> Result<&'a [u8], Error> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        self.skip_string()?;
+        Ok(&self.json[start..self.pos - 1])
+    }
+    /// Move past the closing quote of a string whose opening quote has
+    /// been consumed.
+    fn skip_string(&mut self) -> Result<(), Error> {
+        loop {
+            match self.json.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some(b'\\') => self.pos += 2,
+                Some(_) => self.pos += 1,
+                None => return Err(Error::PayloadParseFailed),
+            }
+        }
+    }
+    /// The raw bytes of any value.
+    fn value(&mut self) -> Result<&'a [u8], Error> {
+        let first = self.peek().ok_or(Error::PayloadParseFailed)?;
+        let start = self.pos;
+        self.pos += 1;
+        match first {
+            b'"' => self.skip_string()?,
+            b'{' | b'[' => {
+                let mut depth = 1usize;
+                while depth > 0 {
+                    match self.json.get(self.pos) {
+                        Some(b'"') => {
+                            self.pos += 1;
+                            self.skip_string()?;
+                            continue;
+                        }
+                        Some(b'{' | b'[') => depth += 1,
+                        Some(b'}' | b']') => depth -= 1,
+                        Some(_) => {}
+                        None => return Err(Error::PayloadParseFailed),
+                    }
+                    self.pos += 1;
+                }
+            }
+            b',' | b':' | b'}' | b']' => return Err(Error::PayloadParseFailed),
+            // Numbers, true, false and null run up to the next delimiter.
+            _ => {
+                while let Some(b) = self.json.get(self.pos) {
+                    if matches!(b, b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r') {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            }
+        }
+        Ok(&self.json[start..self.pos])
+    }
+}
+
+/// Borrow a raw MQTT topic as `&str`, validating UTF-8 once.
+pub(crate) fn topic_from_bytes(topic: &[u8]) -> Result<&str, Error> {
+    core::str::from_utf8(topic).map_err(|_| Error::MqttTopicFailed)
+}
+
+/// Format an integer as decimal digits, without going through `core::fmt`.
+pub(crate) fn u64_to_str(mut n: u64) -> ArrayString<20> {
+    // u64::MAX has 20 digits
+    let mut digits = [0u8; 20];
+    let mut i = digits.len();
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    let mut s = ArrayString::new();
+    for &d in &digits[i..] {
+        s.push(d as char);
+    }
+    s
+}
+
+/// Append `part` to a topic, failing with `Error::FAIL` instead of
+/// panicking when it doesn't fit.
+pub(crate) fn push_str<B: TopicBuf>(s: &mut B, part: &str) -> Result<(), Error> {
+    s.push_part(part)
+}
+
+/// A string the builders can assemble a topic in: a fixed capacity
+/// `ArrayString`, or a `String` with the `std` feature.
+pub(crate) trait TopicBuf: Default {
+    fn push_part(&mut self, part: &str) -> Result<(), Error>;
+}
+
+impl<const N: usize> TopicBuf for ArrayString<N> {
+    fn push_part(&mut self, part: &str) -> Result<(), Error> {
+        self.try_push_str(part).map_err(|_| Error::FAIL)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TopicBuf for String {
+    fn push_part(&mut self, part: &str) -> Result<(), Error> {
+        self.push_str(part);
+        Ok(())
+    }
+}
+
+/// Deserialize a topic enum from its canonical name, e.g. `"update/delta"`.
+#[cfg(feature = "serde")]
+pub(crate) fn deserialize_name<'de, D, T>(
+    deserializer: D,
+    from_name: fn(&str) -> Result<T, Error>,
+) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct NameVisitor<T>(fn(&str) -> Result<T, Error>);
+
+    impl<T> serde::de::Visitor<'_> for NameVisitor<T> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            f.write_str("a topic name")
+        }
+
+        fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<T, E> {
+            (self.0)(v).map_err(|_| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+        }
+    }
+
+    deserializer.deserialize_str(NameVisitor(from_name))
 }
 
 /// valid parameters?
 ///
+/// `max_len` is inclusive and in bytes, not in chars.
 /// # Example
 /// ```
 /// ```
+#[inline]
 fn is_valid_param(s: &str, max_len: usize) -> Result<(), Error> {
-    if !s.is_empty() && s.len() < max_len {
+    if !s.is_empty() && s.len() <= max_len {
         return Ok(());
     }
     Err(Error::FAIL)
@@ -63,8 +732,44 @@ fn is_valid_param(s: &str, max_len: usize) -> Result<(), Error> {
 /// # Example
 /// ```
 /// ```
+#[inline]
 pub fn is_valid_mqtt_topic(mqtt_topic: &str) -> Result<(), Error> {
-    is_valid_param(mqtt_topic, MQTT_TOPIC_LENGTH_MAX).map_err(|_| Error::MqttTopicFailed)
+    // str::len is the length in bytes, as limited by the spec.
+    if mqtt_topic.len() > MQTT_TOPIC_LENGTH_MAX {
+        return Err(Error::MqttTopicTooLong);
+    }
+    if mqtt_topic.is_empty() {
+        return Err(Error::MqttTopicFailed);
+    }
+    Ok(())
+}
+
+///
+/// reserved aws topic?
+/// Every topic under `$aws/` is reserved by AWS IoT, which includes the
+/// `$aws/things/`, `$aws/rules/` and `$aws/events/` trees.
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::is_reserved_topic;
+///
+/// assert!(is_reserved_topic("$aws/rules/my_rule/telemetry"));
+/// assert!(!is_reserved_topic("my/telemetry"));
+/// ```
+pub fn is_reserved_topic(topic: &str) -> bool {
+    topic.starts_with(AWS_RESERVED_PREFIX)
+}
+
+///
+/// topic under `$aws/things/`?
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::is_aws_things_topic;
+///
+/// assert!(is_aws_things_topic("$aws/things/chloe/shadow/get"));
+/// assert!(!is_aws_things_topic("$aws/events/presence/connected/chloe"));
+/// ```
+pub fn is_aws_things_topic(topic: &str) -> bool {
+    topic.starts_with(AWS_THINGS_PREFIX)
 }
 
 ///
@@ -81,33 +786,414 @@ pub fn is_valid_prefix<'a>(s: &'a str, pre: &str) -> Result<&'a str, Error> {
 /// # Example
 /// ```
 /// ```
-fn is_valid_name(name: &str, len: usize) -> Result<(), Error> {
-    is_valid_param(name, len)?;
-    for a in name.chars() {
-        match a {
-            '-' | '_' | '0'..='9' | 'A'..='Z' | 'a'..='z' | ':' => continue,
-            _ => return Err(Error::FAIL),
+#[inline]
+pub(crate) fn is_valid_name(name: &str, len: usize) -> Result<(), Error> {
+    NamePolicy {
+        max_length: len,
+        ..NamePolicy::THING_NAME
+    }
+    .check(name)
+}
+
+/// The characters and length allowed in a kind of name, for deployments
+/// with naming conventions stricter than AWS IoT's.
+///
+/// The associated constants are the AWS rules, which the plain validators
+/// like [`is_valid_thing_name`] use. A policy allowing more than AWS lets
+/// through names that AWS IoT rejects.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{is_valid_thing_name_with, NamePolicy};
+///
+/// // Lowercase fleet names, e.g. `sensor-0042`.
+/// let policy = NamePolicy {
+///     max_length: 32,
+///     allowed: |b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'-'),
+/// };
+/// assert!(is_valid_thing_name_with(&policy, "sensor-0042").is_ok());
+/// assert!(is_valid_thing_name_with(&policy, "Sensor_42").is_err());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct NamePolicy {
+    /// The maximum length, inclusive and in bytes.
+    pub max_length: usize,
+    /// Whether a byte is allowed in a name. Bytes of multi-byte UTF-8
+    /// characters are checked one by one.
+    pub allowed: fn(u8) -> bool,
+}
+
+impl NamePolicy {
+    /// Thing names: `[a-zA-Z0-9:_-]`, up to 128 bytes.
+    pub const THING_NAME: Self = Self {
+        max_length: THINGNAME_MAX_LENGTH,
+        allowed: is_name_byte,
+    };
+    /// Shadow names: `[a-zA-Z0-9:_-]`, up to 64 bytes.
+    pub const SHADOW_NAME: Self = Self {
+        max_length: SHADOW_NAME_LENGTH_MAX,
+        allowed: is_name_byte,
+    };
+    /// Job ids: `[a-zA-Z0-9_-]`, up to 64 bytes.
+    pub const JOB_ID: Self = Self {
+        max_length: JOBID_MAX_LENGTH,
+        allowed: is_job_id_byte,
+    };
+
+    /// Check `name` against the policy, failing with `Error::FAIL`.
+    pub fn check(&self, name: &str) -> Result<(), Error> {
+        is_valid_param(name, self.max_length)?;
+        if !name.bytes().all(self.allowed) {
+            return Err(Error::FAIL);
         }
+        Ok(())
     }
-    Ok(())
+}
+
+fn is_name_byte(b: u8) -> bool {
+    is_job_id_byte(b) || b == b':'
+}
+
+fn is_job_id_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'-' || b == b'_'
 }
 
 ///
 /// valid aws iot thing name?
+/// Names starting with `$aws` are reserved by AWS IoT and fail with
+/// `Error::ReservedName` rather than `Error::ThingnameParseFailed`, so that
+/// a thing name can never be mistaken for a part of the topic prefix.
 /// # Example
 /// ```
+/// use aws_iot_device_sdk::{is_valid_thing_name, Error};
+///
+/// assert!(is_valid_thing_name("chloe").is_ok());
+/// assert_eq!(is_valid_thing_name("things/chloe"), Err(Error::ThingnameParseFailed));
+/// assert_eq!(is_valid_thing_name("$aws"), Err(Error::ReservedName));
 /// ```
+#[inline]
 pub fn is_valid_thing_name(thing_name: &str) -> Result<(), Error> {
-    is_valid_name(thing_name, THINGNAME_MAX_LENGTH).map_err(|_| Error::ThingnameParseFailed)
+    is_valid_thing_name_with(&NamePolicy::THING_NAME, thing_name)
+}
+
+/// Same as [`is_valid_thing_name`], against `policy` rather than the AWS
+/// rules. Names starting with `$aws` are reserved whatever the policy.
+pub fn is_valid_thing_name_with(policy: &NamePolicy, thing_name: &str) -> Result<(), Error> {
+    if thing_name.starts_with(RESERVED_PREFIX) {
+        return Err(Error::ReservedName);
+    }
+    policy
+        .check(thing_name)
+        .map_err(|_| Error::ThingnameParseFailed)
 }
 
 ///
 /// valid aws iot shadow name?
+/// Names starting with `$` are reserved by AWS IoT, like the `$package`
+/// shadow of the software package catalog, and are valid as well.
 /// # Example
 /// ```
+/// use aws_iot_device_sdk::is_valid_shadow_name;
+///
+/// assert!(is_valid_shadow_name("common").is_ok());
+/// assert!(is_valid_shadow_name("$package").is_ok());
+/// assert!(is_valid_shadow_name("$").is_err());
 /// ```
+#[inline]
 pub fn is_valid_shadow_name(shadow_name: &str) -> Result<(), Error> {
-    is_valid_name(shadow_name, SHADOW_NAME_LENGTH_MAX).map_err(|_| Error::ShadownameParseFailed)
+    is_valid_shadow_name_with(&NamePolicy::SHADOW_NAME, shadow_name)
+}
+
+/// Same as [`is_valid_shadow_name`], against `policy` rather than the AWS
+/// rules. A leading `$` is still allowed, and counts towards the length.
+pub fn is_valid_shadow_name_with(policy: &NamePolicy, shadow_name: &str) -> Result<(), Error> {
+    let name = shadow_name.strip_prefix('$').unwrap_or(shadow_name);
+    // The length limit covers the `$` as well.
+    let policy = NamePolicy {
+        max_length: policy
+            .max_length
+            .saturating_sub(shadow_name.len() - name.len()),
+        ..*policy
+    };
+    policy.check(name).map_err(|_| Error::ShadownameParseFailed)
+}
+
+/// The layout of the thing topics, for the `_with` builders and matchers.
+///
+/// The default matches AWS IoT in every partition, including GovCloud and
+/// China. Only change it for a broker or bridge which remaps the
+/// `$aws/things/` prefix. Topic capacities are sized for the standard
+/// prefix, so builders fail with `Error::FAIL` if a longer one doesn't fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopicConfig<'a> {
+    /// The prefix in front of the thing name, ending with `/`.
+    pub things_prefix: &'a str,
+}
+
+impl Default for TopicConfig<'_> {
+    fn default() -> Self {
+        TopicConfig {
+            things_prefix: AWS_THINGS_PREFIX,
+        }
+    }
+}
+
+impl<'a> TopicConfig<'a> {
+    ///
+    /// Use a custom thing prefix, which must end with `/` and contain no
+    /// wildcards.
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::{shadow, TopicConfig};
+    ///
+    /// let config = TopicConfig::new("bridge/things/").unwrap();
+    /// let topic = shadow::assemble_topic_with(&config, shadow::Topic::Get, "chloe", None).unwrap();
+    /// assert_eq!(&topic[..], "bridge/things/chloe/shadow/get");
+    ///
+    /// assert!(TopicConfig::new("bridge/things").is_err());
+    /// ```
+    pub fn new(things_prefix: &'a str) -> Result<Self, Error> {
+        is_valid_mqtt_topic(things_prefix)?;
+        if !things_prefix.ends_with('/') || things_prefix.contains(['+', '#']) {
+            return Err(Error::MqttTopicFailed);
+        }
+        Ok(TopicConfig { things_prefix })
+    }
+}
+
+///
+/// Split a `$aws/things/<thingName>/...` topic into the thing name and the
+/// rest of the topic, which starts at the `/` following the thing name.
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::parse_thing_prefix;
+///
+/// let (thing_name, rest) = parse_thing_prefix("$aws/things/chloe/shadow/get").unwrap();
+/// assert_eq!(thing_name, "chloe");
+/// assert_eq!(rest, "/shadow/get");
+/// ```
+pub fn parse_thing_prefix(topic: &str) -> Result<(&str, &str), Error> {
+    parse_thing_prefix_with(&TopicConfig::default(), topic)
+}
+
+/// Same as [`parse_thing_prefix`], for a subscription filter which may hold
+/// a `+` wildcard as the thing name.
+pub(crate) fn parse_thing_filter(filter: &str) -> Result<(&str, &str), Error> {
+    is_valid_mqtt_topic(filter)?;
+    let s = is_valid_prefix(filter, AWS_THINGS_PREFIX)?;
+    match s.strip_prefix('+') {
+        Some(rest) if rest.starts_with('/') => Ok(("+", rest)),
+        _ => parse_thing_prefix(filter),
+    }
+}
+
+///
+/// Whether two `$aws/things/` topics belong to the same thing. Thing names
+/// are case-sensitive, so they are compared exactly. A topic which isn't a
+/// thing topic never matches.
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::same_thing;
+///
+/// assert!(same_thing("$aws/things/chloe/shadow/get", "$aws/things/chloe/jobs/notify"));
+/// assert!(!same_thing("$aws/things/chloe/shadow/get", "$aws/things/Chloe/shadow/get"));
+/// ```
+pub fn same_thing(a: &str, b: &str) -> bool {
+    match (parse_thing_prefix(a), parse_thing_prefix(b)) {
+        (Ok((a, _)), Ok((b, _))) => a == b,
+        _ => false,
+    }
+}
+
+///
+/// Same as [`parse_thing_prefix`], with the thing prefix of `config`.
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{parse_thing_prefix_with, TopicConfig};
+///
+/// let config = TopicConfig::new("bridge/things/").unwrap();
+/// let (thing_name, rest) = parse_thing_prefix_with(&config, "bridge/things/chloe/jobs/get").unwrap();
+/// assert_eq!(thing_name, "chloe");
+/// assert_eq!(rest, "/jobs/get");
+/// ```
+pub fn parse_thing_prefix_with<'a>(
+    config: &TopicConfig,
+    topic: &'a str,
+) -> Result<(&'a str, &'a str), Error> {
+    is_valid_mqtt_topic(topic)?;
+
+    let s = is_valid_prefix(topic, config.things_prefix)?;
+
+    let mid = s.find('/').ok_or(Error::MalformedTopic)?;
+    let (thing_name, s) = s.split_at(mid);
+    is_valid_thing_name(thing_name)?;
+
+    Ok((thing_name, s))
+}
+
+/// Log the outcome of a subsystem's matcher: a `debug!` when `topic`
+/// didn't match, a `trace!` with the match otherwise. Without the `log`
+/// feature this only returns `result`.
+///
+/// `topic` is a `str`, or the raw bytes of a topic which isn't UTF-8.
+#[inline(always)]
+pub(crate) fn log_match<T: core::fmt::Debug>(
+    subsystem: &str,
+    topic: &(impl core::fmt::Debug + ?Sized),
+    result: Result<T, Error>,
+) -> Result<T, Error> {
+    #[cfg(feature = "log")]
+    match &result {
+        Ok(matched) => log::trace!("{subsystem}: matched {topic:?} as {matched:?}"),
+        Err(e) => log::debug!("{subsystem}: {topic:?} did not match: {e}"),
+    }
+    #[cfg(not(feature = "log"))]
+    let _ = (subsystem, topic);
+    result
+}
+
+///
+/// The level following the `$aws/things/<thingName>/` prefix, like `shadow`,
+/// `jobs`, `defender` or `tunnels`, without running the matcher of any
+/// subsystem. Any other level is returned as well, it's up to the caller to
+/// tell the subsystems apart.
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::subsystem_segment;
+///
+/// assert_eq!(subsystem_segment("$aws/things/chloe/shadow/name/common/get"), Ok("shadow"));
+/// assert_eq!(subsystem_segment("$aws/things/chloe/jobs/notify"), Ok("jobs"));
+/// ```
+pub fn subsystem_segment(topic: &str) -> Result<&str, Error> {
+    let (_, s) = parse_thing_prefix(topic)?;
+    let segment = levels(s).nth(1).unwrap_or_default();
+    if segment.is_empty() {
+        return Err(Error::MalformedTopic);
+    }
+    Ok(segment)
+}
+
+/// Reject the rest of a topic, as returned by [`parse_thing_prefix`], if any
+/// of its levels is empty, i.e. on a trailing or a double `/`.
+pub(crate) fn is_valid_levels(rest: &str) -> Result<(), Error> {
+    let bytes = rest.as_bytes();
+    if bytes.ends_with(b"/") || bytes.windows(2).any(|w| w == b"//") {
+        return Err(Error::MalformedTopic);
+    }
+    Ok(())
+}
+
+/// Collect the levels of `rest` for the matchers to match on. A topic with
+/// more than `N` levels is not one of the AWS IoT topics.
+pub(crate) fn collect_levels<const N: usize>(rest: &str) -> Result<ArrayVec<&str, N>, Error> {
+    let mut v = ArrayVec::new();
+    for level in levels(rest) {
+        v.try_push(level).map_err(|_| Error::NoMatch)?;
+    }
+    Ok(v)
+}
+
+/// A `$aws/things/<thingName>/...` topic with the prefix and the thing name
+/// validated once, e.g. in a bridge carrying the topics of many things, to
+/// then try the matchers of the subsystems on the rest of the topic.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{shadow, ThingTopic};
+///
+/// let topic = ThingTopic::parse("$aws/things/chloe/shadow/update/delta").unwrap();
+/// assert_eq!(topic.thing_name, "chloe");
+/// assert!(topic.as_jobs().is_err());
+/// assert_eq!(topic.as_shadow().unwrap().shadow_op, shadow::Topic::UpdateDelta);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThingTopic<'a> {
+    pub thing_name: &'a str,
+    /// The rest of the topic, starting at the `/` following the thing name.
+    pub rest: &'a str,
+}
+
+impl<'a> ThingTopic<'a> {
+    /// Validate the prefix and the thing name of `topic`.
+    pub fn parse(topic: &'a str) -> Result<Self, Error> {
+        Self::parse_with(&TopicConfig::default(), topic)
+    }
+    /// Same as [`ThingTopic::parse`], with the thing prefix of `config`.
+    pub fn parse_with(config: &TopicConfig, topic: &'a str) -> Result<Self, Error> {
+        let (thing_name, rest) = parse_thing_prefix_with(config, topic)?;
+        Ok(ThingTopic { thing_name, rest })
+    }
+    /// Same as [`shadow::match_topic`](crate::shadow::match_topic).
+    pub fn as_shadow(&self) -> Result<crate::shadow::ThingShadow<'a>, Error> {
+        crate::shadow::match_rest(self.thing_name, self.rest)
+    }
+    /// Same as [`jobs::match_topic`](crate::jobs::match_topic).
+    pub fn as_jobs(&self) -> Result<crate::jobs::ThingJobs<'a>, Error> {
+        crate::jobs::match_rest(self.thing_name, self.rest)?.try_into()
+    }
+    /// Same as [`defender::match_topic`](crate::defender::match_topic).
+    pub fn as_defender(&self) -> Result<crate::defender::ThingDefender<'a>, Error> {
+        crate::defender::match_rest(self.thing_name, self.rest)
+    }
+}
+
+///
+/// Iterate over the `/` separated levels of a topic.
+///
+/// A leading or trailing `/` yields an empty level, as MQTT counts it as a
+/// level of zero length.
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::levels;
+///
+/// let v: Vec<&str> = levels("$aws/things/chloe/shadow/get").collect();
+/// assert_eq!(v, ["$aws", "things", "chloe", "shadow", "get"]);
+/// ```
+pub fn levels(topic: &str) -> impl Iterator<Item = &str> {
+    topic.split('/')
+}
+
+///
+/// The number of levels of a topic, see [`levels`].
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::topic_levels;
+///
+/// assert_eq!(topic_levels("$aws/things/chloe/shadow/get"), 5);
+/// ```
+pub fn topic_levels(topic: &str) -> usize {
+    topic.bytes().filter(|&b| b == b'/').count() + 1
+}
+
+///
+/// Wrap a topic filter into an MQTT5 shared subscription,
+/// `$share/<share_name>/<filter>`, so that the messages are spread over all
+/// the clients subscribed with the same share name.
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{shadow, shared_filter};
+///
+/// let filter = shadow::assemble_topic(shadow::Topic::UpdateDelta, "chloe", None).unwrap();
+/// let shared = shared_filter("workers", &filter).unwrap();
+/// assert_eq!(&shared[..], "$share/workers/$aws/things/chloe/shadow/update/delta");
+/// ```
+pub fn shared_filter(
+    share_name: &str,
+    filter: &str,
+) -> Result<ArrayString<SHARED_FILTER_MAX_LENGTH>, Error> {
+    // The share name is a single level without wildcards
+    is_valid_param(share_name, SHARE_NAME_MAX_LENGTH).map_err(|_| Error::ShareNameParseFailed)?;
+    if share_name.contains(['/', '+', '#']) {
+        return Err(Error::ShareNameParseFailed);
+    }
+    is_valid_mqtt_topic(filter)?;
+
+    let mut s = ArrayString::<SHARED_FILTER_MAX_LENGTH>::new();
+    push_str(&mut s, SHARED_SUBSCRIPTION_PREFIX)?;
+    push_str(&mut s, share_name)?;
+    push_str(&mut s, "/")?;
+    push_str(&mut s, filter)?;
+    Ok(s)
 }
 
 ///
@@ -125,20 +1211,432 @@ pub fn is_valid_bridge<'a>(s: &'a str, bridge: &str) -> Result<&'a str, Error> {
 /// # Example
 /// ```
 /// ```
+#[inline]
 pub fn is_valid_job_id(job_id: &str) -> Result<(), Error> {
-    // Thing thing_name cannot be empty or longer than JOBID_MAX_LENGTH
-    is_valid_param(job_id, JOBID_MAX_LENGTH)?;
-    for a in job_id.chars() {
-        match a {
-            '-' | '_' | '0'..='9' | 'A'..='Z' | 'a'..='z' => continue,
-            _ => return Err(Error::JobsIdParseFailed),
+    is_valid_job_id_with(&NamePolicy::JOB_ID, job_id)
+}
+
+/// Same as [`is_valid_job_id`], against `policy` rather than the AWS rules.
+pub fn is_valid_job_id_with(policy: &NamePolicy, job_id: &str) -> Result<(), Error> {
+    policy.check(job_id).map_err(|_| Error::JobsIdParseFailed)
+}
+
+/// Is `job_id` a valid job id, or the `$next` pseudo-id for the next pending
+/// job execution? Only the `Describe` topics take `$next`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::common::*;
+///
+/// assert!(is_valid_job_id_or_next("$next").is_ok());
+/// assert!(is_valid_job_id_or_next("example-job-01").is_ok());
+/// assert_eq!(is_valid_job_id("$next"), Err(Error::JobsIdParseFailed));
+/// ```
+pub fn is_valid_job_id_or_next(job_id: &str) -> Result<(), Error> {
+    if job_id == JOBID_NEXT {
+        return Ok(());
+    }
+    is_valid_job_id(job_id)
+}
+/// Same as [`is_valid_thing_name`], in const evaluation for the topic
+/// macros.
+#[doc(hidden)]
+pub const fn is_valid_thing_name_const(thing_name: &str) -> bool {
+    is_valid_name_const(thing_name.as_bytes(), THINGNAME_MAX_LENGTH, true)
+}
+
+/// Same as [`is_valid_shadow_name`], in const evaluation.
+#[doc(hidden)]
+pub const fn is_valid_shadow_name_const(shadow_name: &str) -> bool {
+    let name = match shadow_name.as_bytes() {
+        [b'$', name @ ..] => name,
+        name => name,
+    };
+    let max_len = SHADOW_NAME_LENGTH_MAX - (shadow_name.len() - name.len());
+    is_valid_name_const(name, max_len, true)
+}
+
+/// Same as [`is_valid_job_id`], in const evaluation, with `$next` allowed or
+/// not as in [`jobs::assemble_job_topic`](crate::jobs::assemble_job_topic).
+#[doc(hidden)]
+pub const fn is_valid_job_id_const(job_id: &str, allow_next: bool) -> bool {
+    if let b"$next" = job_id.as_bytes() {
+        return allow_next;
+    }
+    // Job ids don't allow `:`.
+    is_valid_name_const(job_id.as_bytes(), JOBID_MAX_LENGTH, false)
+}
+
+const fn is_valid_name_const(name: &[u8], max_len: usize, allow_colon: bool) -> bool {
+    if name.is_empty() || name.len() > max_len {
+        return false;
+    }
+    let mut i = 0;
+    while i < name.len() {
+        let b = name[i];
+        if !(b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || (allow_colon && b == b':')) {
+            return false;
         }
+        i += 1;
     }
-    Ok(())
+    true
 }
+
 #[cfg(test)]
 mod tests {
     use crate::common::*;
+    use crate::TopicType;
+    #[test]
+    fn name_policy() {
+        let policy = NamePolicy {
+            max_length: 16,
+            allowed: |b| b.is_ascii_lowercase() || b.is_ascii_digit(),
+        };
+        // The default policy accepts all of them.
+        for name in ["chloe", "Chloe", "chloe:1"] {
+            assert_eq!(is_valid_thing_name(name), Ok(()));
+        }
+        assert_eq!(is_valid_thing_name_with(&policy, "chloe"), Ok(()));
+        assert_eq!(
+            is_valid_thing_name_with(&policy, "Chloe"),
+            Err(Error::ThingnameParseFailed)
+        );
+        assert_eq!(
+            is_valid_thing_name_with(&policy, "chloe:1"),
+            Err(Error::ThingnameParseFailed)
+        );
+        assert_eq!(
+            is_valid_thing_name_with(&policy, &"a".repeat(17)),
+            Err(Error::ThingnameParseFailed)
+        );
+        assert_eq!(
+            is_valid_thing_name_with(&policy, "$awsthing"),
+            Err(Error::ReservedName)
+        );
+
+        assert_eq!(is_valid_shadow_name_with(&policy, "$package"), Ok(()));
+        assert_eq!(
+            is_valid_shadow_name_with(&policy, &format!("${}", "a".repeat(16))),
+            Err(Error::ShadownameParseFailed)
+        );
+        assert_eq!(is_valid_job_id("ota-1"), Ok(()));
+        assert_eq!(
+            is_valid_job_id_with(&policy, "ota-1"),
+            Err(Error::JobsIdParseFailed)
+        );
+        assert_eq!(NamePolicy::JOB_ID.check("a:b"), Err(Error::FAIL));
+    }
+    #[test]
+    fn mqtt_topic_length_limit() {
+        let topic = "a".repeat(MQTT_TOPIC_LENGTH_MAX);
+        assert_eq!(is_valid_mqtt_topic(&topic), Ok(()));
+        let topic = "a".repeat(MQTT_TOPIC_LENGTH_MAX + 1);
+        assert_eq!(is_valid_mqtt_topic(&topic), Err(Error::MqttTopicTooLong));
+        // The limit is in bytes, 'é' takes two of them.
+        let topic = "é".repeat(MQTT_TOPIC_LENGTH_MAX / 2 + 1);
+        assert_eq!(topic.chars().count(), 32768);
+        assert_eq!(is_valid_mqtt_topic(&topic), Err(Error::MqttTopicTooLong));
+        assert_eq!(is_valid_mqtt_topic(""), Err(Error::MqttTopicFailed));
+    }
+    #[test]
+    fn topic_builder_matches_assemble_topic() {
+        use crate::{defender, jobs, shadow};
+
+        fn round_trip<'a, B, M>(api: &B, topic: &'a str) -> M
+        where
+            B: TopicBuilder,
+            M: TopicMatcher<'a>,
+        {
+            assert_eq!(&api.topic("chloe").unwrap()[..], topic);
+            M::match_topic(topic).unwrap()
+        }
+        let api = shadow::Topic::UpdateDocuments;
+        let topic = shadow::assemble_topic(api, "chloe", None).unwrap();
+        let shadow: shadow::ThingShadow = round_trip(&api, &topic);
+        assert_eq!(shadow.shadow_op, api);
+
+        let api = jobs::Topic::StartNextFailed;
+        let topic = jobs::assemble_topic("chloe", api).unwrap();
+        let jobs: jobs::ThingJobs = round_trip(&api, &topic);
+        assert_eq!(jobs.api, api);
+
+        let api = defender::Topic::CborReportRejected;
+        let topic = defender::assemble_topic("chloe", api).unwrap();
+        let defender: defender::ThingDefender = round_trip(&api, &topic);
+        assert_eq!(defender.api, api);
+
+        assert_eq!(
+            shadow::Topic::Get.topic("ch/loe").err(),
+            Some(Error::ThingnameParseFailed)
+        );
+    }
+    #[test]
+    fn topic_directions() {
+        use TopicDirection::*;
+        let cases = [
+            ("$aws/things/chloe/shadow/get", Request),
+            ("$aws/things/chloe/shadow/delete/accepted", Response),
+            (
+                "$aws/things/chloe/shadow/name/common/update/rejected",
+                Response,
+            ),
+            ("$aws/things/chloe/shadow/update/documents", Notification),
+            ("$aws/things/chloe/jobs/notify-next", Notification),
+            ("$aws/things/chloe/jobs/get", Request),
+            ("$aws/things/chloe/jobs/get/accepted", Response),
+            ("$aws/things/chloe/jobs/example/update", Request),
+            ("$aws/things/chloe/jobs/example/update/rejected", Response),
+            ("$aws/things/chloe/defender/metrics/cbor", Request),
+            ("$aws/things/chloe/defender/metrics/json/accepted", Response),
+            ("$aws/things/chloe/tunnels/notify", Notification),
+        ];
+        for (topic, direction) in cases {
+            assert_eq!(topic_direction(topic), Ok(direction), "{topic}");
+        }
+        assert_eq!(
+            topic_direction("$aws/things/chloe/jobs/foo"),
+            Err(Error::NoMatch)
+        );
+        assert_eq!(topic_direction("my/telemetry"), Err(Error::NoMatch));
+    }
+    #[test]
+    fn payload_format() {
+        assert_eq!(detect_format(b"{}"), PayloadFormat::Json);
+        assert_eq!(detect_format(b"\r\n\t [1,2]"), PayloadFormat::Json);
+        // {"hd": {"rid": 1}} in CBOR
+        let cbor = [0xa1, 0x62, 0x68, 0x64, 0xa1, 0x63, 0x72, 0x69, 0x64, 0x01];
+        assert_eq!(detect_format(&cbor), PayloadFormat::Cbor);
+        assert_eq!(detect_format(b""), PayloadFormat::Cbor);
+        assert_eq!(detect_format(b"  "), PayloadFormat::Cbor);
+    }
+    #[test]
+    fn topic_levels_edges() {
+        let cases: [(&str, &[&str]); 6] = [
+            ("a", &["a"]),
+            ("a/b", &["a", "b"]),
+            ("/a", &["", "a"]),
+            ("a/", &["a", ""]),
+            ("a//b", &["a", "", "b"]),
+            ("/", &["", ""]),
+        ];
+        for (topic, expected) in cases {
+            let v: Vec<&str> = levels(topic).collect();
+            assert_eq!(v, expected, "{topic}");
+            assert_eq!(topic_levels(topic), expected.len(), "{topic}");
+        }
+        assert_eq!(topic_levels(""), levels("").count());
+    }
+    #[test]
+    fn client_token() {
+        let token = "t".repeat(CLIENT_TOKEN_MAX_LENGTH);
+        assert!(is_valid_client_token(&token).is_ok());
+        let payload = token_payload(Some(&token)).unwrap();
+        assert!(payload.ends_with(r#"tt"}"#));
+        assert_eq!(&token_payload(None).unwrap()[..], "{}");
+
+        let token = "t".repeat(CLIENT_TOKEN_MAX_LENGTH + 1);
+        assert_eq!(
+            is_valid_client_token(&token),
+            Err(Error::ClientTokenParseFailed)
+        );
+        assert_eq!(
+            is_valid_client_token(""),
+            Err(Error::ClientTokenParseFailed)
+        );
+        assert_eq!(
+            token_payload(Some("a\\b")),
+            Err(Error::ClientTokenParseFailed)
+        );
+    }
+    #[test]
+    fn json_field_scan() {
+        let json = br#" { "a" : "x\"}{", "b": [1, {"c": "]"}], "n": -1.5e3 , "t":true, "o":{} } "#;
+        assert_eq!(json_field(json, "a"), Ok(Some(&br#""x\"}{""#[..])));
+        assert_eq!(json_field(json, "b"), Ok(Some(&br#"[1, {"c": "]"}]"#[..])));
+        assert_eq!(json_field(json, "n"), Ok(Some(&b"-1.5e3"[..])));
+        assert_eq!(json_field(json, "t"), Ok(Some(&b"true"[..])));
+        assert_eq!(json_field(json, "o"), Ok(Some(&b"{}"[..])));
+        // Nested members are not searched.
+        assert_eq!(json_field(json, "c"), Ok(None));
+        assert_eq!(json_field(b"{}", "a"), Ok(None));
+
+        for json in [
+            &b""[..],
+            b"[]",
+            b"{\"a\"",
+            b"{\"a\":{",
+            b"{\"a\":1 \"b\":2}",
+            b"{\"a\":\"1}",
+            b"{\"a\":,\"b\":1}",
+        ] {
+            assert_eq!(json_field(json, "b"), Err(Error::PayloadParseFailed));
+        }
+    }
+    #[test]
+    fn match_topic_type_named_before_classic() {
+        use crate::match_topic_type;
+        // `/shadow/` is a prefix of `/shadow/name/`, so a named shadow topic
+        // would pass for a classic one if the order of the checks changed.
+        let cases = [
+            (
+                "$aws/things/chloe/shadow/name/x/get",
+                TopicType::NamedShadow,
+            ),
+            (
+                "$aws/things/chloe/shadow/name/name/get",
+                TopicType::NamedShadow,
+            ),
+            (
+                "$aws/things/chloe/shadow/name/x/update/delta",
+                TopicType::NamedShadow,
+            ),
+            ("$aws/things/chloe/shadow/get", TopicType::Shadow),
+            ("$aws/things/chloe/shadow/update/delta", TopicType::Shadow),
+        ];
+        for (topic, expected) in cases {
+            assert_eq!(match_topic_type(topic), Ok(expected), "{topic}");
+            let named = matches!(match_topic_type(topic), Ok(TopicType::NamedShadow));
+            let shadow = crate::shadow::match_topic(topic).unwrap();
+            assert_eq!(shadow.shadow_name.is_some(), named, "{topic}");
+        }
+    }
+    #[test]
+    fn topic_config_custom_prefix() {
+        let config = TopicConfig::new("bridge/things/").unwrap();
+        let topic = "bridge/things/chloe/shadow/name/common/update/delta";
+        let shadow = crate::shadow::match_topic_with(&config, topic).unwrap();
+        assert_eq!(shadow.shadow_name, Some("common"));
+        assert_eq!(
+            crate::match_topic_type_with(&config, topic),
+            Ok(TopicType::NamedShadow)
+        );
+        // The standard prefix doesn't match a custom configuration.
+        assert_eq!(
+            crate::shadow::match_topic_with(&config, "$aws/things/chloe/shadow/get").err(),
+            Some(Error::NoMatch)
+        );
+
+        let topic =
+            crate::jobs::assemble_topic_with(&config, "chloe", crate::jobs::Topic::UpdateSuccess)
+                .unwrap();
+        assert_eq!(&topic[..], "bridge/things/chloe/jobs/+/update/accepted");
+        let topic = crate::defender::assemble_topic_with(
+            &config,
+            "chloe",
+            crate::defender::Topic::JsonReportAccepted,
+        )
+        .unwrap();
+        assert!(crate::defender::match_topic_with(&config, &topic).is_ok());
+        assert_eq!(
+            crate::tunneling::match_topic_with(&config, "bridge/things/chloe/tunnels/notify"),
+            Ok(())
+        );
+
+        assert_eq!(TopicConfig::default().things_prefix, AWS_THINGS_PREFIX);
+        assert_eq!(TopicConfig::new(""), Err(Error::MqttTopicFailed));
+        assert_eq!(TopicConfig::new("+/things/"), Err(Error::MqttTopicFailed));
+    }
+    #[test]
+    fn topic_config_bridged_device() {
+        use crate::{jobs, shadow};
+        let config = TopicConfig::new("bridge/things/").unwrap();
+
+        // Every topic a shadow and jobs agent uses carries the prefix, and
+        // matches back with the same configuration.
+        let client = shadow::ShadowClient::new_with(&config, "chloe", Some("common")).unwrap();
+        let update = client.update_topic();
+        assert_eq!(&update[..], "bridge/things/chloe/shadow/name/common/update");
+        let delta = client.topic(shadow::Topic::UpdateDelta);
+        let matched = shadow::match_topic_with(&config, &delta).unwrap();
+        assert_eq!(matched.shadow_op, shadow::Topic::UpdateDelta);
+        assert_eq!(
+            &matched.to_filter_with(&config).unwrap()[..],
+            "bridge/things/chloe/shadow/name/common/update/+"
+        );
+        assert_eq!(
+            &shadow::get_any_named_filter_with(&config, "chloe").unwrap()[..],
+            "bridge/things/chloe/shadow/name/+/get/+"
+        );
+
+        let topics = [
+            jobs::get_pending_with(&config, "chloe").unwrap(),
+            jobs::start_next_with(&config, "chloe").unwrap(),
+            jobs::describe_with(&config, "chloe", "ota-1").unwrap(),
+            jobs::update_with(&config, "chloe", "ota-1").unwrap(),
+        ];
+        for topic in topics
+            .iter()
+            .chain(&jobs::job_scoped_topics_with(&config, "chloe", "ota-1").unwrap())
+        {
+            assert!(topic.starts_with("bridge/things/chloe/jobs/"), "{topic}");
+            assert!(jobs::match_topic_with(&config, topic).is_ok(), "{topic}");
+            assert_eq!(
+                jobs::match_topic(topic).err(),
+                Some(Error::NoMatch),
+                "{topic}"
+            );
+        }
+        let matched =
+            jobs::match_topic_with(&config, "bridge/things/chloe/jobs/ota-1/update/accepted")
+                .unwrap();
+        assert_eq!(
+            &matched.to_filter_with(&config).unwrap()[..],
+            "bridge/things/chloe/jobs/+/update/+"
+        );
+
+        // A prefix leaving no room for the longest shadow topic.
+        let prefix = format!("{}/", "b".repeat(SHADOW_TOPIC_MAX_LENGTH - 20));
+        let config = TopicConfig::new(&prefix).unwrap();
+        assert_eq!(
+            shadow::ShadowClient::new_with(&config, "chloe", None).err(),
+            Some(Error::FAIL)
+        );
+    }
+    #[test]
+    fn integer_to_str() {
+        assert_eq!(&u64_to_str(0)[..], "0");
+        assert_eq!(&u64_to_str(7)[..], "7");
+        assert_eq!(&u64_to_str(1530304554)[..], "1530304554");
+        assert_eq!(&u64_to_str(u64::MAX)[..], "18446744073709551615");
+    }
+    #[test]
+    fn push_str_overflow() {
+        let mut s = arrayvec::ArrayString::<4>::new();
+        assert_eq!(push_str(&mut s, "$aws"), Ok(()));
+        assert_eq!(push_str(&mut s, "/"), Err(Error::FAIL));
+        assert_eq!(&s[..], "$aws");
+    }
+    #[test]
+    fn error_kind() {
+        use crate::shadow;
+        let err = shadow::match_topic("$aws/things/chloe/jobs/notify").unwrap_err();
+        assert!(err.is_no_match());
+        let err = shadow::match_topic("$aws/things/chloe/shadow/name").unwrap_err();
+        assert!(err.is_malformed());
+        assert_eq!(ErrorKind::from(&err), ErrorKind::Validation);
+        let err = shadow::match_topic("$aws/things/chloe").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Parse);
+        assert!(!err.is_no_match());
+    }
+    #[test]
+    fn shared_subscription() {
+        let shared = shared_filter("group", "$aws/things/+/jobs/notify").unwrap();
+        assert_eq!(&shared[..], "$share/group/$aws/things/+/jobs/notify");
+        assert_eq!(shared_filter("", "a/b"), Err(Error::ShareNameParseFailed));
+        assert_eq!(
+            shared_filter("a/b", "a/b"),
+            Err(Error::ShareNameParseFailed)
+        );
+        assert_eq!(shared_filter("g+", "a/b"), Err(Error::ShareNameParseFailed));
+        assert_eq!(shared_filter("group", ""), Err(Error::MqttTopicFailed));
+    }
+    #[test]
+    fn qos() {
+        assert_eq!(recommended_qos(&TopicType::NamedShadow), 1);
+        assert_eq!(recommended_qos(&TopicType::Jobs), 1);
+        assert_eq!(recommended_qos(&TopicType::Defender), 0);
+        assert_eq!(recommended_qos(&TopicType::Other), 0);
+    }
     #[test]
     fn valid_mqtt_topic() -> Result<(), Error> {
         is_valid_mqtt_topic("hello/world")?;
@@ -150,6 +1648,58 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn reserved_topic() {
+        assert!(is_reserved_topic("$aws/things/chloe/shadow/update"));
+        assert!(is_reserved_topic("$aws/rules/my_rule"));
+        assert!(is_reserved_topic("$aws/events/presence/connected/chloe"));
+        assert!(!is_reserved_topic("aws/things/chloe"));
+        assert!(!is_reserved_topic("$awsome/topic"));
+    }
+    #[test]
+    fn aws_things_topic() {
+        assert!(is_aws_things_topic("$aws/things/chloe/jobs/notify"));
+        assert!(!is_aws_things_topic("$aws/rules/my_rule"));
+    }
+    #[test]
+    fn thing_prefix() -> Result<(), Error> {
+        let (thing_name, rest) = parse_thing_prefix("$aws/things/chloe/jobs/notify")?;
+        assert_eq!(thing_name, "chloe");
+        assert_eq!(rest, "/jobs/notify");
+        Ok(())
+    }
+    #[test]
+    fn thing_prefix_errors() {
+        assert_eq!(parse_thing_prefix(""), Err(Error::MqttTopicFailed));
+        assert_eq!(parse_thing_prefix("hello/world"), Err(Error::NoMatch));
+        assert_eq!(
+            parse_thing_prefix("$aws/things/chloe"),
+            Err(Error::MalformedTopic)
+        );
+        assert_eq!(
+            parse_thing_prefix("$aws/things/ch@loe/shadow/get"),
+            Err(Error::ThingnameParseFailed)
+        );
+    }
+    #[test]
+    fn thing_name_only_topic_is_malformed_in_every_matcher() {
+        use crate::{defender, jobs, match_topic_type, shadow, tunneling};
+        let topic = "$aws/things/chloe";
+        assert_eq!(match_topic_type(topic).err(), Some(Error::MalformedTopic));
+        assert_eq!(
+            shadow::match_topic(topic).err(),
+            Some(Error::MalformedTopic)
+        );
+        assert_eq!(jobs::match_topic(topic).err(), Some(Error::MalformedTopic));
+        assert_eq!(
+            defender::match_topic(topic).err(),
+            Some(Error::MalformedTopic)
+        );
+        assert_eq!(
+            tunneling::match_topic(topic).err(),
+            Some(Error::MalformedTopic)
+        );
+    }
+    #[test]
     fn valid_thing_name() -> Result<(), Error> {
         is_valid_thing_name("-_09AZaz:")?;
         Ok(())
@@ -160,8 +1710,401 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn names_reject_slash() {
+        assert_eq!(is_valid_thing_name("a/b"), Err(Error::ThingnameParseFailed));
+        assert_eq!(
+            is_valid_shadow_name("a/b"),
+            Err(Error::ShadownameParseFailed)
+        );
+        assert_eq!(is_valid_job_id("a/b"), Err(Error::JobsIdParseFailed));
+    }
+    #[test]
+    fn thing_names_reserved() {
+        use crate::shadow;
+        for name in ["$aws", "$aws-iot", "$awsthing"] {
+            assert_eq!(is_valid_thing_name(name), Err(Error::ReservedName));
+        }
+        // Separators and other `$` names are plain invalid names.
+        for name in ["things/chloe", "chloe/shadow", "$other", "+", "#"] {
+            assert_eq!(is_valid_thing_name(name), Err(Error::ThingnameParseFailed));
+        }
+        assert_eq!(is_valid_thing_name("aws"), Ok(()));
+        assert_eq!(Error::ReservedName.kind(), ErrorKind::Validation);
+
+        assert_eq!(
+            shadow::assemble_topic(shadow::Topic::Get, "$aws", None),
+            Err(Error::ReservedName)
+        );
+        assert_eq!(
+            shadow::assemble_topic(shadow::Topic::Get, "things/chloe", None),
+            Err(Error::ThingnameParseFailed)
+        );
+        assert_eq!(
+            shadow::match_topic("$aws/things/$aws/shadow/get"),
+            Err(Error::ReservedName)
+        );
+    }
+    #[test]
+    fn name_lengths_in_bytes() {
+        // The limits are inclusive.
+        let name = "t".repeat(THINGNAME_MAX_LENGTH);
+        assert_eq!(is_valid_thing_name(&name), Ok(()));
+        assert!(is_valid_thing_name_const(&name));
+        assert_eq!(
+            is_valid_thing_name(&name[1..].repeat(2)),
+            Err(Error::ThingnameParseFailed)
+        );
+        let name = "s".repeat(SHADOW_NAME_LENGTH_MAX);
+        assert_eq!(is_valid_shadow_name(&name), Ok(()));
+        assert_eq!(is_valid_shadow_name(&format!("${}", &name[1..])), Ok(()));
+        assert_eq!(
+            is_valid_shadow_name(&format!("${name}")),
+            Err(Error::ShadownameParseFailed)
+        );
+        let id = "j".repeat(JOBID_MAX_LENGTH);
+        assert_eq!(is_valid_job_id(&id), Ok(()));
+        assert!(is_valid_job_id_const(&id, false));
+        assert_eq!(
+            is_valid_job_id(&format!("{id}j")),
+            Err(Error::JobsIdParseFailed)
+        );
+
+        // Non-ASCII names are rejected whatever their length, without
+        // slicing into a multi-byte character.
+        for name in ["é", "chlöe", "👍", &"é".repeat(THINGNAME_MAX_LENGTH / 2)] {
+            assert_eq!(is_valid_thing_name(name), Err(Error::ThingnameParseFailed));
+            assert_eq!(
+                is_valid_shadow_name(name),
+                Err(Error::ShadownameParseFailed)
+            );
+            assert_eq!(is_valid_job_id(name), Err(Error::JobsIdParseFailed));
+            assert!(!is_valid_thing_name_const(name));
+        }
+        assert_eq!(
+            is_valid_shadow_name("$é"),
+            Err(Error::ShadownameParseFailed)
+        );
+    }
+    #[test]
+    fn names_colon() {
+        // Thing and shadow names share the [a-zA-Z0-9:_-] character set,
+        // job ids don't allow ':'.
+        assert_eq!(is_valid_thing_name("chloe:1"), Ok(()));
+        assert_eq!(is_valid_shadow_name("common:1"), Ok(()));
+        assert_eq!(is_valid_job_id("job:1"), Err(Error::JobsIdParseFailed));
+    }
+    #[test]
     fn valid_job_id() -> Result<(), Error> {
         is_valid_job_id("_-09AZaz")?;
         Ok(())
     }
+    #[test]
+    fn matchers_reject_too_many_levels() {
+        use crate::{defender, jobs, shadow};
+        let rest = ["a"; 17].join("/");
+        assert_eq!(collect_levels::<16>(&rest[2..]).unwrap().len(), 16);
+        assert_eq!(collect_levels::<16>(&rest).err(), Some(Error::NoMatch));
+
+        // 18 levels after the subsystem, too many to collect; the first ones
+        // would make a valid topic if the others were dropped.
+        let extra = "/x".repeat(14);
+        let topic = format!("$aws/things/chloe/shadow/name/common/update/delta{extra}");
+        assert_eq!(shadow::match_topic(&topic), Err(Error::NoMatch));
+        let topic = format!("$aws/things/chloe/jobs/ota-1/update/accepted{extra}/x");
+        assert_eq!(jobs::match_topic(&topic), Err(Error::NoMatch));
+        let topic = format!("$aws/things/chloe/defender/metrics/json/accepted{extra}/x/x");
+        assert_eq!(defender::match_topic(&topic), Err(Error::NoMatch));
+    }
+    #[test]
+    fn matchers_reject_empty_levels() {
+        use crate::{defender, jobs, shadow, tunneling};
+        let shadow_cases = [
+            ("$aws/things/chloe/shadow/get/", Err(Error::MalformedTopic)),
+            ("$aws/things/chloe/shadow//get", Err(Error::MalformedTopic)),
+            ("$aws/things/chloe//shadow/get", Err(Error::MalformedTopic)),
+            (
+                "$aws/things/chloe/shadow/name//get",
+                Err(Error::MalformedTopic),
+            ),
+            // Not a `$aws/things/` topic at all.
+            ("/$aws/things/chloe/shadow/get", Err(Error::NoMatch)),
+        ];
+        for (topic, expected) in shadow_cases {
+            assert_eq!(shadow::match_topic(topic), expected, "{topic}");
+        }
+        let jobs_cases = [
+            ("$aws/things/chloe/jobs/get/", Err(Error::MalformedTopic)),
+            ("$aws/things/chloe/jobs//get", Err(Error::MalformedTopic)),
+            (
+                "$aws/things/chloe/jobs/ota-1//update",
+                Err(Error::MalformedTopic),
+            ),
+            ("/$aws/things/chloe/jobs/get", Err(Error::NoMatch)),
+        ];
+        for (topic, expected) in jobs_cases {
+            assert_eq!(jobs::match_topic_ref(topic), expected, "{topic}");
+        }
+        assert_eq!(
+            defender::match_topic("$aws/things/chloe/defender/metrics/json/").err(),
+            Some(Error::MalformedTopic)
+        );
+        assert_eq!(
+            tunneling::match_topic("$aws/things/chloe/tunnels//notify"),
+            Err(Error::MalformedTopic)
+        );
+    }
+    #[test]
+    fn matchers_never_panic() {
+        use crate::{defender, jobs, match_topic_type_bytes, shadow, tunneling};
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        fn match_all(topic: &[u8]) {
+            let _ = match_topic_type_bytes(topic);
+            let _ = shadow::match_topic_bytes(topic);
+            let _ = jobs::match_topic_bytes(topic);
+            let _ = defender::match_topic_bytes(topic);
+            let _ = tunneling::match_topic_bytes(topic);
+            if let Ok(topic) = core::str::from_utf8(topic) {
+                let _ = jobs::match_topic_ref(topic);
+                let _ = topic_direction(topic);
+            }
+        }
+
+        // Random levels from the topics, so that the matchers get past the
+        // prefix instead of rejecting nearly every input up front.
+        let pieces: &[&[u8]] = &[
+            b"$aws",
+            b"things",
+            b"chloe",
+            b"shadow",
+            b"name",
+            b"common",
+            b"get",
+            b"update",
+            b"delete",
+            b"accepted",
+            b"rejected",
+            b"delta",
+            b"documents",
+            b"jobs",
+            b"notify",
+            b"notify-next",
+            b"start-next",
+            b"$next",
+            b"defender",
+            b"metrics",
+            b"json",
+            b"cbor",
+            b"tunnels",
+            b"+",
+            b"#",
+            b"",
+            b"\xff",
+            b"\xe2\x82",
+        ];
+        let mut rng = StdRng::seed_from_u64(346);
+        let mut topic = Vec::new();
+        for _ in 0..20_000 {
+            topic.clear();
+            if rng.gen_bool(0.8) {
+                topic.extend_from_slice(b"$aws/things/");
+            }
+            for i in 0..rng.gen_range(0..24) {
+                if i > 0 {
+                    topic.push(b'/');
+                }
+                topic.extend_from_slice(pieces[rng.gen_range(0..pieces.len())]);
+            }
+            match_all(&topic);
+            let len = rng.gen_range(0..64);
+            topic.clear();
+            topic.extend((0..len).map(|_| rng.gen::<u8>()));
+            match_all(&topic);
+        }
+
+        let deep = "$aws/things/chloe/shadow/name/common/get".to_string() + &"/a".repeat(20);
+        assert_eq!(shadow::match_topic(&deep), Err(Error::NoMatch));
+        let deep = "$aws/things/chloe/jobs/ota-1/get".to_string() + &"/a".repeat(20);
+        assert_eq!(jobs::match_topic_ref(&deep), Err(Error::NoMatch));
+        let deep = "$aws/things/chloe/defender/metrics/json".to_string() + &"/a".repeat(20);
+        assert_eq!(defender::match_topic(&deep).err(), Some(Error::NoMatch));
+    }
+    #[test]
+    fn service_error_code_retryable() {
+        let cases = [
+            (400, false),
+            (401, false),
+            (403, false),
+            (404, false),
+            (409, false),
+            (413, false),
+            (415, false),
+            (422, false),
+            (429, true),
+            (500, true),
+        ];
+        for (code, retryable) in cases {
+            let error_code = ServiceErrorCode::from_u32(code).unwrap();
+            assert_eq!(error_code.as_u32(), code);
+            assert_eq!(error_code.retryable(), retryable, "{code}");
+        }
+        assert_eq!(ServiceErrorCode::from_u32(200), None);
+        assert_eq!(ServiceErrorCode::from_u32(503), None);
+    }
+    #[test]
+    fn client_token_matches() {
+        let payload = token_payload(Some("token-1")).unwrap();
+        assert!(super::client_token_matches(payload.as_bytes(), "token-1"));
+        assert!(!super::client_token_matches(payload.as_bytes(), "token-10"));
+        assert!(!super::client_token_matches(payload.as_bytes(), "token"));
+
+        let rejected = br#"{"code":409,"message":"Version conflict","clientToken":"token-2","timestamp":1469529572}"#;
+        assert!(super::client_token_matches(rejected, "token-2"));
+        assert!(!super::client_token_matches(rejected, "token-1"));
+
+        // The nested token of the state document doesn't count.
+        let payload = br#"{"state":{"clientToken":"token-1"},"version":2}"#;
+        assert!(!super::client_token_matches(payload, "token-1"));
+        assert!(!super::client_token_matches(b"{}", ""));
+        assert!(!super::client_token_matches(b"not json", "token-1"));
+    }
+    #[test]
+    fn message_context() {
+        let payload = br#"{"clientToken":"token-1"}"#;
+        let context = MessageContext::default();
+        assert_eq!(context.user_property("source"), None);
+        assert!(context.correlates(payload, "token-1"));
+        assert!(!context.correlates(payload, "token-2"));
+
+        let properties = [("source", "a"), ("hop", "1"), ("source", "b")];
+        let context = MessageContext {
+            correlation_data: Some(b"token-2"),
+            response_topic: Some("client/chloe/responses"),
+            user_properties: &properties,
+        };
+        assert_eq!(context.user_property("source"), Some("a"));
+        assert_eq!(context.user_property("hop"), Some("1"));
+        // The correlation data takes precedence over the payload.
+        assert!(context.correlates(payload, "token-2"));
+        assert!(!context.correlates(payload, "token-1"));
+    }
+    #[test]
+    fn subsystem_segment() {
+        let cases = [
+            ("$aws/things/chloe/shadow/get", Ok("shadow")),
+            (
+                "$aws/things/chloe/shadow/name/common/update/delta",
+                Ok("shadow"),
+            ),
+            ("$aws/things/chloe/jobs/ota-1/get", Ok("jobs")),
+            ("$aws/things/chloe/defender/metrics/json", Ok("defender")),
+            ("$aws/things/chloe/tunnels/notify", Ok("tunnels")),
+            ("$aws/things/chloe/streams/get", Ok("streams")),
+            ("$aws/things/chloe/shadow", Ok("shadow")),
+            ("$aws/things/chloe/", Err(Error::MalformedTopic)),
+            ("$aws/things/chloe//shadow/get", Err(Error::MalformedTopic)),
+            ("$aws/things/chloe", Err(Error::MalformedTopic)),
+            ("$aws/rules/chloe/shadow", Err(Error::NoMatch)),
+        ];
+        for (topic, expected) in cases {
+            assert_eq!(super::subsystem_segment(topic), expected, "{topic}");
+        }
+    }
+    #[test]
+    fn thing_topic_combinators() {
+        use crate::{defender, jobs, shadow};
+        let topic = "$aws/things/chloe/shadow/name/common/get/accepted";
+        let thing = ThingTopic::parse(topic).unwrap();
+        assert_eq!(thing.rest, "/shadow/name/common/get/accepted");
+        let shadow = thing
+            .as_jobs()
+            .map(|_| "jobs")
+            .or_else(|_| thing.as_defender().map(|_| "defender"))
+            .or_else(|_| thing.as_shadow().map(|_| "shadow"));
+        assert_eq!(shadow, Ok("shadow"));
+        assert_eq!(thing.as_shadow(), shadow::match_topic(topic));
+        assert_eq!(thing.as_jobs().err(), Some(Error::RootParseFailed));
+
+        let topic = "$aws/things/chloe/jobs/ota-1/get";
+        assert_eq!(
+            ThingTopic::parse(topic).unwrap().as_jobs(),
+            jobs::match_topic(topic)
+        );
+        let topic = "$aws/things/chloe/defender/metrics/cbor";
+        assert_eq!(
+            ThingTopic::parse(topic).unwrap().as_defender(),
+            defender::match_topic(topic)
+        );
+        assert_eq!(
+            ThingTopic::parse("$aws/things/chl+oe/shadow/get"),
+            Err(Error::ThingnameParseFailed)
+        );
+    }
+    #[test]
+    fn same_thing() {
+        let shadow = "$aws/things/chloe/shadow/name/common/update/delta";
+        assert!(super::same_thing(
+            shadow,
+            "$aws/things/chloe/jobs/notify-next"
+        ));
+        assert!(super::same_thing(
+            shadow,
+            "$aws/things/chloe/defender/metrics/json"
+        ));
+        assert!(super::same_thing(
+            shadow,
+            "$aws/things/chloe/tunnels/notify"
+        ));
+        assert!(!super::same_thing(shadow, "$aws/things/CHLOE/shadow/get"));
+        assert!(!super::same_thing(shadow, "$aws/things/chloe2/shadow/get"));
+        assert!(!super::same_thing(shadow, "$aws/things/chlo/shadow/get"));
+        assert!(!super::same_thing(shadow, "chloe/shadow/get"));
+        assert!(!super::same_thing(
+            "$aws/things/chl+oe/a",
+            "$aws/things/chl+oe/a"
+        ));
+    }
+    #[test]
+    fn match_filter_wildcard_thing() {
+        use crate::{defender, jobs, shadow};
+        let shadow = shadow::match_filter("$aws/things/+/shadow/get/accepted").unwrap();
+        assert_eq!(shadow.thing_name, "+");
+        assert_eq!(shadow.shadow_name, None);
+        assert_eq!(shadow.shadow_op, shadow::Topic::GetAccepted);
+        let shadow = shadow::match_filter("$aws/things/+/shadow/name/common/get").unwrap();
+        assert_eq!(shadow.shadow_name, Some("common"));
+
+        // Concrete topics still match, the topic matchers still reject `+`.
+        let shadow = shadow::match_filter("$aws/things/chloe/shadow/get/accepted").unwrap();
+        assert_eq!(shadow.thing_name, "chloe");
+        assert_eq!(
+            shadow::match_topic("$aws/things/+/shadow/get/accepted"),
+            Err(Error::ThingnameParseFailed)
+        );
+
+        let jobs = jobs::match_filter("$aws/things/+/jobs/ota-1/update/accepted").unwrap();
+        assert_eq!(
+            (jobs.thing_name, jobs.api),
+            ("+", jobs::Topic::UpdateSuccess)
+        );
+        let defender = defender::match_filter("$aws/things/+/defender/metrics/cbor").unwrap();
+        assert_eq!(defender.thing_name, "+");
+
+        let cases = [
+            ("$aws/things/+", Err(Error::MalformedTopic)),
+            (
+                "$aws/things/+chloe/shadow/get",
+                Err(Error::ThingnameParseFailed),
+            ),
+            ("$aws/things/#", Err(Error::MalformedTopic)),
+            (
+                "$aws/things/+/shadow/name/+/get",
+                Err(Error::ShadownameParseFailed),
+            ),
+            ("$aws/things/+//shadow/get", Err(Error::MalformedTopic)),
+        ];
+        for (filter, expected) in cases {
+            assert_eq!(shadow::match_filter(filter), expected, "{filter}");
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use core::fmt::Write;
+
 use arrayvec::{ArrayString, ArrayVec};
 
 use self::Topic::*;
@@ -6,8 +8,13 @@ use crate::common::*;
 const API_JSON_FORMAT: &str = "json";
 const API_CBOR_FORMAT: &str = "cbor";
 
+pub const REPORT_VERSION: &str = "1.0";
+pub const REPORT_PORTS_MAX: usize = 16;
+pub const REPORT_CONNECTIONS_MAX: usize = 16;
+
 /// The struct outputs which API the topic is for. It also outputs
 /// the thing name in the given topic.
+#[derive(Debug, PartialEq, Eq)]
 pub struct ThingDefender<'a> {
     pub thing_name: &'a str,
     pub api: Topic,
@@ -23,7 +30,7 @@ pub struct ThingDefender<'a> {
 ///     - Report Format = json or cbor
 ///     - Suffix = /accepted or /rejected or empty
 ///
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Topic {
     JsonReportPublish,
     /* Topic for publishing a JSON report. */
@@ -38,6 +45,89 @@ pub enum Topic {
     CborReportRejected, /* Topic for getting a CBOR report rejected response. */
 }
 
+/// Every defender topic, in declaration order.
+pub const ALL_TOPICS: &[Topic] = &[
+    JsonReportPublish,
+    JsonReportAccepted,
+    JsonReportRejected,
+    CborReportPublish,
+    CborReportAccepted,
+    CborReportRejected,
+];
+
+impl Topic {
+    /// Whether the device publishes the topic or receives it as a response
+    /// to its report.
+    pub fn direction(&self) -> TopicDirection {
+        match self {
+            JsonReportPublish | CborReportPublish => TopicDirection::Request,
+            _ => TopicDirection::Response,
+        }
+    }
+    /// The canonical name of the topic, i.e. the report format and suffix
+    /// levels after `defender/metrics/`, like `"json/accepted"`.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::defender;
+    ///
+    /// assert_eq!(defender::Topic::CborReportRejected.as_str(), "cbor/rejected");
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JsonReportPublish => "json",
+            JsonReportAccepted => "json/accepted",
+            JsonReportRejected => "json/rejected",
+            CborReportPublish => "cbor",
+            CborReportAccepted => "cbor/accepted",
+            CborReportRejected => "cbor/rejected",
+        }
+    }
+}
+
+fn from_name(name: &str) -> Result<Topic, Error> {
+    match name {
+        "json" => Ok(JsonReportPublish),
+        "json/accepted" => Ok(JsonReportAccepted),
+        "json/rejected" => Ok(JsonReportRejected),
+        "cbor" => Ok(CborReportPublish),
+        "cbor/accepted" => Ok(CborReportAccepted),
+        "cbor/rejected" => Ok(CborReportRejected),
+        _ => Err(Error::MessageTypeParseFailed),
+    }
+}
+
+/// The inverse of [`Topic::as_str`].
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::defender;
+///
+/// let topic = defender::Topic::JsonReportAccepted;
+/// assert_eq!(defender::Topic::try_from(topic.as_str()), Ok(topic));
+/// ```
+impl TryFrom<&str> for Topic {
+    type Error = Error;
+
+    fn try_from(name: &str) -> Result<Self, Error> {
+        from_name(name)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Topic {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Topic {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_name(deserializer, from_name)
+    }
+}
+
 /// Populate the topic string for a Device Defender operation.
 ///
 /// # Example
@@ -48,21 +138,126 @@ pub enum Topic {
 /// let topic = defender::assemble_topic("chloe", defender::Topic::JsonReportPublish).unwrap();
 /// assert_eq!(&topic[..], "$aws/things/chloe/defender/metrics/json")
 /// ```
+#[must_use = "this returns the topic without publishing or subscribing to it"]
 pub fn assemble_topic(
     thing_name: &str,
     api: Topic,
 ) -> Result<ArrayString<DEFENDER_TOPIC_MAX_LENGTH>, Error> {
+    assemble_topic_with(&TopicConfig::default(), thing_name, api)
+}
+
+/// Same as [`assemble_topic`], with the thing prefix of `config`.
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_topic_with(
+    config: &TopicConfig,
+    thing_name: &str,
+    api: Topic,
+) -> Result<ArrayString<DEFENDER_TOPIC_MAX_LENGTH>, Error> {
+    assemble_api(config, thing_name, api)
+}
+
+/// Same as [`assemble_topic`], into a `String`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::defender;
+///
+/// let topic = defender::assemble_topic_string("chloe", defender::Topic::CborReportPublish).unwrap();
+/// assert_eq!(topic, "$aws/things/chloe/defender/metrics/cbor");
+/// ```
+#[cfg(feature = "std")]
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_topic_string(thing_name: &str, api: Topic) -> Result<String, Error> {
+    assemble_api(&TopicConfig::default(), thing_name, api)
+}
+
+/// Same as [`assemble_topic_with`], into a `String`, so that the thing
+/// prefix of `config` may be longer than the capacity allows. The topic
+/// still fails with `Error::MqttTopicTooLong` past `MQTT_TOPIC_LENGTH_MAX`.
+#[cfg(feature = "std")]
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_topic_string_with(
+    config: &TopicConfig,
+    thing_name: &str,
+    api: Topic,
+) -> Result<String, Error> {
+    let topic: String = assemble_api(config, thing_name, api)?;
+    is_valid_mqtt_topic(&topic)?;
+    Ok(topic)
+}
+
+fn assemble_api<B: TopicBuf>(
+    config: &TopicConfig,
+    thing_name: &str,
+    api: Topic,
+) -> Result<B, Error> {
     is_valid_thing_name(thing_name)?;
-    let mut s = ArrayString::<DEFENDER_TOPIC_MAX_LENGTH>::new();
-    s.push_str(AWS_THINGS_PREFIX);
-    s.push_str(thing_name);
-    s.push_str(DEFENDER_API_BRIDGE);
-    s.push_str(op(&api));
-    s.push_str(suffix(&api));
+    let mut s = B::default();
+    push_str(&mut s, config.things_prefix)?;
+    push_str(&mut s, thing_name)?;
+    push_str(&mut s, DEFENDER_API_BRIDGE)?;
+    push_str(&mut s, op(&api))?;
+    push_str(&mut s, suffix(&api))?;
 
     Ok(s)
 }
 
+/// The encoding of a Device Defender report, which picks the `json` or the
+/// `cbor` topics.
+pub type ReportFormat = PayloadFormat;
+
+/// Populate the topic to publish a report in the given format on, i.e. the
+/// [`Topic::JsonReportPublish`] or the [`Topic::CborReportPublish`] topic.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::defender::{self, ReportFormat};
+///
+/// let topic = defender::publish_topic("chloe", ReportFormat::Cbor).unwrap();
+/// assert_eq!(&topic[..], "$aws/things/chloe/defender/metrics/cbor");
+/// ```
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn publish_topic(
+    thing_name: &str,
+    format: ReportFormat,
+) -> Result<ArrayString<DEFENDER_TOPIC_MAX_LENGTH>, Error> {
+    let publish = match format {
+        PayloadFormat::Json => JsonReportPublish,
+        PayloadFormat::Cbor => CborReportPublish,
+    };
+    assemble_topic(thing_name, publish)
+}
+
+/// Populate the `accepted` and the `rejected` topics of the reports in the
+/// given format, the two topics to subscribe to before publishing on the
+/// [`publish_topic`].
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{defender, PayloadFormat};
+///
+/// let (accepted, rejected) = defender::response_topics("chloe", PayloadFormat::Json).unwrap();
+/// assert_eq!(&accepted[..], "$aws/things/chloe/defender/metrics/json/accepted");
+/// assert_eq!(&rejected[..], "$aws/things/chloe/defender/metrics/json/rejected");
+/// ```
+#[must_use = "this returns the topics without subscribing to them"]
+pub fn response_topics(
+    thing_name: &str,
+    format: ReportFormat,
+) -> Result<
+    (
+        ArrayString<DEFENDER_TOPIC_MAX_LENGTH>,
+        ArrayString<DEFENDER_TOPIC_MAX_LENGTH>,
+    ),
+    Error,
+> {
+    let mut accepted = publish_topic(thing_name, format)?;
+    let mut rejected = accepted;
+    push_str(&mut accepted, SUFFIX_ACCEPTED)?;
+    push_str(&mut rejected, SUFFIX_REJECTED)?;
+    Ok((accepted, rejected))
+}
+
 fn op(api: &Topic) -> &str {
     match api {
         JsonReportPublish | JsonReportAccepted | JsonReportRejected => API_JSON_FORMAT,
@@ -78,6 +273,59 @@ fn suffix(topic_type: &Topic) -> &str {
     }
 }
 
+/// The length [`assemble_topic`] gives the topic, without building it.
+///
+/// The thing name is not validated.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::defender;
+///
+/// let len = defender::topic_len("chloe", defender::Topic::CborReportPublish);
+/// assert_eq!(len, "$aws/things/chloe/defender/metrics/cbor".len());
+/// ```
+pub fn topic_len(thing_name: &str, api: Topic) -> usize {
+    AWS_THINGS_PREFIX.len()
+        + thing_name.len()
+        + DEFENDER_API_BRIDGE.len()
+        + op(&api).len()
+        + suffix(&api).len()
+}
+
+impl TopicBuilder for Topic {
+    type Output = ArrayString<DEFENDER_TOPIC_MAX_LENGTH>;
+
+    fn topic(&self, thing_name: &str) -> Result<Self::Output, Error> {
+        assemble_topic_with(&TopicConfig::default(), thing_name, *self)
+    }
+}
+
+impl<'a> TopicMatcher<'a> for ThingDefender<'a> {
+    fn match_topic(topic: &'a str) -> Result<Self, Error> {
+        match_topic(topic)
+    }
+}
+
+/// A summary for logs, like `defender(thing=chloe, op=JsonReportAccepted)`.
+impl core::fmt::Display for ThingDefender<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "defender(thing={}, op={:?})", self.thing_name, self.api)
+    }
+}
+
+impl ThingDefender<'_> {
+    /// Always [`TopicType::Defender`](crate::TopicType::Defender).
+    pub fn subsystem(&self) -> crate::TopicType {
+        crate::TopicType::Defender
+    }
+}
+
+impl From<&ThingDefender<'_>> for crate::TopicType {
+    fn from(defender: &ThingDefender<'_>) -> Self {
+        defender.subsystem()
+    }
+}
+
 /// Check if the given topic is one of the Device Defender topics.
 ///
 /// # Example
@@ -91,20 +339,43 @@ fn suffix(topic_type: &Topic) -> &str {
 /// assert_eq!(defender.thing_name, "chloe");
 /// assert_eq!(defender.api, defender::Topic::JsonReportAccepted)
 /// ```
-pub fn match_topic(topic: &str) -> Result<ThingDefender, Error> {
-    is_valid_mqtt_topic(topic)?;
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic(topic: &str) -> Result<ThingDefender<'_>, Error> {
+    match_topic_with(&TopicConfig::default(), topic)
+}
 
-    let s = is_valid_prefix(topic, AWS_THINGS_PREFIX)?;
+/// Same as [`match_topic`], with the thing prefix of `config`.
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_with<'a>(
+    config: &TopicConfig,
+    topic: &'a str,
+) -> Result<ThingDefender<'a>, Error> {
+    let result = parse_thing_prefix_with(config, topic)
+        .and_then(|(thing_name, s)| match_rest(thing_name, s));
+    log_match("defender", topic, result)
+}
 
-    let mid = s.find('/').ok_or(Error::FAIL);
-    let (thing_name, mut s) = s.split_at(mid?);
-    is_valid_thing_name(thing_name)?;
+/// Match the rest of a topic, following the thing name, see [`ThingTopic`].
+pub(crate) fn match_rest<'a>(
+    thing_name: &'a str,
+    mut s: &'a str,
+) -> Result<ThingDefender<'a>, Error> {
+    is_valid_levels(s)?;
 
     s = is_valid_bridge(s, DEFENDER_API_BRIDGE)?;
 
-    let v: ArrayVec<&str, 16> = s.split('/').collect();
+    let v = collect_levels::<16>(s)?;
     let api: Topic;
     match v[..] {
+        // ~$aws/things/<thingName>/defender/metrics/~<format>
+        [op] => {
+            match op {
+                API_JSON_FORMAT => api = JsonReportPublish,
+                API_CBOR_FORMAT => api = CborReportPublish,
+                _ => return Err(Error::NoMatch),
+            }
+            Ok(ThingDefender { thing_name, api })
+        }
         // ~$aws/things/<thingName>/defender/metrics/~<format>/suffix
         [op, suffix] => {
             match (op, suffix) {
@@ -120,10 +391,857 @@ pub fn match_topic(topic: &str) -> Result<ThingDefender, Error> {
         _ => Err(Error::NoMatch),
     }
 }
+
+/// Same as [`match_topic`], for a filter with `+` as the thing name, see
+/// [`shadow::match_filter`](crate::shadow::match_filter).
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::defender;
+///
+/// let filter = defender::match_filter("$aws/things/+/defender/metrics/json/accepted").unwrap();
+/// assert_eq!(filter.thing_name, "+");
+/// assert_eq!(filter.api, defender::Topic::JsonReportAccepted);
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_filter(filter: &str) -> Result<ThingDefender<'_>, Error> {
+    let result =
+        parse_thing_filter(filter).and_then(|(thing_name, rest)| match_rest(thing_name, rest));
+    log_match("defender", filter, result)
+}
+
+/// Same as [`match_topic`], for topics handed over as raw bytes by the MQTT
+/// client.
+///
+/// A topic which is not valid UTF-8 is rejected with `Error::MqttTopicFailed`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::defender;
+///
+/// let defender =
+///     defender::match_topic_bytes(b"$aws/things/chloe/defender/metrics/cbor/rejected").unwrap();
+/// assert_eq!(defender.api, defender::Topic::CborReportRejected);
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_bytes(topic: &[u8]) -> Result<ThingDefender<'_>, Error> {
+    match topic_from_bytes(topic) {
+        Ok(topic) => match_topic(topic),
+        Err(e) => log_match("defender", topic, Err(e)),
+    }
+}
+
+/// Check that `topic` is a valid Device Defender topic, matching it fully as
+/// [`match_topic`] does. See [`shadow::validate_topic`](crate::shadow::validate_topic).
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{defender, Error};
+///
+/// assert_eq!(defender::validate_topic("$aws/things/chloe/defender/metrics/cbor/rejected"), Ok(()));
+/// assert_eq!(defender::validate_topic("$aws/things/chloe/defender/metrics/xml"), Err(Error::NoMatch));
+/// ```
+pub fn validate_topic(topic: &str) -> Result<(), Error> {
+    match_topic(topic).map(|_| ())
+}
+
+/// Byte and packet counters of the network interfaces.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkStats {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub packets_in: u64,
+    pub packets_out: u64,
+}
+
+/// A listening TCP or UDP port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Port<'a> {
+    pub interface: Option<&'a str>,
+    pub port: u16,
+}
+
+/// An established TCP connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Connection<'a> {
+    pub local_interface: Option<&'a str>,
+    pub local_port: Option<u16>,
+    /// The remote address and port, like `"192.168.0.1:8000"`.
+    pub remote_addr: &'a str,
+}
+
+/// The header of a Device Defender report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// A unique, monotonically increasing id, usually the Unix time.
+    pub report_id: u64,
+    pub version: &'static str,
+}
+
+/// Generates the increasing ids every report needs.
+///
+/// Devices commonly use the current Unix time, which [`ReportId::next_at`]
+/// takes while still increasing if the clock goes back, or two reports are
+/// made within a second. Without a clock, [`ReportId::next_id`] counts up
+/// from a seed, e.g. persisted across reboots.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::defender::ReportId;
+///
+/// let mut ids = ReportId::new(0);
+/// assert_eq!(ids.next_at(1530304554), 1530304554);
+/// // Same second, or a clock which went back.
+/// assert_eq!(ids.next_at(1530304554), 1530304555);
+/// assert_eq!(ids.next_at(1530304500), 1530304556);
+/// let report = ids.new_report(1530304600);
+/// assert_eq!(report.header.report_id, 1530304600);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportId {
+    next: u64,
+}
+
+impl ReportId {
+    /// Start at `seed`, the first id handed out.
+    pub fn new(seed: u64) -> ReportId {
+        ReportId { next: seed }
+    }
+    /// The next id of the counter.
+    pub fn next_id(&mut self) -> u64 {
+        self.next_at(0)
+    }
+    /// The next id, `timestamp` unless an id that large was already handed
+    /// out. Ids stay at `u64::MAX` once they get there.
+    pub fn next_at(&mut self, timestamp: u64) -> u64 {
+        let id = self.next.max(timestamp);
+        self.next = id.saturating_add(1);
+        id
+    }
+    /// Start an empty report with the next id, see [`ReportId::next_at`].
+    pub fn new_report<'a>(&mut self, timestamp: u64) -> Report<'a> {
+        Report::new(self.next_at(timestamp))
+    }
+}
+
+/// A Device Defender metrics report, serialized with the long field names of
+/// <https://docs.aws.amazon.com/iot/latest/developerguide/detect-device-side-metrics.html>.
+///
+/// Every metric section is optional and left out of the report when `None`.
+/// The port and connection lists are bounded, and serialized with a `total`
+/// alongside them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report<'a> {
+    pub header: Header,
+    pub listening_tcp_ports: Option<ArrayVec<Port<'a>, REPORT_PORTS_MAX>>,
+    pub listening_udp_ports: Option<ArrayVec<Port<'a>, REPORT_PORTS_MAX>>,
+    pub network_stats: Option<NetworkStats>,
+    pub tcp_connections: Option<ArrayVec<Connection<'a>, REPORT_CONNECTIONS_MAX>>,
+}
+
+impl<'a> Report<'a> {
+    /// Start an empty report.
+    pub fn new(report_id: u64) -> Report<'a> {
+        Report {
+            header: Header {
+                report_id,
+                version: REPORT_VERSION,
+            },
+            listening_tcp_ports: None,
+            listening_udp_ports: None,
+            network_stats: None,
+            tcp_connections: None,
+        }
+    }
+    /// Add a listening TCP port, failing when the list is full.
+    pub fn add_listening_tcp_port(&mut self, port: Port<'a>) -> Result<(), Error> {
+        push_port(&mut self.listening_tcp_ports, port)
+    }
+    /// Add a listening UDP port, failing when the list is full.
+    pub fn add_listening_udp_port(&mut self, port: Port<'a>) -> Result<(), Error> {
+        push_port(&mut self.listening_udp_ports, port)
+    }
+    /// Add an established TCP connection, failing when the list is full.
+    pub fn add_tcp_connection(&mut self, connection: Connection<'a>) -> Result<(), Error> {
+        is_valid_json_str(connection.remote_addr)?;
+        if let Some(interface) = connection.local_interface {
+            is_valid_json_str(interface)?;
+        }
+        self.tcp_connections
+            .get_or_insert_with(ArrayVec::new)
+            .try_push(connection)
+            .map_err(|_| Error::FAIL)
+    }
+    /// Add the counters of one network interface to the network stats.
+    ///
+    /// The report only carries totals across interfaces, so adding every
+    /// interface one by one yields the device-wide stats.
+    pub fn add_interface_stats(&mut self, stats: NetworkStats) {
+        let total = self.network_stats.get_or_insert_with(NetworkStats::default);
+        total.bytes_in = total.bytes_in.saturating_add(stats.bytes_in);
+        total.bytes_out = total.bytes_out.saturating_add(stats.bytes_out);
+        total.packets_in = total.packets_in.saturating_add(stats.packets_in);
+        total.packets_out = total.packets_out.saturating_add(stats.packets_out);
+    }
+    /// Check the report against the constraints of Device Defender, so that
+    /// a malformed report fails on the device rather than being rejected
+    /// after a publish. Fails with `Error::InvalidReport` and:
+    ///
+    /// - `ReportError::MissingVersion` if the version isn't set,
+    /// - `ReportError::NoMetrics` if the report carries no metric section,
+    /// - `ReportError::ZeroPort` if a listening port or the local port of a
+    ///   connection is 0,
+    /// - `ReportError::CounterOverflow` if a network counter exceeds
+    ///   `i64::MAX`, as the service stores them as signed 64 bits; a
+    ///   saturated [`Report::add_interface_stats`] sum does,
+    /// - `ReportError::InvalidString` if an interface or a remote address is
+    ///   empty or would need JSON escapes. The `add_*` methods check them,
+    ///   but the lists are public and may be filled directly.
+    ///
+    /// The report id is any `u64`, Device Defender only needs it to
+    /// increase, see [`ReportId`].
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::defender::{Port, Report};
+    /// use aws_iot_device_sdk::{Error, ReportError};
+    ///
+    /// let mut report = Report::new(1530304554);
+    /// assert_eq!(report.validate(), Err(Error::InvalidReport(ReportError::NoMetrics)));
+    /// report.add_listening_tcp_port(Port { interface: None, port: 22 }).unwrap();
+    /// assert!(report.validate().is_ok());
+    /// ```
+    pub fn validate(&self) -> Result<(), Error> {
+        self.check().map_err(Error::InvalidReport)
+    }
+
+    fn check(&self) -> Result<(), ReportError> {
+        if self.header.version.is_empty() {
+            return Err(ReportError::MissingVersion);
+        }
+        if self.listening_tcp_ports.is_none()
+            && self.listening_udp_ports.is_none()
+            && self.network_stats.is_none()
+            && self.tcp_connections.is_none()
+        {
+            return Err(ReportError::NoMetrics);
+        }
+        let ports = self
+            .listening_tcp_ports
+            .iter()
+            .chain(&self.listening_udp_ports)
+            .flatten();
+        for port in ports {
+            if port.port == 0 {
+                return Err(ReportError::ZeroPort);
+            }
+            check_str(port.interface)?;
+        }
+        for connection in self.tcp_connections.iter().flatten() {
+            if connection.local_port == Some(0) {
+                return Err(ReportError::ZeroPort);
+            }
+            check_str(connection.local_interface)?;
+            check_str(Some(connection.remote_addr))?;
+        }
+        if let Some(stats) = &self.network_stats {
+            let counters = [
+                stats.bytes_in,
+                stats.bytes_out,
+                stats.packets_in,
+                stats.packets_out,
+            ];
+            if counters.iter().any(|&counter| counter > i64::MAX as u64) {
+                return Err(ReportError::CounterOverflow);
+            }
+        }
+        Ok(())
+    }
+    /// Serialize the report to JSON, failing if it doesn't fit in `N` bytes.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::defender::{Port, Report};
+    ///
+    /// let mut report = Report::new(1530304554);
+    /// report
+    ///     .add_listening_udp_port(Port { interface: None, port: 5353 })
+    ///     .unwrap();
+    /// let json = report.to_json::<256>().unwrap();
+    /// assert_eq!(
+    ///     &json[..],
+    ///     r#"{"header":{"report_id":1530304554,"version":"1.0"},"metrics":{"listening_udp_ports":{"ports":[{"port":5353}],"total":1}}}"#
+    /// );
+    /// ```
+    pub fn to_json<const N: usize>(&self) -> Result<ArrayString<N>, Error> {
+        let mut s = ArrayString::<N>::new();
+        self.write_json(&mut s).map_err(|_| Error::FAIL)?;
+        Ok(s)
+    }
+
+    /// Serialize the report to CBOR, failing if it doesn't fit in `N`
+    /// bytes. The document is the same as [`Report::to_json`], with the
+    /// same long field names, for the `cbor` topics.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::defender::{NetworkStats, Report};
+    ///
+    /// let mut report = Report::new(1530304554);
+    /// report.add_interface_stats(NetworkStats { bytes_in: 100, ..Default::default() });
+    /// let cbor = report.to_cbor::<256>().unwrap();
+    /// // A map of two entries, the first one being "header".
+    /// assert_eq!(&cbor[..8], b"\xa2\x66header");
+    /// ```
+    pub fn to_cbor<const N: usize>(&self) -> Result<ArrayVec<u8, N>, Error> {
+        let metrics = self.metrics();
+        let mut out = ArrayVec::new();
+        write_cbor_header(&mut out, &self.header, metrics.len() as u64)?;
+        for metric in &metrics {
+            write_cbor_metric(&mut out, metric)?;
+        }
+        Ok(out)
+    }
+
+    /// The metric sections of the report, in serialization order.
+    fn metrics(&self) -> ArrayVec<Metric<'_>, 4> {
+        let mut metrics = ArrayVec::new();
+        if let Some(ports) = &self.listening_tcp_ports {
+            metrics.push(Metric::ListeningTcpPorts(ports));
+        }
+        if let Some(ports) = &self.listening_udp_ports {
+            metrics.push(Metric::ListeningUdpPorts(ports));
+        }
+        if let Some(stats) = self.network_stats {
+            metrics.push(Metric::NetworkStats(stats));
+        }
+        if let Some(connections) = &self.tcp_connections {
+            metrics.push(Metric::TcpConnections(connections));
+        }
+        metrics
+    }
+
+    fn write_json(&self, w: &mut impl Write) -> core::fmt::Result {
+        write_header(w, &self.header)?;
+        let mut first = true;
+        for metric in &self.metrics() {
+            write_json_metric(w, &mut first, metric)?;
+        }
+        w.write_str("}}")
+    }
+}
+
+/// A metric section of a report, for [`ReportWriter::add_metric`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric<'a> {
+    ListeningTcpPorts(&'a [Port<'a>]),
+    ListeningUdpPorts(&'a [Port<'a>]),
+    NetworkStats(NetworkStats),
+    TcpConnections(&'a [Connection<'a>]),
+}
+
+/// Writes a JSON or CBOR report section by section into a caller provided
+/// buffer, for devices which can't hold a whole [`Report`] in RAM. The
+/// output is the same as [`Report::to_json`] or [`Report::to_cbor`] for the
+/// same sections in the same order.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::defender::{Metric, NetworkStats, ReportWriter};
+///
+/// let mut buf = [0; 256];
+/// let mut writer = ReportWriter::begin(&mut buf, 1530304554).unwrap();
+/// writer.add_metric(Metric::NetworkStats(NetworkStats { bytes_in: 29358693495, ..Default::default() })).unwrap();
+/// let len = writer.finish().unwrap();
+/// assert!(buf[..len].starts_with(br#"{"header":{"report_id":1530304554,"version":"1.0"},"metrics":{"network_stats""#));
+/// ```
+#[derive(Debug)]
+pub struct ReportWriter<'b> {
+    out: SliceWriter<'b>,
+    format: ReportFormat,
+    sections: u64,
+    /// Where the CBOR metrics map starts.
+    metrics_at: usize,
+}
+
+impl<'b> ReportWriter<'b> {
+    /// Write the header of a JSON report, failing if `buf` is too small.
+    pub fn begin(buf: &'b mut [u8], report_id: u64) -> Result<Self, Error> {
+        Self::begin_with(buf, report_id, ReportFormat::Json)
+    }
+    /// Write the header of a report in `format`, failing if `buf` is too
+    /// small.
+    pub fn begin_with(
+        buf: &'b mut [u8],
+        report_id: u64,
+        format: ReportFormat,
+    ) -> Result<Self, Error> {
+        let mut out = SliceWriter { buf, len: 0 };
+        let header = Header {
+            report_id,
+            version: REPORT_VERSION,
+        };
+        match format {
+            ReportFormat::Json => write_header(&mut out, &header).map_err(|_| Error::FAIL)?,
+            // The count of sections is patched in `finish`.
+            ReportFormat::Cbor => write_cbor_header(&mut out, &header, 0)?,
+        }
+        Ok(ReportWriter {
+            metrics_at: out.len - 1,
+            out,
+            format,
+            sections: 0,
+        })
+    }
+    /// Append a metric section, failing if the buffer is full, after which
+    /// the report is truncated and must be started over. The strings are
+    /// checked as in [`Report`].
+    pub fn add_metric(&mut self, metric: Metric) -> Result<(), Error> {
+        check_metric(&metric).map_err(Error::InvalidReport)?;
+        match self.format {
+            ReportFormat::Json => {
+                let mut first = self.sections == 0;
+                write_json_metric(&mut self.out, &mut first, &metric).map_err(|_| Error::FAIL)?;
+            }
+            ReportFormat::Cbor => {
+                // The count has to fit in the single byte of the header.
+                if self.sections == CBOR_TINY_MAX {
+                    return Err(Error::FAIL);
+                }
+                write_cbor_metric(&mut self.out, &metric)?;
+            }
+        }
+        self.sections += 1;
+        Ok(())
+    }
+    /// Close the report, returning its length in the buffer.
+    pub fn finish(mut self) -> Result<usize, Error> {
+        match self.format {
+            ReportFormat::Json => self.out.write_str("}}").map_err(|_| Error::FAIL)?,
+            ReportFormat::Cbor => {
+                self.out.buf[self.metrics_at] = CBOR_MAP << 5 | self.sections as u8;
+            }
+        }
+        Ok(self.out.len)
+    }
+}
+
+/// A `Write` into a byte buffer, failing once it's full.
+#[derive(Debug)]
+struct SliceWriter<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        let dst = self.buf.get_mut(self.len..end).ok_or(core::fmt::Error)?;
+        dst.copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// A destination for the CBOR encoding, which unlike JSON isn't text.
+trait ByteSink {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Error>;
+}
+
+impl ByteSink for SliceWriter<'_> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.len + bytes.len();
+        let dst = self.buf.get_mut(self.len..end).ok_or(Error::FAIL)?;
+        dst.copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+}
+
+impl<const N: usize> ByteSink for ArrayVec<u8, N> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.try_extend_from_slice(bytes).map_err(|_| Error::FAIL)
+    }
+}
+
+// The CBOR major types of RFC 8949 the reports need.
+const CBOR_UNSIGNED: u8 = 0;
+const CBOR_TEXT: u8 = 3;
+const CBOR_ARRAY: u8 = 4;
+const CBOR_MAP: u8 = 5;
+/// The largest argument held in the initial byte itself.
+const CBOR_TINY_MAX: u64 = 23;
+
+/// The initial bytes of a data item, with the argument in its shortest
+/// form, as the deterministic encoding of RFC 8949 requires.
+fn cbor_head(w: &mut impl ByteSink, major: u8, argument: u64) -> Result<(), Error> {
+    let major = major << 5;
+    match argument {
+        0..=CBOR_TINY_MAX => w.put(&[major | argument as u8]),
+        24..=0xff => w.put(&[major | 24, argument as u8]),
+        0x100..=0xffff => {
+            w.put(&[major | 25])?;
+            w.put(&(argument as u16).to_be_bytes())
+        }
+        0x1_0000..=0xffff_ffff => {
+            w.put(&[major | 26])?;
+            w.put(&(argument as u32).to_be_bytes())
+        }
+        _ => {
+            w.put(&[major | 27])?;
+            w.put(&argument.to_be_bytes())
+        }
+    }
+}
+
+fn cbor_text(w: &mut impl ByteSink, s: &str) -> Result<(), Error> {
+    cbor_head(w, CBOR_TEXT, s.len() as u64)?;
+    w.put(s.as_bytes())
+}
+
+/// A text key followed by an unsigned value.
+fn cbor_uint_entry(w: &mut impl ByteSink, key: &str, value: u64) -> Result<(), Error> {
+    cbor_text(w, key)?;
+    cbor_head(w, CBOR_UNSIGNED, value)
+}
+
+/// The CBOR counterpart of `write_header`, with the metrics map holding
+/// `sections` entries.
+fn write_cbor_header(w: &mut impl ByteSink, header: &Header, sections: u64) -> Result<(), Error> {
+    cbor_head(w, CBOR_MAP, 2)?;
+    cbor_text(w, "header")?;
+    cbor_head(w, CBOR_MAP, 2)?;
+    cbor_uint_entry(w, "report_id", header.report_id)?;
+    cbor_text(w, "version")?;
+    cbor_text(w, header.version)?;
+    cbor_text(w, "metrics")?;
+    cbor_head(w, CBOR_MAP, sections)
+}
+
+/// The CBOR counterpart of `write_json_metric`.
+fn write_cbor_metric(w: &mut impl ByteSink, metric: &Metric) -> Result<(), Error> {
+    match *metric {
+        Metric::ListeningTcpPorts(ports) => write_cbor_ports(w, "listening_tcp_ports", ports),
+        Metric::ListeningUdpPorts(ports) => write_cbor_ports(w, "listening_udp_ports", ports),
+        Metric::NetworkStats(stats) => {
+            cbor_text(w, "network_stats")?;
+            cbor_head(w, CBOR_MAP, 4)?;
+            cbor_uint_entry(w, "bytes_in", stats.bytes_in)?;
+            cbor_uint_entry(w, "bytes_out", stats.bytes_out)?;
+            cbor_uint_entry(w, "packets_in", stats.packets_in)?;
+            cbor_uint_entry(w, "packets_out", stats.packets_out)
+        }
+        Metric::TcpConnections(connections) => {
+            cbor_text(w, "tcp_connections")?;
+            cbor_head(w, CBOR_MAP, 1)?;
+            cbor_text(w, "established_connections")?;
+            cbor_head(w, CBOR_MAP, 2)?;
+            cbor_text(w, "connections")?;
+            cbor_head(w, CBOR_ARRAY, connections.len() as u64)?;
+            for c in connections {
+                let fields = 1 + c.local_interface.is_some() as u64 + c.local_port.is_some() as u64;
+                cbor_head(w, CBOR_MAP, fields)?;
+                if let Some(interface) = c.local_interface {
+                    cbor_text(w, "local_interface")?;
+                    cbor_text(w, interface)?;
+                }
+                if let Some(port) = c.local_port {
+                    cbor_uint_entry(w, "local_port", port.into())?;
+                }
+                cbor_text(w, "remote_addr")?;
+                cbor_text(w, c.remote_addr)?;
+            }
+            cbor_uint_entry(w, "total", connections.len() as u64)
+        }
+    }
+}
+
+fn write_cbor_ports(w: &mut impl ByteSink, name: &str, ports: &[Port]) -> Result<(), Error> {
+    cbor_text(w, name)?;
+    cbor_head(w, CBOR_MAP, 2)?;
+    cbor_text(w, "ports")?;
+    cbor_head(w, CBOR_ARRAY, ports.len() as u64)?;
+    for p in ports {
+        cbor_head(w, CBOR_MAP, 1 + p.interface.is_some() as u64)?;
+        if let Some(interface) = p.interface {
+            cbor_text(w, "interface")?;
+            cbor_text(w, interface)?;
+        }
+        cbor_uint_entry(w, "port", p.port.into())?;
+    }
+    cbor_uint_entry(w, "total", ports.len() as u64)
+}
+
+fn write_header(w: &mut impl Write, header: &Header) -> core::fmt::Result {
+    w.write_str(r#"{"header":{"report_id":"#)?;
+    w.write_str(&u64_to_str(header.report_id))?;
+    w.write_str(r#","version":""#)?;
+    w.write_str(header.version)?;
+    w.write_str(r#""},"metrics":{"#)
+}
+
+fn check_str(s: Option<&str>) -> Result<(), ReportError> {
+    match s {
+        Some(s) => is_valid_json_str(s).map_err(|_| ReportError::InvalidString),
+        None => Ok(()),
+    }
+}
+
+fn push_port<'a>(
+    ports: &mut Option<ArrayVec<Port<'a>, REPORT_PORTS_MAX>>,
+    port: Port<'a>,
+) -> Result<(), Error> {
+    if let Some(interface) = port.interface {
+        is_valid_json_str(interface)?;
+    }
+    ports
+        .get_or_insert_with(ArrayVec::new)
+        .try_push(port)
+        .map_err(|_| Error::FAIL)
+}
+
+fn check_metric(metric: &Metric) -> Result<(), ReportError> {
+    match *metric {
+        Metric::ListeningTcpPorts(ports) | Metric::ListeningUdpPorts(ports) => {
+            ports.iter().try_for_each(|p| check_str(p.interface))
+        }
+        Metric::TcpConnections(connections) => connections.iter().try_for_each(|c| {
+            check_str(c.local_interface)?;
+            check_str(Some(c.remote_addr))
+        }),
+        Metric::NetworkStats(_) => Ok(()),
+    }
+}
+
+fn write_json_metric(w: &mut impl Write, first: &mut bool, metric: &Metric) -> core::fmt::Result {
+    match *metric {
+        Metric::ListeningTcpPorts(ports) => write_ports(w, first, "listening_tcp_ports", ports),
+        Metric::ListeningUdpPorts(ports) => write_ports(w, first, "listening_udp_ports", ports),
+        Metric::NetworkStats(stats) => write_network_stats(w, first, &stats),
+        Metric::TcpConnections(connections) => write_connections(w, first, connections),
+    }
+}
+
+fn write_separator(w: &mut impl Write, first: &mut bool) -> core::fmt::Result {
+    if !*first {
+        w.write_char(',')?;
+    }
+    *first = false;
+    Ok(())
+}
+
+fn write_network_stats(
+    w: &mut impl Write,
+    first: &mut bool,
+    stats: &NetworkStats,
+) -> core::fmt::Result {
+    write_separator(w, first)?;
+    w.write_str(r#""network_stats":{"bytes_in":"#)?;
+    w.write_str(&u64_to_str(stats.bytes_in))?;
+    w.write_str(r#","bytes_out":"#)?;
+    w.write_str(&u64_to_str(stats.bytes_out))?;
+    w.write_str(r#","packets_in":"#)?;
+    w.write_str(&u64_to_str(stats.packets_in))?;
+    w.write_str(r#","packets_out":"#)?;
+    w.write_str(&u64_to_str(stats.packets_out))?;
+    w.write_char('}')
+}
+
+fn write_connections(
+    w: &mut impl Write,
+    first: &mut bool,
+    connections: &[Connection],
+) -> core::fmt::Result {
+    write_separator(w, first)?;
+    w.write_str(r#""tcp_connections":{"established_connections":{"connections":["#)?;
+    for (i, c) in connections.iter().enumerate() {
+        if i > 0 {
+            w.write_char(',')?;
+        }
+        w.write_char('{')?;
+        if let Some(interface) = c.local_interface {
+            w.write_str(r#""local_interface":""#)?;
+            w.write_str(interface)?;
+            w.write_str(r#"","#)?;
+        }
+        if let Some(port) = c.local_port {
+            w.write_str(r#""local_port":"#)?;
+            w.write_str(&u64_to_str(port.into()))?;
+            w.write_char(',')?;
+        }
+        w.write_str(r#""remote_addr":""#)?;
+        w.write_str(c.remote_addr)?;
+        w.write_str(r#""}"#)?;
+    }
+    w.write_str(r#"],"total":"#)?;
+    w.write_str(&u64_to_str(connections.len() as u64))?;
+    w.write_str("}}")
+}
+
+fn write_ports(
+    w: &mut impl Write,
+    first: &mut bool,
+    name: &str,
+    ports: &[Port],
+) -> core::fmt::Result {
+    write_separator(w, first)?;
+    w.write_char('"')?;
+    w.write_str(name)?;
+    w.write_str(r#"":{"ports":["#)?;
+    for (i, p) in ports.iter().enumerate() {
+        if i > 0 {
+            w.write_char(',')?;
+        }
+        w.write_char('{')?;
+        if let Some(interface) = p.interface {
+            w.write_str(r#""interface":""#)?;
+            w.write_str(interface)?;
+            w.write_str(r#"","#)?;
+        }
+        w.write_str(r#""port":"#)?;
+        w.write_str(&u64_to_str(p.port.into()))?;
+        w.write_char('}')?;
+    }
+    w.write_str(r#"],"total":"#)?;
+    w.write_str(&u64_to_str(ports.len() as u64))?;
+    w.write_char('}')
+}
+
+/// Check that a payload is in the encoding of the topic it came with,
+/// according to [`detect_format`], before decoding it. A mismatch fails
+/// with `Error::FormatMismatch`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{defender, Error, PayloadFormat};
+///
+/// assert_eq!(defender::check_format(br#"{"status":"ACCEPTED"}"#, PayloadFormat::Json), Ok(()));
+/// assert_eq!(
+///     defender::check_format(&[0xa1, 0x61, 0x61, 0x01], PayloadFormat::Json),
+///     Err(Error::FormatMismatch)
+/// );
+/// ```
+pub fn check_format(payload: &[u8], format: ReportFormat) -> Result<(), Error> {
+    if detect_format(payload) != format {
+        return Err(Error::FormatMismatch);
+    }
+    Ok(())
+}
+
+/// The payload of a JSON `accepted` or `rejected` report response.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub struct ReportResponse<'a> {
+    #[serde(rename = "thingName")]
+    pub thing_name: &'a str,
+    #[serde(rename = "reportId")]
+    pub report_id: u64,
+    /// `ACCEPTED` or `REJECTED`.
+    pub status: &'a str,
+    pub timestamp: Option<u64>,
+    /// Only present on a rejected report.
+    #[serde(rename = "statusDetails", borrow)]
+    pub status_details: Option<ReportStatusDetails<'a>>,
+}
+
+/// Why a report was rejected.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub struct ReportStatusDetails<'a> {
+    #[serde(rename = "ErrorCode")]
+    pub error_code: &'a str,
+    #[serde(rename = "ErrorMessage")]
+    pub error_message: Option<&'a str>,
+}
+
+/// Parse the payload of a `json/accepted` or `json/rejected` response.
+///
+/// A CBOR payload fails with `Error::FormatMismatch` rather than
+/// `Error::PayloadParseFailed`, see [`check_format`].
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::defender;
+///
+/// let payload = br#"{"thingName":"chloe","reportId":1530304554,"status":"ACCEPTED","timestamp":1530304555}"#;
+/// let response = defender::parse_json_response(payload).unwrap();
+/// assert_eq!(response.report_id, 1530304554);
+/// assert!(response.status_details.is_none());
+/// ```
+#[cfg(feature = "serde")]
+pub fn parse_json_response(payload: &[u8]) -> Result<ReportResponse<'_>, Error> {
+    check_format(payload, ReportFormat::Json)?;
+    serde_json::from_slice(payload).map_err(|_| Error::PayloadParseFailed)
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::common::*;
     use crate::defender;
     #[test]
+    fn check_format() {
+        use crate::PayloadFormat::*;
+        // {"a": 1} in CBOR, a map of one pair.
+        let cbor = [0xa1, 0x61, 0x61, 0x01];
+        assert_eq!(defender::check_format(&cbor, Cbor), Ok(()));
+        assert_eq!(
+            defender::check_format(&cbor, Json),
+            Err(Error::FormatMismatch)
+        );
+        assert_eq!(
+            defender::check_format(b" {}", Cbor),
+            Err(Error::FormatMismatch)
+        );
+        assert_eq!(Error::FormatMismatch.kind(), ErrorKind::Parse);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_json_response() {
+        let payload = br#"{"thingName":"chloe","reportId":1530304554,"status":"REJECTED","statusDetails":{"ErrorCode":"InvalidPayload","ErrorMessage":"Malformed metrics"},"timestamp":1530304555}"#;
+        let response = defender::parse_json_response(payload).unwrap();
+        assert_eq!(response.status, "REJECTED");
+        let details = response.status_details.unwrap();
+        assert_eq!(details.error_code, "InvalidPayload");
+        assert_eq!(details.error_message, Some("Malformed metrics"));
+
+        // The same response in CBOR, from a handler subscribed to the wrong
+        // format: {"status": "ACCEPTED"}.
+        let mut cbor = vec![0xa1, 0x66];
+        cbor.extend_from_slice(b"status");
+        cbor.push(0x68);
+        cbor.extend_from_slice(b"ACCEPTED");
+        assert_eq!(
+            defender::parse_json_response(&cbor),
+            Err(Error::FormatMismatch)
+        );
+        assert_eq!(
+            defender::parse_json_response(br#"{"status":"ACCEPTED"}"#),
+            Err(Error::PayloadParseFailed)
+        );
+    }
+    #[test]
+    fn match_topic_publish() {
+        let defender = defender::match_topic("$aws/things/chloe/defender/metrics/json").unwrap();
+        assert_eq!(defender.thing_name, "chloe");
+        assert_eq!(defender.api, defender::Topic::JsonReportPublish);
+        let defender = defender::match_topic("$aws/things/chloe/defender/metrics/cbor").unwrap();
+        assert_eq!(defender.api, defender::Topic::CborReportPublish);
+        let defender = defender::match_topic("$aws/things/chloe/defender/metrics/xml");
+        assert_eq!(defender.err(), Some(Error::NoMatch));
+    }
+    #[test]
+    fn defender_match_topic_bytes() {
+        let defender =
+            defender::match_topic_bytes(b"$aws/things/chloe/defender/metrics/json/accepted")
+                .unwrap();
+        assert_eq!(defender.thing_name, "chloe");
+        assert_eq!(defender.api, defender::Topic::JsonReportAccepted);
+
+        let defender =
+            defender::match_topic_bytes(b"$aws/things/\xff/defender/metrics/json/accepted");
+        assert_eq!(defender.err(), Some(Error::MqttTopicFailed));
+    }
+    #[test]
     fn assemble_topic_json() {
         let topic = defender::assemble_topic("chloe", defender::Topic::JsonReportPublish).unwrap();
         assert_eq!(&topic[..], "$aws/things/chloe/defender/metrics/json");
@@ -138,6 +1256,113 @@ mod tests {
         );
     }
     #[test]
+    fn assemble_topic_max_length_name() {
+        let thing_name = "t".repeat(THINGNAME_MAX_LENGTH);
+        let topic =
+            defender::assemble_topic(&thing_name, defender::Topic::CborReportAccepted).unwrap();
+        assert!(topic.ends_with("/defender/metrics/cbor/accepted"));
+    }
+    #[test]
+    fn topic_name_round_trip() {
+        for &topic in defender::ALL_TOPICS {
+            assert_eq!(defender::Topic::try_from(topic.as_str()), Ok(topic));
+        }
+        assert_eq!(
+            defender::Topic::try_from(""),
+            Err(Error::MessageTypeParseFailed)
+        );
+        assert_eq!(
+            defender::Topic::try_from("foo/accepted"),
+            Err(Error::MessageTypeParseFailed)
+        );
+        let topic = defender::Topic::JsonReportAccepted;
+        assert_eq!(defender::from_name(topic.as_str()), Ok(topic));
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn topic_serde() {
+        let json = serde_json::to_string(&defender::Topic::CborReportPublish).unwrap();
+        assert_eq!(json, "\"cbor\"");
+        let topic: defender::Topic = serde_json::from_str(&json).unwrap();
+        assert_eq!(topic, defender::Topic::CborReportPublish);
+    }
+    #[test]
+    fn report_to_json() {
+        // The example report of the AWS IoT Device Defender documentation
+        let mut report = defender::Report::new(1530304554);
+        let port = |port| defender::Port {
+            interface: Some("eth0"),
+            port,
+        };
+        report.add_listening_tcp_port(port(24800)).unwrap();
+        report.add_listening_udp_port(port(5353)).unwrap();
+        report.add_interface_stats(defender::NetworkStats {
+            bytes_in: 29358693000,
+            bytes_out: 26485000,
+            packets_in: 10013573000,
+            packets_out: 11382000,
+        });
+        report.add_interface_stats(defender::NetworkStats {
+            bytes_in: 495,
+            bytes_out: 35,
+            packets_in: 555,
+            packets_out: 615,
+        });
+        report
+            .add_tcp_connection(defender::Connection {
+                local_interface: Some("eth0"),
+                local_port: Some(80),
+                remote_addr: "192.168.0.1:8000",
+            })
+            .unwrap();
+        let json = report.to_json::<1024>().unwrap();
+        assert_eq!(
+            &json[..],
+            concat!(
+                r#"{"header":{"report_id":1530304554,"version":"1.0"},"metrics":{"#,
+                r#""listening_tcp_ports":{"ports":[{"interface":"eth0","port":24800}],"total":1},"#,
+                r#""listening_udp_ports":{"ports":[{"interface":"eth0","port":5353}],"total":1},"#,
+                r#""network_stats":{"bytes_in":29358693495,"bytes_out":26485035,"#,
+                r#""packets_in":10013573555,"packets_out":11382615},"#,
+                r#""tcp_connections":{"established_connections":{"connections":["#,
+                r#"{"local_interface":"eth0","local_port":80,"remote_addr":"192.168.0.1:8000"}"#,
+                r#"],"total":1}}}}"#
+            )
+        );
+        assert_eq!(report.to_json::<64>(), Err(Error::FAIL));
+    }
+    #[test]
+    fn report_empty_sections() {
+        let mut report = defender::Report::new(1);
+        report.listening_tcp_ports = Some(arrayvec::ArrayVec::new());
+        let json = report.to_json::<256>().unwrap();
+        assert_eq!(
+            &json[..],
+            r#"{"header":{"report_id":1,"version":"1.0"},"metrics":{"listening_tcp_ports":{"ports":[],"total":0}}}"#
+        );
+    }
+    #[test]
+    fn report_bounds() {
+        let mut report = defender::Report::new(1);
+        for port in 0..defender::REPORT_PORTS_MAX as u16 {
+            let port = defender::Port {
+                interface: None,
+                port,
+            };
+            report.add_listening_tcp_port(port).unwrap();
+        }
+        let port = defender::Port {
+            interface: None,
+            port: 1,
+        };
+        assert_eq!(report.add_listening_tcp_port(port), Err(Error::FAIL));
+        let port = defender::Port {
+            interface: Some("eth\"0"),
+            port: 1,
+        };
+        assert_eq!(report.add_listening_udp_port(port), Err(Error::FAIL));
+    }
+    #[test]
     fn test_match_topic_some_name() {
         let defender =
             defender::match_topic("$aws/things/chloe/defender/metrics/json/accepted").unwrap();
@@ -145,4 +1370,403 @@ mod tests {
         assert_eq!(defender.thing_name, "chloe");
         assert_eq!(defender.api, defender::Topic::JsonReportAccepted);
     }
+    #[test]
+    fn compare_matches() {
+        let defender = defender::match_topic("$aws/things/chloe/defender/metrics/json");
+        assert_eq!(
+            defender,
+            Ok(defender::ThingDefender {
+                thing_name: "chloe",
+                api: defender::Topic::JsonReportPublish,
+            })
+        );
+    }
+    #[test]
+    fn report_id_increases() {
+        let mut ids = defender::ReportId::new(41);
+        assert_eq!(ids.next_id(), 41);
+        assert_eq!(ids.next_id(), 42);
+        let mut last = 0;
+        for timestamp in [100, 100, 99, 250, 0, 251] {
+            let id = ids.next_at(timestamp);
+            assert!(id > last, "{id} after {last}");
+            last = id;
+        }
+        assert_eq!(last, 252);
+
+        let mut ids = defender::ReportId::new(u64::MAX);
+        assert_eq!(ids.next_id(), u64::MAX);
+        assert_eq!(ids.next_id(), u64::MAX);
+    }
+    #[test]
+    fn all_topics() {
+        use defender::ALL_TOPICS;
+        // The number of variants, to bump along with the enum.
+        assert_eq!(ALL_TOPICS.len(), 6);
+        for (i, api) in ALL_TOPICS.iter().enumerate() {
+            assert_eq!(*api as usize, i);
+        }
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn string_topics() {
+        let config = TopicConfig::new("bridge/things/").unwrap();
+        for &api in defender::ALL_TOPICS {
+            let topic = defender::assemble_topic("chloe", api).unwrap();
+            assert_eq!(
+                defender::assemble_topic_string("chloe", api).unwrap(),
+                &topic[..]
+            );
+            let topic = defender::assemble_topic_with(&config, "chloe", api).unwrap();
+            let string = defender::assemble_topic_string_with(&config, "chloe", api);
+            assert_eq!(string.unwrap(), &topic[..]);
+        }
+        assert_eq!(
+            defender::assemble_topic_string("ch/loe", defender::Topic::JsonReportPublish),
+            Err(Error::ThingnameParseFailed)
+        );
+        let prefix = format!("{}/", "b".repeat(MQTT_TOPIC_LENGTH_MAX - 8));
+        let config = TopicConfig::new(&prefix).unwrap();
+        assert_eq!(
+            defender::assemble_topic_string_with(
+                &config,
+                "chloe",
+                defender::Topic::JsonReportPublish
+            ),
+            Err(Error::MqttTopicTooLong)
+        );
+    }
+    #[test]
+    fn topic_len_matches_assemble_topic() {
+        for &api in defender::ALL_TOPICS {
+            let topic = defender::assemble_topic("chloe", api).unwrap();
+            assert_eq!(defender::topic_len("chloe", api), topic.len());
+        }
+    }
+    #[test]
+    fn thing_defender_display() {
+        let defender =
+            defender::match_topic("$aws/things/chloe/defender/metrics/json/accepted").unwrap();
+        assert_eq!(
+            defender.to_string(),
+            "defender(thing=chloe, op=JsonReportAccepted)"
+        );
+    }
+    #[test]
+    fn publish_topic() {
+        for (format, publish) in [
+            (
+                defender::ReportFormat::Json,
+                defender::Topic::JsonReportPublish,
+            ),
+            (
+                defender::ReportFormat::Cbor,
+                defender::Topic::CborReportPublish,
+            ),
+        ] {
+            assert_eq!(
+                defender::publish_topic("chloe", format),
+                defender::assemble_topic("chloe", publish)
+            );
+        }
+        let topic = defender::publish_topic("chloe", defender::ReportFormat::Json).unwrap();
+        assert_eq!(&topic[..], "$aws/things/chloe/defender/metrics/json");
+        assert_eq!(
+            defender::publish_topic("", defender::ReportFormat::Json),
+            Err(Error::ThingnameParseFailed)
+        );
+    }
+    #[test]
+    fn response_topics() {
+        for (format, accepted, rejected) in [
+            (
+                defender::ReportFormat::Json,
+                defender::Topic::JsonReportAccepted,
+                defender::Topic::JsonReportRejected,
+            ),
+            (
+                defender::ReportFormat::Cbor,
+                defender::Topic::CborReportAccepted,
+                defender::Topic::CborReportRejected,
+            ),
+        ] {
+            let topics = defender::response_topics("chloe", format).unwrap();
+            assert_eq!(
+                topics.0,
+                defender::assemble_topic("chloe", accepted).unwrap()
+            );
+            assert_eq!(
+                topics.1,
+                defender::assemble_topic("chloe", rejected).unwrap()
+            );
+        }
+        assert_eq!(
+            defender::response_topics("chl/oe", defender::ReportFormat::Cbor),
+            Err(Error::ThingnameParseFailed)
+        );
+    }
+    #[test]
+    fn thing_defender_subsystem() {
+        use crate::TopicType;
+        let defender = defender::match_topic("$aws/things/chloe/defender/metrics/json").unwrap();
+        assert_eq!(defender.subsystem(), TopicType::Defender);
+        assert_eq!(TopicType::from(&defender), TopicType::Defender);
+    }
+    #[test]
+    fn report_validate() {
+        use defender::{Connection, NetworkStats, Port, Report};
+        let mut report = Report::new(1530304554);
+        report
+            .add_listening_udp_port(Port {
+                interface: Some("eth0"),
+                port: 5353,
+            })
+            .unwrap();
+        report
+            .add_tcp_connection(Connection {
+                local_interface: None,
+                local_port: Some(8883),
+                remote_addr: "192.168.0.1:8000",
+            })
+            .unwrap();
+        report.add_interface_stats(NetworkStats {
+            bytes_in: 100,
+            ..Default::default()
+        });
+        assert_eq!(report.validate(), Ok(()));
+
+        let rejected = |reason| Err(Error::InvalidReport(reason));
+        assert_eq!(Report::new(1).validate(), rejected(ReportError::NoMetrics));
+
+        let mut report_without_version = report.clone();
+        report_without_version.header.version = "";
+        assert_eq!(
+            report_without_version.validate(),
+            rejected(ReportError::MissingVersion)
+        );
+
+        // Strings pushed into the public lists, around the checks of the
+        // `add_*` methods.
+        let mut injected = report.clone();
+        injected.tcp_connections.as_mut().unwrap()[0].remote_addr = "1\",\"x\":\"y";
+        assert_eq!(injected.validate(), rejected(ReportError::InvalidString));
+        let mut injected = report.clone();
+        injected.listening_udp_ports.as_mut().unwrap()[0].interface = Some("");
+        assert_eq!(injected.validate(), rejected(ReportError::InvalidString));
+        let mut injected = report.clone();
+        injected.tcp_connections.as_mut().unwrap()[0].local_interface = Some("eth\n0");
+        assert_eq!(injected.validate(), rejected(ReportError::InvalidString));
+
+        let mut invalid = report.clone();
+        invalid
+            .add_listening_tcp_port(Port {
+                interface: None,
+                port: 0,
+            })
+            .unwrap();
+        assert_eq!(invalid.validate(), rejected(ReportError::ZeroPort));
+
+        let mut invalid = report.clone();
+        invalid
+            .add_tcp_connection(Connection {
+                local_interface: None,
+                local_port: Some(0),
+                remote_addr: "192.168.0.1:8000",
+            })
+            .unwrap();
+        assert_eq!(invalid.validate(), rejected(ReportError::ZeroPort));
+
+        let mut invalid = report.clone();
+        invalid.add_interface_stats(NetworkStats {
+            packets_out: u64::MAX,
+            ..Default::default()
+        });
+        assert_eq!(invalid.validate(), rejected(ReportError::CounterOverflow));
+    }
+    #[test]
+    fn report_writer_matches_report() {
+        use defender::{Connection, Metric, NetworkStats, Port, Report, ReportWriter};
+        let tcp = [
+            Port {
+                interface: Some("eth0"),
+                port: 22,
+            },
+            Port {
+                interface: None,
+                port: 443,
+            },
+        ];
+        let udp = [Port {
+            interface: None,
+            port: 5353,
+        }];
+        let stats = NetworkStats {
+            bytes_in: 29358693495,
+            bytes_out: 26485035,
+            packets_in: 10013573555,
+            packets_out: 11382615,
+        };
+        let connections = [Connection {
+            local_interface: Some("eth0"),
+            local_port: Some(8883),
+            remote_addr: "192.168.0.1:8000",
+        }];
+
+        let mut report = Report::new(1530304554);
+        for port in tcp {
+            report.add_listening_tcp_port(port).unwrap();
+        }
+        for port in udp {
+            report.add_listening_udp_port(port).unwrap();
+        }
+        report.add_interface_stats(stats);
+        report.add_tcp_connection(connections[0]).unwrap();
+        let json = report.to_json::<1024>().unwrap();
+
+        let mut buf = [0; 1024];
+        let mut writer = ReportWriter::begin(&mut buf, 1530304554).unwrap();
+        writer.add_metric(Metric::ListeningTcpPorts(&tcp)).unwrap();
+        writer.add_metric(Metric::ListeningUdpPorts(&udp)).unwrap();
+        writer.add_metric(Metric::NetworkStats(stats)).unwrap();
+        writer
+            .add_metric(Metric::TcpConnections(&connections))
+            .unwrap();
+        let len = writer.finish().unwrap();
+        assert_eq!(&buf[..len], json.as_bytes());
+
+        // An empty report, and a buffer too small for the whole report.
+        let mut buf = [0; 1024];
+        let len = ReportWriter::begin(&mut buf, 7).unwrap().finish().unwrap();
+        assert_eq!(
+            &buf[..len],
+            Report::new(7).to_json::<128>().unwrap().as_bytes()
+        );
+        let mut buf = [0; 64];
+        let mut writer = ReportWriter::begin(&mut buf, 1530304554).unwrap();
+        assert_eq!(
+            writer.add_metric(Metric::NetworkStats(stats)),
+            Err(Error::FAIL)
+        );
+        assert_eq!(
+            ReportWriter::begin(&mut [0; 16], 7).err(),
+            Some(Error::FAIL)
+        );
+
+        let bad = [Port {
+            interface: Some("eth\"0"),
+            port: 22,
+        }];
+        let mut buf = [0; 1024];
+        let mut writer = ReportWriter::begin(&mut buf, 7).unwrap();
+        assert_eq!(
+            writer.add_metric(Metric::ListeningTcpPorts(&bad)),
+            Err(Error::InvalidReport(ReportError::InvalidString))
+        );
+    }
+
+    #[test]
+    fn report_to_cbor() {
+        use defender::{
+            Connection, Metric, NetworkStats, Port, Report, ReportFormat, ReportWriter,
+        };
+
+        // Encoded by hand from RFC 8949, with the deterministic lengths.
+        let mut report = Report::new(1530304554);
+        report
+            .add_listening_udp_port(Port {
+                interface: None,
+                port: 5353,
+            })
+            .unwrap();
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"\xa2\x66header\xa2");
+        expected.extend_from_slice(b"\x69report_id\x1a\x5b\x36\x98\x2a");
+        expected.extend_from_slice(b"\x67version\x631.0");
+        expected.extend_from_slice(b"\x67metrics\xa1\x73listening_udp_ports\xa2");
+        expected.extend_from_slice(b"\x65ports\x81\xa1\x64port\x19\x14\xe9");
+        expected.extend_from_slice(b"\x65total\x01");
+        assert_eq!(&report.to_cbor::<256>().unwrap()[..], &expected[..]);
+
+        // The arguments of every length, and the optional fields.
+        let stats = NetworkStats {
+            bytes_in: 29358693495,
+            bytes_out: 26485035,
+            packets_in: 200,
+            packets_out: 7,
+        };
+        let mut report = Report::new(0);
+        report.add_interface_stats(stats);
+        let cbor = report.to_cbor::<256>().unwrap();
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"\xa2\x66header\xa2\x69report_id\x00\x67version\x631.0");
+        expected.extend_from_slice(b"\x67metrics\xa1\x6dnetwork_stats\xa4");
+        expected.extend_from_slice(b"\x68bytes_in\x1b\x00\x00\x00\x06\xd5\xea\x1c\x77");
+        expected.extend_from_slice(b"\x69bytes_out\x1a\x01\x94\x21\x2b");
+        expected.extend_from_slice(b"\x6apackets_in\x18\xc8");
+        expected.extend_from_slice(b"\x6bpackets_out\x07");
+        assert_eq!(&cbor[..], &expected[..]);
+
+        // The streaming writer agrees with the report, section by section.
+        let tcp = [Port {
+            interface: Some("eth0"),
+            port: 22,
+        }];
+        let connections = [Connection {
+            local_interface: Some("eth0"),
+            local_port: None,
+            remote_addr: "192.168.0.1:8000",
+        }];
+        let mut report = Report::new(1530304554);
+        report.add_listening_tcp_port(tcp[0]).unwrap();
+        report.add_interface_stats(stats);
+        report.add_tcp_connection(connections[0]).unwrap();
+        let cbor = report.to_cbor::<512>().unwrap();
+
+        let mut buf = [0; 512];
+        let mut writer =
+            ReportWriter::begin_with(&mut buf, 1530304554, ReportFormat::Cbor).unwrap();
+        writer.add_metric(Metric::ListeningTcpPorts(&tcp)).unwrap();
+        writer.add_metric(Metric::NetworkStats(stats)).unwrap();
+        writer
+            .add_metric(Metric::TcpConnections(&connections))
+            .unwrap();
+        let len = writer.finish().unwrap();
+        assert_eq!(&buf[..len], &cbor[..]);
+
+        let mut buf = [0; 64];
+        let len = ReportWriter::begin_with(&mut buf, 7, ReportFormat::Cbor)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(&buf[..len], &Report::new(7).to_cbor::<64>().unwrap()[..]);
+        assert_eq!(Report::new(7).to_cbor::<16>().err(), Some(Error::FAIL));
+    }
+    #[test]
+    fn validate_topic() {
+        for &api in defender::ALL_TOPICS {
+            let topic = defender::assemble_topic("chloe", api).unwrap();
+            assert_eq!(defender::validate_topic(&topic), Ok(()), "{topic}");
+        }
+        for (topic, err) in [
+            // The subsystem is right, the structure isn't.
+            ("$aws/things/chloe/defender/metrics/xml", Error::NoMatch),
+            (
+                "$aws/things/chloe/defender/metrics/json/pending",
+                Error::NoMatch,
+            ),
+            (
+                "$aws/things/chloe/defender/metrics/json/accepted/more",
+                Error::NoMatch,
+            ),
+            ("$aws/things/chloe/defender/metrics", Error::RootParseFailed),
+            (
+                "$aws/things/ch+loe/defender/metrics/json",
+                Error::ThingnameParseFailed,
+            ),
+            ("$aws/things/chloe/jobs/notify", Error::RootParseFailed),
+            ("chloe/defender/metrics/json", Error::NoMatch),
+        ] {
+            assert_eq!(defender::validate_topic(topic), Err(err), "{topic}");
+        }
+    }
 }
@@ -1,5 +1,6 @@
 use crate::common::*;
 use arrayvec::{ArrayString, ArrayVec};
+use core::fmt::Write;
 
 use self::Topic::*;
 
@@ -9,36 +10,178 @@ const API_GETPENDING: &str = "get";
 const API_STARTNEXT: &str = "start-next";
 const API_DESCRIBE: &str = "get";
 const API_UPDATE: &str = "update";
-const API_JOBID_NEXT: &str = "$next";
+const STATUS_DETAILS_PROGRESS: &str = "progress";
+const EXECUTION: &str = "execution";
+const JOB_DOCUMENT: &str = "jobDocument";
+
+/// The maximum number of entries in [`StatusDetails`].
+pub const STATUS_DETAILS_MAX: usize = 8;
+/// The maximum length of a [`StatusDetails`] key.
+pub const STATUS_DETAILS_KEY_MAX_LENGTH: usize = 32;
+/// The maximum length of a [`StatusDetails`] value.
+pub const STATUS_DETAILS_VALUE_MAX_LENGTH: usize = 64;
 
 /// The struct outputs which API the topic is for. It also outputs
 /// the thing name in the given topic.
+#[derive(Debug, PartialEq, Eq)]
 pub struct ThingJobs<'a> {
     pub thing_name: &'a str,
     pub api: Topic,
     pub id: Option<ArrayString<JOBID_MAX_LENGTH>>,
 }
 
+/// Same as [`ThingJobs`], with the job id borrowed from the topic, as
+/// returned by [`match_topic_ref`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThingJobsRef<'a> {
+    pub thing_name: &'a str,
+    pub api: Topic,
+    pub id: Option<&'a str>,
+}
+
 ///
-/// Topic values for subscription requests.
+/// Topic values for subscription requests, and for the requests a device
+/// publishes itself.
 ///
-#[derive(Debug, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
 pub enum Topic {
     JobsChanged,
     NextJobChanged,
+    GetPending,
     GetPendingSuccess,
     GetPendingFailed,
+    StartNext,
     StartNextSuccess,
     StartNextFailed,
     /* Topics below use a job ID. */
+    Describe,
+    DescribeSuccess,
+    DescribeFailed,
+    Update,
+    UpdateSuccess,
+    UpdateFailed,
+}
+
+/// Every jobs topic, in declaration order.
+pub const ALL_TOPICS: &[Topic] = &[
+    JobsChanged,
+    NextJobChanged,
+    GetPending,
+    GetPendingSuccess,
+    GetPendingFailed,
+    StartNext,
+    StartNextSuccess,
+    StartNextFailed,
+    Describe,
     DescribeSuccess,
     DescribeFailed,
+    Update,
     UpdateSuccess,
     UpdateFailed,
+];
+
+impl Topic {
+    /// Whether the device publishes the topic, receives it as a response to
+    /// its request, or receives it unprompted.
+    pub fn direction(&self) -> TopicDirection {
+        match self {
+            JobsChanged | NextJobChanged => TopicDirection::Notification,
+            GetPending | StartNext | Describe | Update => TopicDirection::Request,
+            _ => TopicDirection::Response,
+        }
+    }
+    /// The canonical name of the topic, i.e. the levels after `jobs/`,
+    /// with a `+` in place of the job id, like `"+/update/accepted"`.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::jobs;
+    ///
+    /// assert_eq!(jobs::Topic::NextJobChanged.as_str(), "notify-next");
+    /// assert_eq!(jobs::Topic::UpdateSuccess.as_str(), "+/update/accepted");
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobsChanged => "notify",
+            NextJobChanged => "notify-next",
+            GetPending => "get",
+            GetPendingSuccess => "get/accepted",
+            GetPendingFailed => "get/rejected",
+            StartNext => "start-next",
+            StartNextSuccess => "start-next/accepted",
+            StartNextFailed => "start-next/rejected",
+            Describe => "+/get",
+            DescribeSuccess => "+/get/accepted",
+            DescribeFailed => "+/get/rejected",
+            Update => "+/update",
+            UpdateSuccess => "+/update/accepted",
+            UpdateFailed => "+/update/rejected",
+        }
+    }
+}
+
+fn from_name(name: &str) -> Result<Topic, Error> {
+    match name {
+        "notify" => Ok(JobsChanged),
+        "notify-next" => Ok(NextJobChanged),
+        "get" => Ok(GetPending),
+        "get/accepted" => Ok(GetPendingSuccess),
+        "get/rejected" => Ok(GetPendingFailed),
+        "start-next" => Ok(StartNext),
+        "start-next/accepted" => Ok(StartNextSuccess),
+        "start-next/rejected" => Ok(StartNextFailed),
+        "+/get" => Ok(Describe),
+        "+/get/accepted" => Ok(DescribeSuccess),
+        "+/get/rejected" => Ok(DescribeFailed),
+        "+/update" => Ok(Update),
+        "+/update/accepted" => Ok(UpdateSuccess),
+        "+/update/rejected" => Ok(UpdateFailed),
+        _ => Err(Error::MessageTypeParseFailed),
+    }
+}
+
+/// The inverse of [`Topic::as_str`].
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let topic = jobs::Topic::DescribeFailed;
+/// assert_eq!(jobs::Topic::try_from(topic.as_str()), Ok(topic));
+/// ```
+impl TryFrom<&str> for Topic {
+    type Error = Error;
+
+    fn try_from(name: &str) -> Result<Self, Error> {
+        from_name(name)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Topic {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Topic {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_name(deserializer, from_name)
+    }
 }
 
 /// Populate a topic string for a subscription request.
 ///
+/// Jobs that target a thing group are delivered on the topics of every
+/// thing in the group; AWS IoT has no per-group jobs topics. Build these
+/// topics with the name of the thing the job agent runs on.
+///
+/// The topics of a given job, from `Describe` to `UpdateFailed`, get a `+`
+/// wildcard in place of the job id, which suits a subscription covering
+/// every job. To publish a request about one job, or subscribe to its
+/// responses only, use [`assemble_job_topic`] with the job id instead.
+///
 /// # Example
 /// ```
 /// use aws_iot_device_sdk::jobs::Topic::*;
@@ -48,25 +191,228 @@ pub enum Topic {
 /// assert_eq!(jobs.api, jobs::Topic::NextJobChanged);
 /// assert_eq!(jobs.id, None);
 /// ```
+#[must_use = "this returns the topic without publishing or subscribing to it"]
 pub fn assemble_topic(
     thing_name: &str,
     api: Topic,
 ) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+    assemble_topic_with(&TopicConfig::default(), thing_name, api)
+}
+
+/// Same as [`assemble_topic`], with the thing prefix of `config`.
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_topic_with(
+    config: &TopicConfig,
+    thing_name: &str,
+    api: Topic,
+) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+    assemble_api(config, thing_name, api)
+}
+
+/// Same as [`assemble_topic`], into a `String`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let topic = jobs::assemble_topic_string("chloe", jobs::Topic::NextJobChanged).unwrap();
+/// assert_eq!(topic, "$aws/things/chloe/jobs/notify-next");
+/// ```
+#[cfg(feature = "std")]
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_topic_string(thing_name: &str, api: Topic) -> Result<String, Error> {
+    assemble_api(&TopicConfig::default(), thing_name, api)
+}
+
+/// Same as [`assemble_topic_with`], into a `String`, so that the thing
+/// prefix of `config` may be longer than the capacity allows. The topic
+/// still fails with `Error::MqttTopicTooLong` past `MQTT_TOPIC_LENGTH_MAX`.
+#[cfg(feature = "std")]
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_topic_string_with(
+    config: &TopicConfig,
+    thing_name: &str,
+    api: Topic,
+) -> Result<String, Error> {
+    let topic: String = assemble_api(config, thing_name, api)?;
+    is_valid_mqtt_topic(&topic)?;
+    Ok(topic)
+}
+
+fn assemble_api<B: TopicBuf>(
+    config: &TopicConfig,
+    thing_name: &str,
+    api: Topic,
+) -> Result<B, Error> {
     is_valid_thing_name(thing_name)?;
-    let mut s = ArrayString::<JOBS_TOPIC_MAX_LENGTH>::new();
-    s.push_str(AWS_THINGS_PREFIX);
-    s.push_str(thing_name);
-    s.push_str(JOBS_API_BRIDGE);
-    s.push_str(id(&api));
-    s.push_str(op(&api));
-    s.push_str(suffix(&api));
+    let mut s = B::default();
+    push_str(&mut s, config.things_prefix)?;
+    push_str(&mut s, thing_name)?;
+    push_str(&mut s, JOBS_API_BRIDGE)?;
+    push_str(&mut s, id(&api))?;
+    push_str(&mut s, op(&api))?;
+    push_str(&mut s, suffix(&api))?;
 
     Ok(s)
 }
 
+/// Builds the topics with a `+` wildcard as the job id, like
+/// [`assemble_topic`].
+impl TopicBuilder for Topic {
+    type Output = ArrayString<JOBS_TOPIC_MAX_LENGTH>;
+
+    fn topic(&self, thing_name: &str) -> Result<Self::Output, Error> {
+        assemble_topic_with(&TopicConfig::default(), thing_name, *self)
+    }
+}
+
+impl<'a> TopicMatcher<'a> for ThingJobs<'a> {
+    fn match_topic(topic: &'a str) -> Result<Self, Error> {
+        match_topic(topic)
+    }
+}
+
+/// Copy the job id, failing with `Error::JobsIdParseFailed` when it's longer
+/// than `JOBID_MAX_LENGTH`.
+impl<'a> TryFrom<ThingJobsRef<'a>> for ThingJobs<'a> {
+    type Error = Error;
+
+    fn try_from(jobs: ThingJobsRef<'a>) -> Result<Self, Error> {
+        let id = jobs.id.map(ArrayString::from).transpose();
+        Ok(ThingJobs {
+            thing_name: jobs.thing_name,
+            api: jobs.api,
+            id: id.map_err(|_| Error::JobsIdParseFailed)?,
+        })
+    }
+}
+
+/// A summary for logs, like `jobs(thing=chloe, id=ota-1, op=UpdateSuccess)`.
+impl core::fmt::Display for ThingJobs<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        ThingJobsRef {
+            thing_name: self.thing_name,
+            api: self.api,
+            id: self.id.as_deref(),
+        }
+        .fmt(f)
+    }
+}
+
+/// Same as for [`ThingJobs`].
+impl core::fmt::Display for ThingJobsRef<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "jobs(thing={}", self.thing_name)?;
+        if let Some(id) = self.id {
+            write!(f, ", id={id}")?;
+        }
+        write!(f, ", op={:?})", self.api)
+    }
+}
+
+impl ThingJobs<'_> {
+    /// Always [`TopicType::Jobs`](crate::TopicType::Jobs).
+    pub fn subsystem(&self) -> crate::TopicType {
+        crate::TopicType::Jobs
+    }
+}
+
+impl ThingJobsRef<'_> {
+    /// Always [`TopicType::Jobs`](crate::TopicType::Jobs).
+    pub fn subsystem(&self) -> crate::TopicType {
+        crate::TopicType::Jobs
+    }
+}
+
+impl From<&ThingJobs<'_>> for crate::TopicType {
+    fn from(jobs: &ThingJobs<'_>) -> Self {
+        jobs.subsystem()
+    }
+}
+
+impl From<&ThingJobsRef<'_>> for crate::TopicType {
+    fn from(jobs: &ThingJobsRef<'_>) -> Self {
+        jobs.subsystem()
+    }
+}
+
+impl ThingJobs<'_> {
+    /// Rebuild the subscription filter the matched topic belongs to.
+    /// The job id level becomes a `+` wildcard, and so does the
+    /// accepted/rejected suffix.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::{jobs};
+    ///
+    /// let jobs = jobs::match_topic("$aws/things/chloe/jobs/example-job-01/update/accepted").unwrap();
+    /// let filter = jobs.to_filter().unwrap();
+    /// assert_eq!(&filter[..], "$aws/things/chloe/jobs/+/update/+");
+    /// ```
+    #[must_use = "this returns the topic without publishing or subscribing to it"]
+    pub fn to_filter(&self) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+        self.to_filter_with(&TopicConfig::default())
+    }
+    /// Same as [`ThingJobs::to_filter`], with the thing prefix of `config`.
+    #[must_use = "this returns the topic without publishing or subscribing to it"]
+    pub fn to_filter_with(
+        &self,
+        config: &TopicConfig,
+    ) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+        is_valid_thing_name(self.thing_name)?;
+        let mut s = ArrayString::<JOBS_TOPIC_MAX_LENGTH>::new();
+        push_str(&mut s, config.things_prefix)?;
+        push_str(&mut s, self.thing_name)?;
+        push_str(&mut s, JOBS_API_BRIDGE)?;
+        push_str(&mut s, id(&self.api))?;
+        push_str(&mut s, op(&self.api))?;
+        if !suffix(&self.api).is_empty() {
+            push_str(&mut s, SUFFIX_WILDCARD)?;
+        }
+
+        Ok(s)
+    }
+}
+
+/// Populate the filters for every message the jobs service sends a thing:
+/// `notify`, `notify-next`, then the responses of `get`, `start-next`, and
+/// the `get` and `update` of any job id.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let filters = jobs::response_filters("chloe").unwrap();
+/// assert_eq!(&filters[1][..], "$aws/things/chloe/jobs/notify-next");
+/// assert_eq!(&filters[5][..], "$aws/things/chloe/jobs/+/update/+");
+/// ```
+#[must_use = "this returns the topics without subscribing to them"]
+pub fn response_filters(
+    thing_name: &str,
+) -> Result<[ArrayString<JOBS_TOPIC_MAX_LENGTH>; 6], Error> {
+    let mut filters = [ArrayString::new(); 6];
+    let apis = [
+        JobsChanged,
+        NextJobChanged,
+        GetPendingSuccess,
+        StartNextSuccess,
+        DescribeSuccess,
+        UpdateSuccess,
+    ];
+    for (filter, api) in filters.iter_mut().zip(apis) {
+        let jobs = ThingJobs {
+            thing_name,
+            api,
+            id: None,
+        };
+        *filter = jobs.to_filter()?;
+    }
+    Ok(filters)
+}
+
 fn id(api: &Topic) -> &str {
     match api {
-        DescribeSuccess | DescribeFailed | UpdateSuccess | UpdateFailed => "+/",
+        Describe | DescribeSuccess | DescribeFailed | Update | UpdateSuccess | UpdateFailed => "+/",
         _ => "",
     }
 }
@@ -75,13 +421,17 @@ fn op(api: &Topic) -> &str {
     match api {
         JobsChanged => API_JOBSCHANGED,
         NextJobChanged => API_NEXTJOBCHANGED,
+        GetPending => API_GETPENDING,
         GetPendingSuccess => API_GETPENDING,
         GetPendingFailed => API_GETPENDING,
+        StartNext => API_STARTNEXT,
         StartNextSuccess => API_STARTNEXT,
         StartNextFailed => API_STARTNEXT,
         /* Topics below use a => job ID. */
+        Describe => API_DESCRIBE,
         DescribeSuccess => API_DESCRIBE,
         DescribeFailed => API_DESCRIBE,
+        Update => API_UPDATE,
         UpdateSuccess => API_UPDATE,
         UpdateFailed => API_UPDATE,
     }
@@ -108,30 +458,72 @@ fn suffix(topic_type: &Topic) -> &str {
 /// assert_eq!(&id[..], "$next")
 ///
 /// ```
-pub fn match_topic(topic: &str) -> Result<ThingJobs, Error> {
-    is_valid_mqtt_topic(topic)?;
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic(topic: &str) -> Result<ThingJobs<'_>, Error> {
+    match_topic_with(&TopicConfig::default(), topic)
+}
 
-    let s = is_valid_prefix(topic, AWS_THINGS_PREFIX)?;
+/// Same as [`match_topic`], with the thing prefix of `config`.
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_with<'a>(config: &TopicConfig, topic: &'a str) -> Result<ThingJobs<'a>, Error> {
+    let result = match_ref(config, topic).and_then(TryInto::try_into);
+    log_match("jobs", topic, result)
+}
 
-    let mid = s.find('/').ok_or(Error::FAIL);
-    let (thing_name, mut s) = s.split_at(mid?);
-    is_valid_thing_name(thing_name)?;
+/// Same as [`match_topic`], borrowing the job id from `topic` rather than
+/// copying it.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let jobs = jobs::match_topic_ref("$aws/things/chloe/jobs/example-job-01/update/accepted").unwrap();
+/// assert_eq!(jobs.api, jobs::Topic::UpdateSuccess);
+/// assert_eq!(jobs.id, Some("example-job-01"));
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_ref(topic: &str) -> Result<ThingJobsRef<'_>, Error> {
+    match_topic_ref_with(&TopicConfig::default(), topic)
+}
+
+/// Same as [`match_topic_ref`], with the thing prefix of `config`.
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_ref_with<'a>(
+    config: &TopicConfig,
+    topic: &'a str,
+) -> Result<ThingJobsRef<'a>, Error> {
+    log_match("jobs", topic, match_ref(config, topic))
+}
+
+fn match_ref<'a>(config: &TopicConfig, topic: &'a str) -> Result<ThingJobsRef<'a>, Error> {
+    let (thing_name, s) = parse_thing_prefix_with(config, topic)?;
+    match_rest(thing_name, s)
+}
+
+/// Match the rest of a topic, following the thing name, see [`ThingTopic`].
+pub(crate) fn match_rest<'a>(
+    thing_name: &'a str,
+    mut s: &'a str,
+) -> Result<ThingJobsRef<'a>, Error> {
+    is_valid_levels(s)?;
 
     s = is_valid_bridge(s, JOBS_API_BRIDGE)?;
 
-    let v: ArrayVec<&str, 16> = s.split('/').collect();
+    let v = collect_levels::<16>(s)?;
     let api: Topic;
-    let jobs_id;
     match v[..] {
         // ~$aws/things/MyThing/jobs/~<operation>
         // $aws/things/MyThing/jobs/notify (or $aws/things/MyThing/jobs/notify-next)
+        // $aws/things/MyThing/jobs/get (or $aws/things/MyThing/jobs/start-next)
         [op] => {
-            if op == API_JOBSCHANGED {
-                api = JobsChanged;
-            } else {
-                api = NextJobChanged;
+            match op {
+                API_JOBSCHANGED => api = JobsChanged,
+                API_NEXTJOBCHANGED => api = NextJobChanged,
+                API_GETPENDING => api = GetPending,
+                API_STARTNEXT => api = StartNext,
+                _ => return Err(Error::NoMatch),
             }
-            Ok(ThingJobs {
+            Ok(ThingJobsRef {
                 thing_name,
                 api,
                 id: None,
@@ -144,9 +536,12 @@ pub fn match_topic(topic: &str) -> Result<ThingJobs, Error> {
                 (API_GETPENDING, REJECTED) => api = GetPendingFailed,
                 (API_STARTNEXT, ACCEPTED) => api = StartNextSuccess,
                 (API_STARTNEXT, REJECTED) => api = StartNextFailed,
+                // $aws/things/MyThing/jobs/<jobs-id>/<operation>
+                (id, API_DESCRIBE) => return describe_or_update(thing_name, Describe, id),
+                (id, API_UPDATE) => return describe_or_update(thing_name, Update, id),
                 _ => return Err(Error::NoMatch),
             }
-            Ok(ThingJobs {
+            Ok(ThingJobsRef {
                 thing_name,
                 api,
                 id: None,
@@ -161,41 +556,285 @@ pub fn match_topic(topic: &str) -> Result<ThingJobs, Error> {
                 (API_UPDATE, REJECTED) => api = UpdateFailed,
                 _ => return Err(Error::NoMatch),
             }
-            jobs_id = Some(ArrayString::<JOBID_MAX_LENGTH>::from(id).unwrap());
-            Ok(ThingJobs {
-                thing_name,
-                api,
-                id: jobs_id,
-            })
+            describe_or_update(thing_name, api, id)
         }
         // Not jobs topic
         _ => Err(Error::NoMatch),
     }
 }
+
+/// Same as [`match_topic`], for a filter with `+` as the thing name, see
+/// [`shadow::match_filter`](crate::shadow::match_filter).
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let filter = jobs::match_filter("$aws/things/+/jobs/notify-next").unwrap();
+/// assert_eq!(filter.thing_name, "+");
+/// assert_eq!(filter.api, jobs::Topic::NextJobChanged);
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_filter(filter: &str) -> Result<ThingJobs<'_>, Error> {
+    let result = parse_thing_filter(filter)
+        .and_then(|(thing_name, rest)| match_rest(thing_name, rest))
+        .and_then(TryInto::try_into);
+    log_match("jobs", filter, result)
+}
+
+/// Same as [`match_topic`], for topics handed over as raw bytes by the MQTT
+/// client.
+///
+/// A topic which is not valid UTF-8 is rejected with `Error::MqttTopicFailed`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let jobs = jobs::match_topic_bytes(b"$aws/things/chloe/jobs/notify").unwrap();
+/// assert_eq!(jobs.api, jobs::Topic::JobsChanged);
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_bytes(topic: &[u8]) -> Result<ThingJobs<'_>, Error> {
+    match topic_from_bytes(topic) {
+        Ok(topic) => match_topic(topic),
+        Err(e) => log_match("jobs", topic, Err(e)),
+    }
+}
+
+/// Check that `topic` is a valid jobs topic, matching it fully as
+/// [`match_topic`] does. See [`shadow::validate_topic`](crate::shadow::validate_topic).
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{jobs, Error};
+///
+/// assert_eq!(jobs::validate_topic("$aws/things/chloe/jobs/ota-1/update/accepted"), Ok(()));
+/// assert_eq!(jobs::validate_topic("$aws/things/chloe/jobs/notify/accepted"), Err(Error::NoMatch));
+/// ```
+pub fn validate_topic(topic: &str) -> Result<(), Error> {
+    match_topic(topic).map(|_| ())
+}
+
+fn describe_or_update<'a>(
+    thing_name: &'a str,
+    api: Topic,
+    id: &'a str,
+) -> Result<ThingJobsRef<'a>, Error> {
+    // Same rules as the builders, which also keeps the id within ThingJobs.
+    is_valid_id_for(&api, id).map_err(|_| Error::JobsIdParseFailed)?;
+    Ok(ThingJobsRef {
+        thing_name,
+        api,
+        id: Some(id),
+    })
+}
 /// Populate a topic string for a GetPendingJobExecutions request.
 ///
-pub fn get_pending(thing_name: &str) -> Result<ArrayString<THINGNAME_MAX_LENGTH>, Error> {
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn get_pending(thing_name: &str) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+    get_pending_with(&TopicConfig::default(), thing_name)
+}
+/// Same as [`get_pending`], with the thing prefix of `config`.
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn get_pending_with(
+    config: &TopicConfig,
+    thing_name: &str,
+) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
     is_valid_thing_name(thing_name)?;
-    let mut s = ArrayString::<THINGNAME_MAX_LENGTH>::new();
-    s.push_str(AWS_THINGS_PREFIX);
-    s.push_str(thing_name);
-    s.push_str(JOBS_API_BRIDGE);
-    s.push_str(API_GETPENDING);
+    let mut s = ArrayString::<JOBS_TOPIC_MAX_LENGTH>::new();
+    push_str(&mut s, config.things_prefix)?;
+    push_str(&mut s, thing_name)?;
+    push_str(&mut s, JOBS_API_BRIDGE)?;
+    push_str(&mut s, API_GETPENDING)?;
 
     Ok(s)
 }
 /// Populate a topic string for a StartNextPendingJobExecution request.
 ///
-pub fn start_next(thing_name: &str) -> Result<ArrayString<THINGNAME_MAX_LENGTH>, Error> {
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn start_next(thing_name: &str) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+    start_next_with(&TopicConfig::default(), thing_name)
+}
+/// Same as [`start_next`], with the thing prefix of `config`.
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn start_next_with(
+    config: &TopicConfig,
+    thing_name: &str,
+) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
     is_valid_thing_name(thing_name)?;
-    let mut s = ArrayString::<THINGNAME_MAX_LENGTH>::new();
-    s.push_str(AWS_THINGS_PREFIX);
-    s.push_str(thing_name);
-    s.push_str(JOBS_API_BRIDGE);
-    s.push_str(API_STARTNEXT);
+    let mut s = ArrayString::<JOBS_TOPIC_MAX_LENGTH>::new();
+    push_str(&mut s, config.things_prefix)?;
+    push_str(&mut s, thing_name)?;
+    push_str(&mut s, JOBS_API_BRIDGE)?;
+    push_str(&mut s, API_STARTNEXT)?;
 
     Ok(s)
 }
+/// The length [`assemble_topic`] gives the topic, without building it.
+///
+/// The thing name is not validated.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let len = jobs::topic_len("chloe", jobs::Topic::UpdateSuccess);
+/// assert_eq!(len, "$aws/things/chloe/jobs/+/update/accepted".len());
+/// ```
+pub fn topic_len(thing_name: &str, api: Topic) -> usize {
+    AWS_THINGS_PREFIX.len()
+        + thing_name.len()
+        + JOBS_API_BRIDGE.len()
+        + id(&api).len()
+        + op(&api).len()
+        + suffix(&api).len()
+}
+
+/// The length [`assemble_job_topic`] gives the topic, without building it.
+///
+/// Neither the thing name nor the job id are validated, and `api` is taken
+/// as a job id topic.
+pub fn job_topic_len(thing_name: &str, id: &str, api: Topic) -> usize {
+    AWS_THINGS_PREFIX.len()
+        + thing_name.len()
+        + JOBS_API_BRIDGE.len()
+        + id.len()
+        + 1
+        + op(&api).len()
+        + suffix(&api).len()
+}
+
+/// Populate the four response topics an agent subscribes to once it
+/// works on a job: `update/accepted`, `update/rejected`, `get/accepted` and
+/// `get/rejected` of that job id.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let topics = jobs::job_scoped_topics("chloe", "example-job-01").unwrap();
+/// assert_eq!(&topics[0][..], "$aws/things/chloe/jobs/example-job-01/update/accepted");
+/// assert_eq!(&topics[3][..], "$aws/things/chloe/jobs/example-job-01/get/rejected");
+/// ```
+#[must_use = "this returns the topics without subscribing to them"]
+pub fn job_scoped_topics(
+    thing_name: &str,
+    id: &str,
+) -> Result<ArrayVec<ArrayString<JOBS_TOPIC_MAX_LENGTH>, 4>, Error> {
+    job_scoped_topics_with(&TopicConfig::default(), thing_name, id)
+}
+
+/// Same as [`job_scoped_topics`], with the thing prefix of `config`.
+#[must_use = "this returns the topics without subscribing to them"]
+pub fn job_scoped_topics_with(
+    config: &TopicConfig,
+    thing_name: &str,
+    id: &str,
+) -> Result<ArrayVec<ArrayString<JOBS_TOPIC_MAX_LENGTH>, 4>, Error> {
+    is_valid_job_id(id)?;
+    let mut topics = ArrayVec::new();
+    for api in [UpdateSuccess, UpdateFailed, DescribeSuccess, DescribeFailed] {
+        topics.push(assemble_id_topic(config, thing_name, id, &api)?);
+    }
+    Ok(topics)
+}
+
+/// Populate the topic for a given job id, such as
+/// `$aws/things/chloe/jobs/<id>/get/accepted`.
+///
+/// `api` must be one of the job id topics, `Describe` to `UpdateFailed`,
+/// otherwise this fails with `Error::FAIL`. The id is checked with
+/// [`is_valid_job_id`], except that the `Describe` topics also take `$next`
+/// for the next pending job.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let topic = jobs::assemble_job_topic("chloe", "example-job-01", jobs::Topic::Describe).unwrap();
+/// assert_eq!(&topic[..], "$aws/things/chloe/jobs/example-job-01/get");
+/// let topic = jobs::assemble_job_topic("chloe", "$next", jobs::Topic::DescribeSuccess).unwrap();
+/// assert_eq!(&topic[..], "$aws/things/chloe/jobs/$next/get/accepted");
+///
+/// assert!(jobs::assemble_job_topic("chloe", "example-job-01", jobs::Topic::GetPending).is_err());
+/// ```
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_job_topic(
+    thing_name: &str,
+    id: &str,
+    api: Topic,
+) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+    assemble_job_topic_with(&TopicConfig::default(), thing_name, id, api)
+}
+
+/// Same as [`assemble_job_topic`], with the thing prefix of `config`.
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_job_topic_with(
+    config: &TopicConfig,
+    thing_name: &str,
+    id: &str,
+    api: Topic,
+) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+    assemble_job_into(config, thing_name, id, api)
+}
+
+/// Same as [`assemble_job_topic`], into a `String`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let topic = jobs::assemble_job_topic_string("chloe", "ota-1", jobs::Topic::Update).unwrap();
+/// assert_eq!(topic, "$aws/things/chloe/jobs/ota-1/update");
+/// ```
+#[cfg(feature = "std")]
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_job_topic_string(thing_name: &str, id: &str, api: Topic) -> Result<String, Error> {
+    assemble_job_into(&TopicConfig::default(), thing_name, id, api)
+}
+
+fn assemble_job_into<B: TopicBuf>(
+    config: &TopicConfig,
+    thing_name: &str,
+    id: &str,
+    api: Topic,
+) -> Result<B, Error> {
+    if self::id(&api).is_empty() {
+        return Err(Error::FAIL);
+    }
+    is_valid_id_for(&api, id)?;
+    assemble_id_topic(config, thing_name, id, &api)
+}
+
+/// Check the id of a job id topic: any valid job id, and `$next` for the
+/// `Describe` topics only.
+fn is_valid_id_for(api: &Topic, id: &str) -> Result<(), Error> {
+    match api {
+        Describe | DescribeSuccess | DescribeFailed => is_valid_job_id_or_next(id),
+        _ => is_valid_job_id(id),
+    }
+}
+
+/// A topic of a given job, with an already validated id.
+fn assemble_id_topic<B: TopicBuf>(
+    config: &TopicConfig,
+    thing_name: &str,
+    id: &str,
+    api: &Topic,
+) -> Result<B, Error> {
+    is_valid_thing_name(thing_name)?;
+    let mut s = B::default();
+    push_str(&mut s, config.things_prefix)?;
+    push_str(&mut s, thing_name)?;
+    push_str(&mut s, JOBS_API_BRIDGE)?;
+    push_str(&mut s, id)?;
+    push_str(&mut s, "/")?;
+    push_str(&mut s, op(api))?;
+    push_str(&mut s, suffix(api))?;
+    Ok(s)
+}
+
 /// Populate a topic string for a DescribeJobExecution request.
 ///
 /// # Example
@@ -207,40 +846,583 @@ pub fn start_next(thing_name: &str) -> Result<ArrayString<THINGNAME_MAX_LENGTH>,
 /// assert_eq!(&topic[..], "$aws/things/chloe/jobs/$next/get")
 ///
 /// ```
-pub fn describe(thing_name: &str, id: &str) -> Result<ArrayString<THINGNAME_MAX_LENGTH>, Error> {
-    is_valid_thing_name(thing_name)?;
-    if id != API_JOBID_NEXT {
-        is_valid_job_id(id)?
-    };
-    let mut s = ArrayString::<THINGNAME_MAX_LENGTH>::new();
-    s.push_str(AWS_THINGS_PREFIX);
-    s.push_str(thing_name);
-    s.push_str(JOBS_API_BRIDGE);
-    s.push_str(id);
-    s.push_str("/");
-    s.push_str(API_DESCRIBE);
-
-    Ok(s)
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn describe(thing_name: &str, id: &str) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+    assemble_job_topic(thing_name, id, Describe)
+}
+/// Same as [`describe`], with the thing prefix of `config`.
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn describe_with(
+    config: &TopicConfig,
+    thing_name: &str,
+    id: &str,
+) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+    assemble_job_topic_with(config, thing_name, id, Describe)
 }
 /// Populate a topic string for an UpdateJobExecution request.
 ///
-pub fn update(thing_name: &str, id: &str) -> Result<ArrayString<THINGNAME_MAX_LENGTH>, Error> {
-    is_valid_thing_name(thing_name)?;
-    is_valid_job_id(id)?;
-    let mut s = ArrayString::<THINGNAME_MAX_LENGTH>::new();
-    s.push_str(AWS_THINGS_PREFIX);
-    s.push_str(thing_name);
-    s.push_str(JOBS_API_BRIDGE);
-    s.push_str(id);
-    s.push_str("/");
-    s.push_str(API_UPDATE);
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn update(thing_name: &str, id: &str) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+    assemble_job_topic(thing_name, id, Update)
+}
+/// Same as [`update`], with the thing prefix of `config`.
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn update_with(
+    config: &TopicConfig,
+    thing_name: &str,
+    id: &str,
+) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+    assemble_job_topic_with(config, thing_name, id, Update)
+}
 
-    Ok(s)
+/// The status of a job execution.
+///
+/// A device may only report `InProgress`, `Succeeded`, `Failed` and
+/// `Rejected` in an [`UpdateRequest`]. The other statuses are set by the
+/// cloud: `Queued` for a new execution, `Canceled` and `Removed` when the
+/// job is canceled or the execution deleted through the control plane,
+/// `TimedOut` when its timeout expires. The jobs MQTT API has no topic to
+/// delete an execution; a device which can't run the job reports `Rejected`
+/// or `Failed` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JobStatus {
+    InProgress,
+    Succeeded,
+    Failed,
+    Rejected,
+    Queued,
+    Canceled,
+    TimedOut,
+    Removed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::InProgress => "IN_PROGRESS",
+            JobStatus::Succeeded => "SUCCEEDED",
+            JobStatus::Failed => "FAILED",
+            JobStatus::Rejected => "REJECTED",
+            JobStatus::Queued => "QUEUED",
+            JobStatus::Canceled => "CANCELED",
+            JobStatus::TimedOut => "TIMED_OUT",
+            JobStatus::Removed => "REMOVED",
+        }
+    }
+    /// Whether the device can report the status in an [`UpdateRequest`];
+    /// the service rejects an update to any other status.
+    pub fn is_device_settable(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::InProgress | JobStatus::Succeeded | JobStatus::Failed | JobStatus::Rejected
+        )
+    }
+    /// Whether the execution is over, i.e. any status but `Queued` and
+    /// `InProgress`.
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::InProgress)
+    }
+}
+
+/// The `statusDetails` of a job execution, a bounded map of string pairs.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs::StatusDetails;
+///
+/// let mut details = StatusDetails::new();
+/// details.progress_percent(50).unwrap();
+/// details.insert("step", "download").unwrap();
+/// assert_eq!(details.get("progress"), Some("50"));
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StatusDetails {
+    entries: ArrayVec<
+        (
+            ArrayString<STATUS_DETAILS_KEY_MAX_LENGTH>,
+            ArrayString<STATUS_DETAILS_VALUE_MAX_LENGTH>,
+        ),
+        STATUS_DETAILS_MAX,
+    >,
+}
+
+impl StatusDetails {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Insert or replace an entry.
+    ///
+    /// Keys follow the AWS pattern `[a-zA-Z0-9:_-]+`, values must not need
+    /// JSON escaping. Fails when either is too long or the map is full.
+    pub fn insert(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        // The length is bounded by the key storage below.
+        is_valid_name(key, usize::MAX)?;
+        is_valid_json_str(value)?;
+        let value = ArrayString::from(value).map_err(|_| Error::FAIL)?;
+        if let Some(entry) = self.entries.iter_mut().find(|(k, _)| &k[..] == key) {
+            entry.1 = value;
+            return Ok(());
+        }
+        let key = ArrayString::from(key).map_err(|_| Error::FAIL)?;
+        self.entries.try_push((key, value)).map_err(|_| Error::FAIL)
+    }
+    /// Insert the conventional `progress` entry, as a percentage.
+    pub fn progress_percent(&mut self, percent: u8) -> Result<(), Error> {
+        if percent > 100 {
+            return Err(Error::FAIL);
+        }
+        self.insert(STATUS_DETAILS_PROGRESS, &u64_to_str(percent.into()))
+    }
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| &k[..] == key)
+            .map(|(_, v)| &v[..])
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn write_json(&self, w: &mut impl Write) -> core::fmt::Result {
+        w.write_char('{')?;
+        for (i, (key, value)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                w.write_char(',')?;
+            }
+            w.write_char('"')?;
+            w.write_str(key)?;
+            w.write_str(r#"":""#)?;
+            w.write_str(value)?;
+            w.write_char('"')?;
+        }
+        w.write_char('}')
+    }
+}
+
+/// The payload of an [`update`] request.
+///
+/// Any status serializes, but only the ones for which
+/// [`JobStatus::is_device_settable`] holds are accepted by the service.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs::{JobStatus, StatusDetails, UpdateRequest};
+///
+/// let mut request = UpdateRequest::new(JobStatus::InProgress);
+/// let mut details = StatusDetails::new();
+/// details.progress_percent(50).unwrap();
+/// request.status_details = Some(details);
+/// request.expected_version = Some(2);
+///
+/// let json = request.to_json::<128>().unwrap();
+/// assert_eq!(
+///     &json[..],
+///     r#"{"status":"IN_PROGRESS","statusDetails":{"progress":"50"},"expectedVersion":2}"#
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateRequest {
+    pub status: JobStatus,
+    pub status_details: Option<StatusDetails>,
+    /// The job execution version the device last saw.
+    pub expected_version: Option<u64>,
+}
+
+impl UpdateRequest {
+    pub fn new(status: JobStatus) -> Self {
+        UpdateRequest {
+            status,
+            status_details: None,
+            expected_version: None,
+        }
+    }
+    /// Serialize the request to JSON, failing if it doesn't fit in `N` bytes.
+    pub fn to_json<const N: usize>(&self) -> Result<ArrayString<N>, Error> {
+        let mut s = ArrayString::<N>::new();
+        self.write_json(&mut s).map_err(|_| Error::FAIL)?;
+        Ok(s)
+    }
+
+    fn write_json(&self, w: &mut impl Write) -> core::fmt::Result {
+        w.write_str(r#"{"status":""#)?;
+        w.write_str(self.status.as_str())?;
+        w.write_char('"')?;
+        if let Some(details) = &self.status_details {
+            w.write_str(r#","statusDetails":"#)?;
+            details.write_json(w)?;
+        }
+        if let Some(version) = self.expected_version {
+            w.write_str(r#","expectedVersion":"#)?;
+            w.write_str(&u64_to_str(version))?;
+        }
+        w.write_char('}')
+    }
+}
+
+/// Find the `jobDocument` of a job execution payload, as raw JSON bytes
+/// borrowed from `payload`.
+///
+/// The document is looked up at the top level and in the `execution` of
+/// `notify-next`, `start-next/accepted` and `get/accepted` messages. It is
+/// not copied nor size limited, so deserialize it with whatever suits its
+/// size.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let payload = br#"{"timestamp":1489096425,"execution":{"jobId":"ota-1","status":"QUEUED","jobDocument":{"url":"https://example.com/firmware.bin"}}}"#;
+/// let document = jobs::job_document_slice(payload).unwrap();
+/// assert_eq!(document, br#"{"url":"https://example.com/firmware.bin"}"#);
+/// ```
+pub fn job_document_slice(payload: &[u8]) -> Result<&[u8], Error> {
+    if let Some(document) = json_field(payload, JOB_DOCUMENT)? {
+        return Ok(document);
+    }
+    let execution = json_field(payload, EXECUTION)?.ok_or(Error::PayloadParseFailed)?;
+    json_field(execution, JOB_DOCUMENT)?.ok_or(Error::PayloadParseFailed)
+}
+
+/// A job execution in the lists of [`JobsChangedEvent`].
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+pub struct JobExecutionSummary<'a> {
+    #[serde(rename = "jobId")]
+    pub job_id: &'a str,
+    #[serde(rename = "queuedAt")]
+    pub queued_at: Option<u64>,
+    #[serde(rename = "startedAt")]
+    pub started_at: Option<u64>,
+    #[serde(rename = "lastUpdatedAt")]
+    pub last_updated_at: Option<u64>,
+    #[serde(rename = "versionNumber")]
+    pub version_number: Option<u64>,
+    #[serde(rename = "executionNumber")]
+    pub execution_number: Option<u64>,
+}
+
+/// The pending job executions of a `notify` message, by status, with at
+/// most `N` executions per status.
+#[cfg(feature = "serde")]
+#[derive(Debug, Default, PartialEq, Eq, serde::Deserialize)]
+pub struct PendingJobs<'a, const N: usize> {
+    #[serde(rename = "IN_PROGRESS", default, borrow)]
+    pub in_progress: ArrayVec<JobExecutionSummary<'a>, N>,
+    #[serde(rename = "QUEUED", default, borrow)]
+    pub queued: ArrayVec<JobExecutionSummary<'a>, N>,
+}
+
+/// The payload of a `notify` message, published whenever a job execution
+/// is added to or removed from the pending executions of the thing.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+pub struct JobsChangedEvent<'a, const N: usize> {
+    pub timestamp: Option<u64>,
+    #[serde(default, borrow)]
+    pub jobs: PendingJobs<'a, N>,
+}
+
+/// Parse the payload of a `notify` message.
+///
+/// A status with more than `N` executions fails with
+/// `Error::PayloadParseFailed`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let payload = br#"{"timestamp":1517016948,"jobs":{"QUEUED":[{"jobId":"ota-1","queuedAt":1517016947,"lastUpdatedAt":1517016947,"executionNumber":1,"versionNumber":1}]}}"#;
+/// let event = jobs::parse_jobs_changed::<4>(payload).unwrap();
+/// assert!(event.jobs.in_progress.is_empty());
+/// assert_eq!(event.jobs.queued[0].job_id, "ota-1");
+/// ```
+#[cfg(feature = "serde")]
+pub fn parse_jobs_changed<const N: usize>(
+    payload: &[u8],
+) -> Result<JobsChangedEvent<'_, N>, Error> {
+    serde_json::from_slice(payload).map_err(|_| Error::PayloadParseFailed)
+}
+
+#[cfg(feature = "serde")]
+impl JobExecutionSummary<'_> {
+    /// The DescribeJobExecution topic of this execution.
+    #[must_use = "this returns the topic without publishing or subscribing to it"]
+    pub fn describe_topic(
+        &self,
+        thing_name: &str,
+    ) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+        describe(thing_name, self.job_id)
+    }
+    /// The UpdateJobExecution topic of this execution.
+    #[must_use = "this returns the topic without publishing or subscribing to it"]
+    pub fn update_topic(
+        &self,
+        thing_name: &str,
+    ) -> Result<ArrayString<JOBS_TOPIC_MAX_LENGTH>, Error> {
+        update(thing_name, self.job_id)
+    }
+}
+
+/// The pending job executions of a thing in the order they should be
+/// processed: the executions already in progress first, by the time they
+/// were started, then the queued ones, oldest first.
+///
+/// Iterating the queue takes the executions out of it one at a time.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let payload = br#"{"timestamp":1489096425,"inProgressJobs":[{"jobId":"ota-2","queuedAt":1489096300,"startedAt":1489096400}],"queuedJobs":[{"jobId":"reboot","queuedAt":1489096350},{"jobId":"ota-3","queuedAt":1489096320}]}"#;
+/// let mut queue = jobs::parse_pending::<4>(payload).unwrap();
+/// let next = queue.next().unwrap();
+/// assert_eq!(next.job_id, "ota-2");
+/// assert_eq!(&next.update_topic("chloe").unwrap()[..], "$aws/things/chloe/jobs/ota-2/update");
+/// assert_eq!(queue.map(|job| job.job_id).collect::<Vec<_>>(), ["ota-3", "reboot"]);
+/// ```
+#[cfg(feature = "serde")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct JobQueue<'a, const N: usize> {
+    in_progress: ArrayVec<JobExecutionSummary<'a>, N>,
+    queued: ArrayVec<JobExecutionSummary<'a>, N>,
+}
+
+/// The payload of a `get/accepted` response.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct PendingExecutions<'a, const N: usize> {
+    #[serde(rename = "inProgressJobs", default, borrow)]
+    in_progress: ArrayVec<JobExecutionSummary<'a>, N>,
+    #[serde(rename = "queuedJobs", default, borrow)]
+    queued: ArrayVec<JobExecutionSummary<'a>, N>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a, const N: usize> JobQueue<'a, N> {
+    fn new(
+        mut in_progress: ArrayVec<JobExecutionSummary<'a>, N>,
+        mut queued: ArrayVec<JobExecutionSummary<'a>, N>,
+    ) -> Self {
+        // A missing time sorts last, ties are broken by id so that the order
+        // doesn't depend on the order of the payload. Both are kept in
+        // reverse, so that `next` pops from the end.
+        in_progress.sort_unstable_by_key(|job| {
+            core::cmp::Reverse((
+                job.started_at.or(job.queued_at).unwrap_or(u64::MAX),
+                job.job_id,
+            ))
+        });
+        queued.sort_unstable_by_key(|job| {
+            core::cmp::Reverse((job.queued_at.unwrap_or(u64::MAX), job.job_id))
+        });
+        Self {
+            in_progress,
+            queued,
+        }
+    }
+    /// The execution [`next`](Iterator::next) would return.
+    pub fn peek(&self) -> Option<&JobExecutionSummary<'a>> {
+        self.in_progress.last().or_else(|| self.queued.last())
+    }
+    pub fn len(&self) -> usize {
+        self.in_progress.len() + self.queued.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, const N: usize> From<PendingExecutions<'a, N>> for JobQueue<'a, N> {
+    fn from(pending: PendingExecutions<'a, N>) -> Self {
+        Self::new(pending.in_progress, pending.queued)
+    }
+}
+
+/// The executions of a `notify` message.
+#[cfg(feature = "serde")]
+impl<'a, const N: usize> From<PendingJobs<'a, N>> for JobQueue<'a, N> {
+    fn from(pending: PendingJobs<'a, N>) -> Self {
+        Self::new(pending.in_progress, pending.queued)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a, const N: usize> Iterator for JobQueue<'a, N> {
+    type Item = JobExecutionSummary<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.in_progress.pop().or_else(|| self.queued.pop())
+    }
+}
+
+/// Parse the payload of a `get/accepted` response into a [`JobQueue`].
+///
+/// A status with more than `N` executions fails with
+/// `Error::PayloadParseFailed`.
+#[cfg(feature = "serde")]
+pub fn parse_pending<const N: usize>(payload: &[u8]) -> Result<JobQueue<'_, N>, Error> {
+    serde_json::from_slice::<PendingExecutions<N>>(payload)
+        .map(JobQueue::from)
+        .map_err(|_| Error::PayloadParseFailed)
+}
+
+/// A job execution, as in the `start-next/accepted` response, with its job
+/// document parsed as `T`. `statusDetails` is ignored.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub struct JobExecution<'a, T> {
+    #[serde(rename = "jobId")]
+    pub job_id: &'a str,
+    #[serde(rename = "thingName")]
+    pub thing_name: Option<&'a str>,
+    pub status: &'a str,
+    #[serde(rename = "queuedAt")]
+    pub queued_at: Option<u64>,
+    #[serde(rename = "startedAt")]
+    pub started_at: Option<u64>,
+    #[serde(rename = "lastUpdatedAt")]
+    pub last_updated_at: Option<u64>,
+    #[serde(rename = "versionNumber")]
+    pub version_number: Option<u64>,
+    #[serde(rename = "executionNumber")]
+    pub execution_number: Option<u64>,
+    #[serde(rename = "jobDocument")]
+    pub job_document: T,
+}
+
+/// Parse the payload of a `start-next/accepted` response.
+///
+/// `None` when there was no pending job execution to start, i.e. when the
+/// `execution` member is missing, `null` or empty.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Ota<'a> {
+///     url: &'a str,
+/// }
+///
+/// let payload = br#"{"timestamp":1489096425,"execution":{"jobId":"ota-1","status":"IN_PROGRESS","versionNumber":2,"executionNumber":1,"jobDocument":{"url":"https://example.com/fw.bin"}}}"#;
+/// let execution = jobs::parse_start_next::<Ota>(payload).unwrap().unwrap();
+/// assert_eq!(execution.job_id, "ota-1");
+/// assert_eq!(execution.job_document.url, "https://example.com/fw.bin");
+///
+/// let payload = br#"{"timestamp":1489096425,"clientToken":"token-1"}"#;
+/// assert!(jobs::parse_start_next::<Ota>(payload).unwrap().is_none());
+/// ```
+#[cfg(feature = "serde")]
+pub fn parse_start_next<'a, T: serde::Deserialize<'a>>(
+    payload: &'a [u8],
+) -> Result<Option<JobExecution<'a, T>>, Error> {
+    let execution = match json_field(payload, EXECUTION)? {
+        None | Some(b"null") => return Ok(None),
+        Some(execution) => execution,
+    };
+    // `{}`, possibly with whitespace inside.
+    if execution
+        .iter()
+        .filter(|b| !b.is_ascii_whitespace())
+        .eq(b"{}")
+    {
+        return Ok(None);
+    }
+    serde_json::from_slice(execution)
+        .map(Some)
+        .map_err(|_| Error::PayloadParseFailed)
+}
+
+/// The payload of a rejected jobs response, e.g. on `update/rejected`.
+///
+/// Common jobs error codes:
+///
+/// | Code                     | Meaning                                                  |
+/// |--------------------------|----------------------------------------------------------|
+/// | `InvalidTopic`           | The request was published to an unknown jobs topic       |
+/// | `InvalidJson`            | The request payload isn't valid JSON                     |
+/// | `InvalidRequest`         | The request payload is not a valid jobs request          |
+/// | `InvalidStateTransition` | The execution can't move to the requested status         |
+/// | `ResourceNotFound`       | The job or the job execution doesn't exist               |
+/// | `VersionMismatch`        | The `expectedVersion` doesn't match the execution's      |
+/// | `InternalError`          | Internal service failure                                 |
+/// | `RequestThrottled`       | Too many requests                                        |
+/// | `TerminalStateReached`   | The execution already reached a terminal status          |
+///
+/// `execution_state` is only present for `InvalidStateTransition` and
+/// `VersionMismatch`. The strings are borrowed from the payload, so a
+/// message with JSON escapes in it fails to parse.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub struct JobUpdateError<'a> {
+    pub code: &'a str,
+    pub message: Option<&'a str>,
+    #[serde(rename = "clientToken")]
+    pub client_token: Option<&'a str>,
+    pub timestamp: Option<u64>,
+    #[serde(rename = "executionState")]
+    pub execution_state: Option<ExecutionState<'a>>,
+}
+
+/// The current state of the job execution, as reported in a rejected
+/// response. `statusDetails` is ignored.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub struct ExecutionState<'a> {
+    pub status: &'a str,
+    #[serde(rename = "versionNumber")]
+    pub version_number: Option<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl JobUpdateError<'_> {
+    /// The update carried an `expectedVersion` other than the execution's;
+    /// fetch the execution again and retry against the new version.
+    pub fn is_version_conflict(&self) -> bool {
+        self.code == "VersionMismatch"
+    }
+    /// The execution can't move to the requested status, e.g. because the
+    /// job was canceled; fetch the execution again before going on.
+    pub fn is_invalid_transition(&self) -> bool {
+        self.code == "InvalidStateTransition"
+    }
+}
+
+/// Parse the payload of a rejected jobs response.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs;
+///
+/// let payload = br#"{"code":"VersionMismatch","message":"Version mismatch","timestamp":1489096425,"executionState":{"status":"IN_PROGRESS","versionNumber":3}}"#;
+/// let error = jobs::parse_rejected(payload).unwrap();
+/// assert!(error.is_version_conflict());
+/// assert_eq!(error.execution_state.unwrap().version_number, Some(3));
+/// ```
+#[cfg(feature = "serde")]
+pub fn parse_rejected(payload: &[u8]) -> Result<JobUpdateError<'_>, Error> {
+    serde_json::from_slice(payload).map_err(|_| Error::PayloadParseFailed)
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::common::*;
     use crate::jobs;
+    use arrayvec::ArrayString;
+    #[test]
+    fn jobs_match_topic_bytes() {
+        let jobs =
+            jobs::match_topic_bytes(b"$aws/things/chloe/jobs/example-job-01/update/accepted")
+                .unwrap();
+        assert_eq!(jobs.thing_name, "chloe");
+        assert_eq!(jobs.api, jobs::Topic::UpdateSuccess);
+        assert_eq!(&jobs.id.unwrap()[..], "example-job-01");
+
+        let jobs = jobs::match_topic_bytes(b"$aws/things/chloe/jobs/\xc3/get");
+        assert_eq!(jobs.err(), Some(Error::MqttTopicFailed));
+    }
     #[test]
     fn assemble_topic_notify_next() {
         let topic = jobs::assemble_topic("chloe", jobs::Topic::NextJobChanged).unwrap();
@@ -259,6 +1441,21 @@ mod tests {
         assert_eq!(&topic[..], "$aws/things/chloe/jobs/+/update/rejected");
     }
 
+    #[test]
+    fn assemble_topic_max_length_names() {
+        let thing_name = "t".repeat(THINGNAME_MAX_LENGTH);
+        let job_id = "j".repeat(JOBID_MAX_LENGTH);
+        let topic = jobs::assemble_topic(&thing_name, jobs::Topic::UpdateFailed).unwrap();
+        assert!(topic.ends_with("/jobs/+/update/rejected"));
+        let topic = jobs::get_pending(&thing_name).unwrap();
+        assert!(topic.ends_with("/jobs/get"));
+        let topic = jobs::start_next(&thing_name).unwrap();
+        assert!(topic.ends_with("/jobs/start-next"));
+        let topic = jobs::describe(&thing_name, &job_id).unwrap();
+        assert!(topic.ends_with("/get"));
+        let topic = jobs::update(&thing_name, &job_id).unwrap();
+        assert!(topic.ends_with("/update"));
+    }
     #[test]
     fn match_topic() {
         let jobs = jobs::match_topic("$aws/things/chloe/jobs/notify-next").unwrap();
@@ -279,6 +1476,97 @@ mod tests {
         assert_eq!(&id[..], "example-job-01");
     }
     #[test]
+    fn response_filters() {
+        let filters = jobs::response_filters("chloe").unwrap();
+        assert_eq!(
+            filters.each_ref().map(|f| f.as_str()),
+            [
+                "$aws/things/chloe/jobs/notify",
+                "$aws/things/chloe/jobs/notify-next",
+                "$aws/things/chloe/jobs/get/+",
+                "$aws/things/chloe/jobs/start-next/+",
+                "$aws/things/chloe/jobs/+/get/+",
+                "$aws/things/chloe/jobs/+/update/+",
+            ]
+        );
+        assert_eq!(
+            jobs::response_filters("ch+loe"),
+            Err(Error::ThingnameParseFailed)
+        );
+    }
+    #[test]
+    fn to_filter() {
+        let jobs = jobs::match_topic("$aws/things/chloe/jobs/notify").unwrap();
+        assert_eq!(
+            &jobs.to_filter().unwrap()[..],
+            "$aws/things/chloe/jobs/notify"
+        );
+        let jobs = jobs::match_topic("$aws/things/chloe/jobs/start-next/rejected").unwrap();
+        assert_eq!(
+            &jobs.to_filter().unwrap()[..],
+            "$aws/things/chloe/jobs/start-next/+"
+        );
+        let jobs = jobs::match_topic("$aws/things/chloe/jobs/example-job-01/get/accepted").unwrap();
+        assert_eq!(
+            &jobs.to_filter().unwrap()[..],
+            "$aws/things/chloe/jobs/+/get/+"
+        );
+    }
+    #[test]
+    fn match_topic_get_pending_request() {
+        let jobs = jobs::match_topic("$aws/things/chloe/jobs/get").unwrap();
+        assert_eq!(jobs.api, jobs::Topic::GetPending);
+        assert_eq!(jobs.id, None);
+    }
+    #[test]
+    fn match_topic_start_next_request() {
+        let jobs = jobs::match_topic("$aws/things/chloe/jobs/start-next").unwrap();
+        assert_eq!(jobs.api, jobs::Topic::StartNext);
+        assert_eq!(jobs.id, None);
+    }
+    #[test]
+    fn match_topic_update_request() {
+        let jobs = jobs::match_topic("$aws/things/chloe/jobs/example/update").unwrap();
+        assert_eq!(jobs.api, jobs::Topic::Update);
+        assert_eq!(&jobs.id.unwrap()[..], "example");
+        let jobs = jobs::match_topic("$aws/things/chloe/jobs/example/get").unwrap();
+        assert_eq!(jobs.api, jobs::Topic::Describe);
+        assert_eq!(&jobs.id.unwrap()[..], "example");
+    }
+    #[test]
+    fn match_topic_unknown_op() {
+        let res = jobs::match_topic("$aws/things/chloe/jobs/foo");
+        assert_eq!(res.err(), Some(Error::NoMatch));
+    }
+    #[test]
+    fn topic_name_round_trip() {
+        for &topic in jobs::ALL_TOPICS {
+            assert_eq!(jobs::Topic::try_from(topic.as_str()), Ok(topic));
+        }
+        assert_eq!(
+            jobs::Topic::try_from(""),
+            Err(Error::MessageTypeParseFailed)
+        );
+        assert_eq!(
+            jobs::Topic::try_from("foo/accepted"),
+            Err(Error::MessageTypeParseFailed)
+        );
+        let topic = jobs::Topic::DescribeFailed;
+        assert_eq!(jobs::from_name(topic.as_str()), Ok(topic));
+        assert_eq!(
+            jobs::from_name("describe"),
+            Err(Error::MessageTypeParseFailed)
+        );
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn topic_serde() {
+        let json = serde_json::to_string(&jobs::Topic::UpdateSuccess).unwrap();
+        assert_eq!(json, "\"+/update/accepted\"");
+        let topic: jobs::Topic = serde_json::from_str(&json).unwrap();
+        assert_eq!(topic, jobs::Topic::UpdateSuccess);
+    }
+    #[test]
     fn get_pending() {
         let topic = jobs::get_pending("chloe").unwrap();
         assert_eq!(&topic[..], "$aws/things/chloe/jobs/get");
@@ -293,4 +1581,490 @@ mod tests {
         let topic = jobs::update("chloe", "example-job-01").unwrap();
         assert_eq!(&topic[..], "$aws/things/chloe/jobs/example-job-01/update");
     }
+    #[test]
+    fn status_details_progress() {
+        let mut details = jobs::StatusDetails::new();
+        details.progress_percent(50).unwrap();
+        let mut json = ArrayString::<64>::new();
+        json.push_str(r#"{"statusDetails":"#);
+        details.write_json(&mut json).unwrap();
+        json.push('}');
+        assert_eq!(&json[..], r#"{"statusDetails":{"progress":"50"}}"#);
+
+        let request = jobs::UpdateRequest {
+            status_details: Some(details),
+            ..jobs::UpdateRequest::new(jobs::JobStatus::Succeeded)
+        };
+        assert_eq!(
+            &request.to_json::<64>().unwrap()[..],
+            r#"{"status":"SUCCEEDED","statusDetails":{"progress":"50"}}"#
+        );
+    }
+    #[test]
+    fn job_status() {
+        use jobs::JobStatus::*;
+        for status in [InProgress, Succeeded, Failed, Rejected] {
+            assert!(status.is_device_settable());
+        }
+        for status in [Queued, Canceled, TimedOut, Removed] {
+            assert!(!status.is_device_settable());
+            assert_eq!(status.is_terminal(), status != Queued);
+        }
+        assert!(!InProgress.is_terminal() && Succeeded.is_terminal());
+
+        let request = jobs::UpdateRequest::new(Canceled);
+        assert_eq!(
+            &request.to_json::<32>().unwrap()[..],
+            r#"{"status":"CANCELED"}"#
+        );
+        let request = jobs::UpdateRequest {
+            expected_version: Some(3),
+            ..jobs::UpdateRequest::new(Removed)
+        };
+        assert_eq!(
+            &request.to_json::<64>().unwrap()[..],
+            r#"{"status":"REMOVED","expectedVersion":3}"#
+        );
+    }
+    #[test]
+    fn status_details_bounds() {
+        let mut details = jobs::StatusDetails::new();
+        assert_eq!(details.progress_percent(101), Err(Error::FAIL));
+        assert_eq!(details.insert("bad key", "v"), Err(Error::FAIL));
+        assert_eq!(details.insert("key", "say \"hi\""), Err(Error::FAIL));
+        let long = "v".repeat(jobs::STATUS_DETAILS_VALUE_MAX_LENGTH + 1);
+        assert_eq!(details.insert("key", &long), Err(Error::FAIL));
+
+        for i in 0..jobs::STATUS_DETAILS_MAX {
+            details.insert(&u64_to_str(i as u64), "v").unwrap();
+        }
+        assert_eq!(details.insert("key", "v"), Err(Error::FAIL));
+        // Replacing an existing key still works on a full map.
+        details.insert("0", "w").unwrap();
+        assert_eq!(details.get("0"), Some("w"));
+
+        let request = jobs::UpdateRequest::new(jobs::JobStatus::InProgress);
+        assert_eq!(request.to_json::<16>(), Err(Error::FAIL));
+    }
+    #[test]
+    fn job_document_slice() {
+        let payload = br#"{
+            "clientToken": "client-001",
+            "timestamp": 1489088524,
+            "execution": {
+                "jobId": "example-job-01",
+                "statusDetails": {"progress": "}"},
+                "jobDocument": {"operation": "install", "urls": ["s3://a", "s3://b"]},
+                "versionNumber": 1
+            }
+        }"#;
+        let document = jobs::job_document_slice(payload).unwrap();
+        assert_eq!(
+            document,
+            br#"{"operation": "install", "urls": ["s3://a", "s3://b"]}"#
+        );
+
+        let payload = br#"{"jobDocument":{"a":1}}"#;
+        assert_eq!(jobs::job_document_slice(payload), Ok(&br#"{"a":1}"#[..]));
+
+        let payload = br#"{"execution":{"jobId":"example-job-01"}}"#;
+        assert_eq!(
+            jobs::job_document_slice(payload),
+            Err(Error::PayloadParseFailed)
+        );
+        assert_eq!(
+            jobs::job_document_slice(b"{}"),
+            Err(Error::PayloadParseFailed)
+        );
+        assert_eq!(
+            jobs::job_document_slice(br#"{"execution":{"jobDocument":{"#),
+            Err(Error::PayloadParseFailed)
+        );
+    }
+    #[test]
+    fn compare_matches() {
+        let jobs = jobs::match_topic("$aws/things/chloe/jobs/example/get/accepted").unwrap();
+        assert_eq!(
+            jobs,
+            jobs::ThingJobs {
+                thing_name: "chloe",
+                api: jobs::Topic::DescribeSuccess,
+                id: Some(ArrayString::from("example").unwrap()),
+            }
+        );
+        assert_ne!(
+            jobs,
+            jobs::match_topic("$aws/things/chloe/jobs/other/get/accepted").unwrap()
+        );
+    }
+    #[test]
+    fn job_scoped_topics() {
+        let topics = jobs::job_scoped_topics("chloe", "example-job-01").unwrap();
+        assert_eq!(
+            topics.iter().map(|t| &t[..]).collect::<Vec<_>>(),
+            [
+                "$aws/things/chloe/jobs/example-job-01/update/accepted",
+                "$aws/things/chloe/jobs/example-job-01/update/rejected",
+                "$aws/things/chloe/jobs/example-job-01/get/accepted",
+                "$aws/things/chloe/jobs/example-job-01/get/rejected",
+            ]
+        );
+        for topic in &topics {
+            let jobs = jobs::match_topic(topic).unwrap();
+            assert_eq!(&jobs.id.unwrap()[..], "example-job-01");
+        }
+        assert_eq!(
+            jobs::job_scoped_topics("chloe", "bad/id").err(),
+            Some(Error::JobsIdParseFailed)
+        );
+        assert_eq!(
+            jobs::job_scoped_topics("ch#loe", "example-job-01").err(),
+            Some(Error::ThingnameParseFailed)
+        );
+    }
+    #[test]
+    fn assemble_job_topic() {
+        let topic =
+            jobs::assemble_job_topic("chloe", "example", jobs::Topic::UpdateFailed).unwrap();
+        assert_eq!(&topic[..], "$aws/things/chloe/jobs/example/update/rejected");
+        assert_eq!(
+            jobs::assemble_job_topic("chloe", "$next", jobs::Topic::Update).err(),
+            Some(Error::JobsIdParseFailed)
+        );
+        for api in [
+            jobs::Topic::JobsChanged,
+            jobs::Topic::GetPendingSuccess,
+            jobs::Topic::StartNext,
+        ] {
+            assert_eq!(
+                jobs::assemble_job_topic("chloe", "example", api).err(),
+                Some(Error::FAIL)
+            );
+        }
+    }
+    #[test]
+    fn next_pseudo_id() {
+        use jobs::Topic::*;
+        let topic = jobs::describe("chloe", "$next").unwrap();
+        assert_eq!(&topic[..], "$aws/things/chloe/jobs/$next/get");
+        for api in [Describe, DescribeSuccess, DescribeFailed] {
+            let topic = jobs::assemble_job_topic("chloe", "$next", api).unwrap();
+            let jobs = jobs::match_topic(&topic).unwrap();
+            assert_eq!(jobs.api, api);
+            assert_eq!(&jobs.id.unwrap()[..], "$next");
+        }
+        assert_eq!(
+            jobs::update("chloe", "$next").err(),
+            Some(Error::JobsIdParseFailed)
+        );
+        for topic in [
+            "$aws/things/chloe/jobs/$next/update",
+            "$aws/things/chloe/jobs/$next/update/accepted",
+            "$aws/things/chloe/jobs/$other/get",
+        ] {
+            assert_eq!(
+                jobs::match_topic(topic).err(),
+                Some(Error::JobsIdParseFailed),
+                "{topic}"
+            );
+        }
+    }
+    #[test]
+    fn all_topics() {
+        use jobs::ALL_TOPICS;
+        // The number of variants, to bump along with the enum.
+        assert_eq!(ALL_TOPICS.len(), 14);
+        for (i, api) in ALL_TOPICS.iter().enumerate() {
+            assert_eq!(*api as usize, i);
+        }
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn string_topics() {
+        for &api in jobs::ALL_TOPICS {
+            let topic = jobs::assemble_topic("chloe", api).unwrap();
+            assert_eq!(
+                jobs::assemble_topic_string("chloe", api).unwrap(),
+                &topic[..]
+            );
+            if let Ok(topic) = jobs::assemble_job_topic("chloe", "ota-1", api) {
+                let string = jobs::assemble_job_topic_string("chloe", "ota-1", api);
+                assert_eq!(string.unwrap(), &topic[..]);
+            }
+        }
+        assert_eq!(
+            jobs::assemble_job_topic_string("chloe", "$next", jobs::Topic::Update),
+            Err(Error::JobsIdParseFailed)
+        );
+        // A thing prefix too long for the fixed capacity topics.
+        let prefix = format!("{}/", "bridge".repeat(40));
+        let config = TopicConfig::new(&prefix).unwrap();
+        let long_name = "t".repeat(THINGNAME_MAX_LENGTH);
+        assert_eq!(
+            jobs::assemble_topic_with(&config, &long_name, jobs::Topic::GetPending),
+            Err(Error::FAIL)
+        );
+        let topic =
+            jobs::assemble_topic_string_with(&config, &long_name, jobs::Topic::GetPending).unwrap();
+        assert_eq!(topic, format!("{prefix}{long_name}/jobs/get"));
+        // Up to the MQTT limit only.
+        let prefix = format!("{}/", "b".repeat(MQTT_TOPIC_LENGTH_MAX - 8));
+        let config = TopicConfig::new(&prefix).unwrap();
+        assert_eq!(
+            jobs::assemble_topic_string_with(&config, "chloe", jobs::Topic::GetPending),
+            Err(Error::MqttTopicTooLong)
+        );
+    }
+    #[test]
+    fn topic_len_matches_assemble_topic() {
+        use jobs::Topic::*;
+        for &api in jobs::ALL_TOPICS {
+            let topic = jobs::assemble_topic("chloe", api).unwrap();
+            assert_eq!(jobs::topic_len("chloe", api), topic.len());
+            if api >= Describe {
+                let topic = jobs::assemble_job_topic("chloe", "example", api).unwrap();
+                assert_eq!(jobs::job_topic_len("chloe", "example", api), topic.len());
+            }
+        }
+    }
+    #[test]
+    fn match_topic_ref_borrows_id() {
+        let topic = "$aws/things/chloe/jobs/example-job-01/get/rejected";
+        let jobs = jobs::match_topic_ref(topic).unwrap();
+        assert_eq!(jobs.api, jobs::Topic::DescribeFailed);
+        let id = jobs.id.unwrap();
+        assert_eq!(id, "example-job-01");
+        // The id is a slice of the topic itself.
+        let start = topic.find(id).unwrap();
+        assert!(core::ptr::eq(id.as_ptr(), topic[start..].as_ptr()));
+
+        let jobs = jobs::match_topic_ref("$aws/things/chloe/jobs/start-next/accepted").unwrap();
+        assert_eq!(jobs.id, None);
+
+        let id = "j".repeat(JOBID_MAX_LENGTH + 1);
+        let topic = format!("$aws/things/chloe/jobs/{id}/update");
+        assert_eq!(jobs::match_topic_ref(&topic), Err(Error::JobsIdParseFailed));
+        assert_eq!(
+            jobs::match_topic(&topic).err(),
+            Some(Error::JobsIdParseFailed)
+        );
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn job_queue_order() {
+        let payload = br#"{
+            "timestamp": 1489096425,
+            "inProgressJobs": [
+                {"jobId": "late", "queuedAt": 100, "startedAt": 400},
+                {"jobId": "early", "queuedAt": 200, "startedAt": 300}
+            ],
+            "queuedJobs": [
+                {"jobId": "c", "queuedAt": 250},
+                {"jobId": "untimed"},
+                {"jobId": "a", "queuedAt": 150},
+                {"jobId": "b", "queuedAt": 150}
+            ]
+        }"#;
+        let mut queue = jobs::parse_pending::<4>(payload).unwrap();
+        assert_eq!(queue.len(), 6);
+        assert_eq!(queue.peek().unwrap().job_id, "early");
+        let next = queue.next().unwrap();
+        assert_eq!(
+            &next.describe_topic("chloe").unwrap()[..],
+            "$aws/things/chloe/jobs/early/get"
+        );
+        let order: Vec<_> = queue.by_ref().map(|job| job.job_id).collect();
+        assert_eq!(order, ["late", "a", "b", "c", "untimed"]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.peek(), None);
+
+        let event = jobs::parse_jobs_changed::<4>(
+            br#"{"jobs":{"QUEUED":[{"jobId":"q","queuedAt":2}],"IN_PROGRESS":[{"jobId":"p","queuedAt":1}]}}"#,
+        )
+        .unwrap();
+        let queue = jobs::JobQueue::from(event.jobs);
+        assert_eq!(queue.map(|job| job.job_id).collect::<Vec<_>>(), ["p", "q"]);
+
+        assert!(jobs::parse_pending::<4>(br#"{"timestamp":1}"#)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            jobs::parse_pending::<1>(payload),
+            Err(Error::PayloadParseFailed)
+        );
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_start_next() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Document<'a> {
+            operation: &'a str,
+        }
+        let payload = br#"{
+            "timestamp": 1489096425,
+            "clientToken": "token-1",
+            "execution": {
+                "jobId": "example-job-01",
+                "thingName": "chloe",
+                "status": "IN_PROGRESS",
+                "queuedAt": 1489096123,
+                "startedAt": 1489096425,
+                "lastUpdatedAt": 1489096425,
+                "versionNumber": 2,
+                "executionNumber": 1,
+                "statusDetails": {"step": "download"},
+                "jobDocument": {"operation": "reboot"}
+            }
+        }"#;
+        let execution = jobs::parse_start_next::<Document>(payload)
+            .unwrap()
+            .unwrap();
+        assert_eq!(execution.job_id, "example-job-01");
+        assert_eq!(execution.thing_name, Some("chloe"));
+        assert_eq!(execution.status, "IN_PROGRESS");
+        assert_eq!(execution.version_number, Some(2));
+        assert_eq!(
+            execution.job_document,
+            Document {
+                operation: "reboot"
+            }
+        );
+
+        for payload in [
+            &br#"{"timestamp":1489096425,"clientToken":"token-1"}"#[..],
+            br#"{"timestamp":1489096425,"execution":{ }}"#,
+            br#"{"timestamp":1489096425,"execution":null}"#,
+        ] {
+            assert_eq!(jobs::parse_start_next::<Document>(payload), Ok(None));
+        }
+        for payload in [
+            &br#"{"execution":{"jobId":"example-job-01"}}"#[..],
+            br#"{"execution":"#,
+            b"[]",
+        ] {
+            assert_eq!(
+                jobs::parse_start_next::<Document>(payload),
+                Err(Error::PayloadParseFailed)
+            );
+        }
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_rejected() {
+        let payload = br#"{"code":"InvalidStateTransition","message":"Job execution is already in a terminal state","clientToken":"token-1","timestamp":1489096425,"executionState":{"status":"CANCELED","statusDetails":{"step":"download"},"versionNumber":4}}"#;
+        let error = jobs::parse_rejected(payload).unwrap();
+        assert!(error.is_invalid_transition());
+        assert!(!error.is_version_conflict());
+        assert_eq!(error.client_token, Some("token-1"));
+        assert_eq!(
+            error.execution_state,
+            Some(jobs::ExecutionState {
+                status: "CANCELED",
+                version_number: Some(4),
+            })
+        );
+
+        let payload =
+            br#"{"code":"ResourceNotFound","message":"Job not found","timestamp":1489096425}"#;
+        let error = jobs::parse_rejected(payload).unwrap();
+        assert!(!error.is_invalid_transition() && !error.is_version_conflict());
+        assert_eq!(error.execution_state, None);
+
+        assert_eq!(
+            jobs::parse_rejected(br#"{"code":409}"#),
+            Err(Error::PayloadParseFailed)
+        );
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_jobs_changed() {
+        let payload = br#"{
+            "timestamp": 1517016948,
+            "jobs": {
+                "IN_PROGRESS": [
+                    {"jobId": "ota-1", "queuedAt": 1517016947, "lastUpdatedAt": 1517016958, "startedAt": 1517016955, "executionNumber": 1, "versionNumber": 2}
+                ],
+                "QUEUED": [
+                    {"jobId": "ota-2", "queuedAt": 1517016948, "lastUpdatedAt": 1517016948, "executionNumber": 1, "versionNumber": 1},
+                    {"jobId": "ota-3", "queuedAt": 1517016948, "lastUpdatedAt": 1517016948, "executionNumber": 1, "versionNumber": 1}
+                ]
+            }
+        }"#;
+        let event = jobs::parse_jobs_changed::<2>(payload).unwrap();
+        assert_eq!(event.timestamp, Some(1517016948));
+        let in_progress = &event.jobs.in_progress[0];
+        assert_eq!(in_progress.job_id, "ota-1");
+        assert_eq!(in_progress.started_at, Some(1517016955));
+        assert_eq!(in_progress.version_number, Some(2));
+        let queued: Vec<&str> = event.jobs.queued.iter().map(|job| job.job_id).collect();
+        assert_eq!(queued, ["ota-2", "ota-3"]);
+
+        assert_eq!(
+            jobs::parse_jobs_changed::<1>(payload),
+            Err(Error::PayloadParseFailed)
+        );
+
+        // The last pending execution went away.
+        let event =
+            jobs::parse_jobs_changed::<2>(br#"{"timestamp":1517016960,"jobs":{}}"#).unwrap();
+        assert!(event.jobs.in_progress.is_empty() && event.jobs.queued.is_empty());
+    }
+    #[test]
+    fn thing_jobs_display() {
+        let topic = "$aws/things/chloe/jobs/ota-1/update/accepted";
+        let expected = "jobs(thing=chloe, id=ota-1, op=UpdateSuccess)";
+        assert_eq!(jobs::match_topic(topic).unwrap().to_string(), expected);
+        assert_eq!(jobs::match_topic_ref(topic).unwrap().to_string(), expected);
+        let jobs = jobs::match_topic("$aws/things/chloe/jobs/notify-next").unwrap();
+        assert_eq!(jobs.to_string(), "jobs(thing=chloe, op=NextJobChanged)");
+    }
+    #[test]
+    fn thing_jobs_subsystem() {
+        use crate::TopicType;
+        let topic = "$aws/things/chloe/jobs/notify";
+        assert_eq!(
+            jobs::match_topic(topic).unwrap().subsystem(),
+            TopicType::Jobs
+        );
+        assert_eq!(
+            TopicType::from(&jobs::match_topic_ref(topic).unwrap()),
+            TopicType::Jobs
+        );
+    }
+    #[test]
+    fn validate_topic() {
+        for topic in [
+            "$aws/things/chloe/jobs/notify",
+            "$aws/things/chloe/jobs/start-next/accepted",
+            "$aws/things/chloe/jobs/$next/get",
+            "$aws/things/chloe/jobs/ota-1/update/rejected",
+        ] {
+            assert_eq!(jobs::validate_topic(topic), Ok(()), "{topic}");
+        }
+        for (topic, err) in [
+            // The subsystem is right, the structure isn't.
+            ("$aws/things/chloe/jobs/notify/accepted", Error::NoMatch),
+            (
+                "$aws/things/chloe/jobs/ota+1/update",
+                Error::JobsIdParseFailed,
+            ),
+            (
+                "$aws/things/chloe/jobs/$next/update",
+                Error::JobsIdParseFailed,
+            ),
+            ("$aws/things/chloe/jobs/ota-1/delete", Error::NoMatch),
+            (
+                "$aws/things/chloe/jobs/ota-1/update/accepted/more",
+                Error::NoMatch,
+            ),
+            (
+                "$aws/things/ch#loe/jobs/notify",
+                Error::ThingnameParseFailed,
+            ),
+            ("$aws/things/chloe/shadow/get", Error::RootParseFailed),
+            ("chloe/jobs/notify", Error::NoMatch),
+        ] {
+            assert_eq!(jobs::validate_topic(topic), Err(err), "{topic}");
+        }
+    }
 }
@@ -17,17 +17,26 @@
 //!
 //!
 //!
-// #![no_std]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#[macro_use]
+mod macros;
 pub mod backoff_algo;
 pub mod common;
 pub mod defender;
 pub mod jobs;
+pub mod prelude;
+#[cfg(feature = "serde")]
+pub mod response;
 pub mod shadow;
+pub mod subscribe;
 pub mod tunneling;
 
 pub use common::*;
+#[cfg(feature = "serde")]
+pub use response::{parse_response, Response};
+pub use subscribe::{QoS, SubscribePlan};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum TopicType {
     Other = 0,
     NamedShadow,
@@ -50,14 +59,16 @@ pub enum TopicType {
 ///
 /// assert_eq!(topic_type, TopicType::NamedShadow);
 /// ```
-pub fn match_topic_type<'a>(topic: &'a str) -> Result<TopicType, Error> {
-    is_valid_mqtt_topic(topic)?;
-
-    let s = is_valid_prefix(topic, AWS_THINGS_PREFIX)?;
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_type(topic: &str) -> Result<TopicType, Error> {
+    match_topic_type_with(&TopicConfig::default(), topic)
+}
 
-    let mid = s.find('/').ok_or(Error::NoMatch);
-    let (thing_name, s) = s.split_at(mid?);
-    is_valid_thing_name(thing_name)?;
+/// Same as [`match_topic_type`], with the thing prefix of `config`.
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_type_with(config: &TopicConfig, topic: &str) -> Result<TopicType, Error> {
+    let (_, s) = parse_thing_prefix_with(config, topic)?;
+    // `/shadow/name/` must come before its prefix `/shadow/`.
     if s.starts_with(NAMED_SHADOW_API_BRIDGE)   { Ok(TopicType::NamedShadow) }
     else if s.starts_with(SHADOW_API_BRIDGE)    { Ok(TopicType::Shadow) }
     else if s.starts_with(JOBS_API_BRIDGE)      { Ok(TopicType::Jobs) }
@@ -65,3 +76,50 @@ pub fn match_topic_type<'a>(topic: &'a str) -> Result<TopicType, Error> {
     else if s.starts_with(TUNNELS_API_BRIDGE)   { Ok(TopicType::Tunneling) }
     else { Err(Error::NoMatch) }
 }
+
+/// Same as [`match_topic_type`], for topics handed over as raw bytes by the
+/// MQTT client.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{match_topic_type_bytes, Error, TopicType};
+///
+/// let topic_type = match_topic_type_bytes(b"$aws/things/chloe/jobs/notify");
+/// assert_eq!(topic_type, Ok(TopicType::Jobs));
+///
+/// let topic_type = match_topic_type_bytes(b"$aws/things/\xffchloe/jobs/notify");
+/// assert_eq!(topic_type, Err(Error::MqttTopicFailed));
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_type_bytes(topic: &[u8]) -> Result<TopicType, Error> {
+    match_topic_type(topic_from_bytes(topic)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn match_topic_type_levels() {
+        let cases = [
+            ("$aws/things/chloe/shadow/get", Ok(TopicType::Shadow)),
+            (
+                "$aws/things/chloe/shadow/name/common/get",
+                Ok(TopicType::NamedShadow),
+            ),
+            ("$aws/things/chloe/shadow/names/get", Ok(TopicType::Shadow)),
+            ("$aws/things/chloe/jobs/notify", Ok(TopicType::Jobs)),
+            (
+                "$aws/things/chloe/defender/metrics/json",
+                Ok(TopicType::Defender),
+            ),
+            ("$aws/things/chloe/tunnels/notify", Ok(TopicType::Tunneling)),
+            ("$aws/things/chloe/defender/json", Err(Error::NoMatch)),
+            ("$aws/things/chloe/shadow", Err(Error::NoMatch)),
+            ("$aws/things/chloe/", Err(Error::NoMatch)),
+            ("$aws/things/chloe/streams/get", Err(Error::NoMatch)),
+        ];
+        for (topic, expected) in cases {
+            assert_eq!(match_topic_type(topic), expected, "{topic}");
+        }
+    }
+}
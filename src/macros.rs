@@ -0,0 +1,263 @@
+/// Build a shadow topic at compile time, for a thing name known at compile
+/// time, as a `&'static str`.
+///
+/// The names are validated during const evaluation, an invalid one fails
+/// the build. The operation is a variant of [`shadow::Topic`](crate::shadow::Topic).
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow_topic;
+///
+/// const DELTA: &str = shadow_topic!("chloe", UpdateDelta);
+/// assert_eq!(DELTA, "$aws/things/chloe/shadow/update/delta");
+/// assert_eq!(shadow_topic!("chloe", "common", Get), "$aws/things/chloe/shadow/name/common/get");
+/// ```
+///
+/// ```compile_fail
+/// use aws_iot_device_sdk::shadow_topic;
+///
+/// const GET: &str = shadow_topic!("chl/oe", Get);
+/// ```
+#[macro_export]
+macro_rules! shadow_topic {
+    ($thing_name:literal, $op:ident) => {{
+        const _: () = assert!(
+            $crate::common::is_valid_thing_name_const($thing_name),
+            "invalid thing name"
+        );
+        concat!(
+            "$aws/things/",
+            $thing_name,
+            "/shadow/",
+            $crate::__shadow_op!($op)
+        )
+    }};
+    ($thing_name:literal, $shadow_name:literal, $op:ident) => {{
+        const _: () = assert!(
+            $crate::common::is_valid_thing_name_const($thing_name),
+            "invalid thing name"
+        );
+        const _: () = assert!(
+            $crate::common::is_valid_shadow_name_const($shadow_name),
+            "invalid shadow name"
+        );
+        concat!(
+            "$aws/things/",
+            $thing_name,
+            "/shadow/name/",
+            $shadow_name,
+            "/",
+            $crate::__shadow_op!($op)
+        )
+    }};
+}
+
+/// Build a jobs topic at compile time, for a thing name and a job id known at
+/// compile time, as a `&'static str`.
+///
+/// The operation is a variant of [`jobs::Topic`](crate::jobs::Topic); the
+/// ones of a single job take the job id before it.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::jobs_topic;
+///
+/// assert_eq!(jobs_topic!("chloe", NextJobChanged), "$aws/things/chloe/jobs/notify-next");
+/// assert_eq!(
+///     jobs_topic!("chloe", "ota-1", UpdateSuccess),
+///     "$aws/things/chloe/jobs/ota-1/update/accepted"
+/// );
+/// ```
+///
+/// ```compile_fail
+/// use aws_iot_device_sdk::jobs_topic;
+///
+/// // Update needs a job id.
+/// const UPDATE: &str = jobs_topic!("chloe", Update);
+/// ```
+#[macro_export]
+macro_rules! jobs_topic {
+    ($thing_name:literal, $op:ident) => {{
+        const _: () = assert!(
+            $crate::common::is_valid_thing_name_const($thing_name),
+            "invalid thing name"
+        );
+        concat!(
+            "$aws/things/",
+            $thing_name,
+            "/jobs/",
+            $crate::__jobs_op!($op)
+        )
+    }};
+    ($thing_name:literal, $job_id:literal, $op:ident) => {{
+        const _: () = assert!(
+            $crate::common::is_valid_thing_name_const($thing_name),
+            "invalid thing name"
+        );
+        const _: () = assert!(
+            $crate::common::is_valid_job_id_const($job_id, $crate::__jobs_id_next!($op)),
+            "invalid job id"
+        );
+        concat!(
+            "$aws/things/",
+            $thing_name,
+            "/jobs/",
+            $job_id,
+            "/",
+            $crate::__jobs_id_op!($op)
+        )
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __shadow_op {
+    (Get) => {
+        "get"
+    };
+    (GetAccepted) => {
+        "get/accepted"
+    };
+    (GetRejected) => {
+        "get/rejected"
+    };
+    (Delete) => {
+        "delete"
+    };
+    (DeleteAccepted) => {
+        "delete/accepted"
+    };
+    (DeleteRejected) => {
+        "delete/rejected"
+    };
+    (Update) => {
+        "update"
+    };
+    (UpdateAccepted) => {
+        "update/accepted"
+    };
+    (UpdateRejected) => {
+        "update/rejected"
+    };
+    (UpdateDocuments) => {
+        "update/documents"
+    };
+    (UpdateDelta) => {
+        "update/delta"
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __jobs_op {
+    (JobsChanged) => {
+        "notify"
+    };
+    (NextJobChanged) => {
+        "notify-next"
+    };
+    (GetPending) => {
+        "get"
+    };
+    (GetPendingSuccess) => {
+        "get/accepted"
+    };
+    (GetPendingFailed) => {
+        "get/rejected"
+    };
+    (StartNext) => {
+        "start-next"
+    };
+    (StartNextSuccess) => {
+        "start-next/accepted"
+    };
+    (StartNextFailed) => {
+        "start-next/rejected"
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __jobs_id_op {
+    (Describe) => {
+        "get"
+    };
+    (DescribeSuccess) => {
+        "get/accepted"
+    };
+    (DescribeFailed) => {
+        "get/rejected"
+    };
+    (Update) => {
+        "update"
+    };
+    (UpdateSuccess) => {
+        "update/accepted"
+    };
+    (UpdateFailed) => {
+        "update/rejected"
+    };
+}
+
+// `$next` stands for the next pending job in the describe topics only.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __jobs_id_next {
+    (Describe) => {
+        true
+    };
+    (DescribeSuccess) => {
+        true
+    };
+    (DescribeFailed) => {
+        true
+    };
+    (Update) => {
+        false
+    };
+    (UpdateSuccess) => {
+        false
+    };
+    (UpdateFailed) => {
+        false
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{jobs, shadow};
+    #[test]
+    fn shadow_topic_matches_assemble_topic() {
+        use shadow::Topic::*;
+        let cases = [
+            (shadow_topic!("chloe", Get), Get),
+            (shadow_topic!("chloe", DeleteRejected), DeleteRejected),
+            (shadow_topic!("chloe", UpdateDocuments), UpdateDocuments),
+        ];
+        for (topic, api) in cases {
+            assert_eq!(
+                topic,
+                &shadow::assemble_topic(api, "chloe", None).unwrap()[..]
+            );
+        }
+        assert_eq!(
+            shadow_topic!("chloe", "$package", UpdateDelta),
+            &shadow::assemble_topic(UpdateDelta, "chloe", Some("$package")).unwrap()[..]
+        );
+    }
+    #[test]
+    fn jobs_topic_matches_assemble_topic() {
+        assert_eq!(
+            jobs_topic!("chloe", StartNextFailed),
+            &jobs::assemble_topic("chloe", jobs::Topic::StartNextFailed).unwrap()[..]
+        );
+        assert_eq!(
+            jobs_topic!("chloe", "$next", Describe),
+            &jobs::describe("chloe", "$next").unwrap()[..]
+        );
+        assert_eq!(
+            jobs_topic!("chloe", "ota-1", UpdateFailed),
+            &jobs::assemble_job_topic("chloe", "ota-1", jobs::Topic::UpdateFailed).unwrap()[..]
+        );
+    }
+}
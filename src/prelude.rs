@@ -0,0 +1,20 @@
+//! A convenience module that brings the common types into scope, with the
+//! per-module topic enums renamed so they don't clash.
+//!
+//! # Example
+//! ```
+//! use aws_iot_device_sdk::prelude::*;
+//!
+//! let topic = shadow::assemble_topic(ShadowTopic::Get, "chloe", None).unwrap();
+//! assert_eq!(match_topic_type(&topic), Ok(TopicType::Shadow));
+//!
+//! let topic = jobs::assemble_topic("chloe", JobsTopic::NextJobChanged).unwrap();
+//! assert_eq!(jobs::match_topic(&topic).unwrap().api, JobsTopic::NextJobChanged);
+//! ```
+pub use crate::backoff_algo::{BackoffAlgorithm, RetryPolicy};
+pub use crate::common::{Error, TopicBuilder, TopicConfig, TopicMatcher};
+pub use crate::defender::Topic as DefenderTopic;
+pub use crate::jobs::Topic as JobsTopic;
+pub use crate::shadow::Topic as ShadowTopic;
+pub use crate::{defender, jobs, shadow, tunneling};
+pub use crate::{match_topic_type, match_topic_type_bytes, TopicType};
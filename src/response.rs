@@ -0,0 +1,189 @@
+use crate::common::*;
+use crate::{defender, jobs, shadow};
+
+/// A response message of any subsystem, with its matched topic and its
+/// parsed payload.
+///
+/// Accepted payloads are parsed as `T`, the type the caller expects for the
+/// request it made. The defender payloads are left as raw bytes, since
+/// they may be CBOR.
+#[derive(Debug, PartialEq)]
+pub enum Response<'a, T> {
+    ShadowAccepted(shadow::ThingShadow<'a>, T),
+    ShadowRejected(shadow::ThingShadow<'a>, shadow::ServiceError<'a>),
+    JobAccepted(jobs::ThingJobs<'a>, T),
+    JobRejected(jobs::ThingJobs<'a>, jobs::JobUpdateError<'a>),
+    DefenderAccepted(defender::ThingDefender<'a>, &'a [u8]),
+    DefenderRejected(defender::ThingDefender<'a>, &'a [u8]),
+}
+
+/// Match the topic of an incoming message, and parse its payload with the
+/// accepted or rejected parser of its subsystem.
+///
+/// Topics which are not responses, like requests or `update/delta` and
+/// `notify` messages, fail with `Error::NoMatch`; they have parsers of
+/// their own.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{parse_response, Response};
+///
+/// let topic = "$aws/things/chloe/shadow/update/rejected";
+/// let payload = br#"{"code":409,"message":"Version conflict"}"#;
+/// match parse_response::<serde_json::Value>(topic, payload).unwrap() {
+///     Response::ShadowRejected(shadow, error) => {
+///         assert_eq!(shadow.thing_name, "chloe");
+///         assert!(error.is_version_conflict());
+///     }
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn parse_response<'a, T: serde::Deserialize<'a>>(
+    topic: &'a str,
+    payload: &'a [u8],
+) -> Result<Response<'a, T>, Error> {
+    let thing = ThingTopic::parse(topic)?;
+    match subsystem_segment(topic)? {
+        "shadow" => {
+            use shadow::Topic::*;
+            let shadow = thing.as_shadow()?;
+            match shadow.shadow_op {
+                GetAccepted | DeleteAccepted | UpdateAccepted => {
+                    Ok(Response::ShadowAccepted(shadow, parse_json(payload)?))
+                }
+                GetRejected | DeleteRejected | UpdateRejected => {
+                    let error = shadow::parse_rejected(payload)?;
+                    Ok(Response::ShadowRejected(shadow, error))
+                }
+                _ => Err(Error::NoMatch),
+            }
+        }
+        "jobs" => {
+            use jobs::Topic::*;
+            let jobs = thing.as_jobs()?;
+            match jobs.api {
+                GetPendingSuccess | StartNextSuccess | DescribeSuccess | UpdateSuccess => {
+                    Ok(Response::JobAccepted(jobs, parse_json(payload)?))
+                }
+                GetPendingFailed | StartNextFailed | DescribeFailed | UpdateFailed => {
+                    let error = jobs::parse_rejected(payload)?;
+                    Ok(Response::JobRejected(jobs, error))
+                }
+                _ => Err(Error::NoMatch),
+            }
+        }
+        "defender" => {
+            use defender::Topic::*;
+            let defender = thing.as_defender()?;
+            match defender.api {
+                JsonReportAccepted | CborReportAccepted => {
+                    Ok(Response::DefenderAccepted(defender, payload))
+                }
+                JsonReportRejected | CborReportRejected => {
+                    Ok(Response::DefenderRejected(defender, payload))
+                }
+                _ => Err(Error::NoMatch),
+            }
+        }
+        _ => Err(Error::NoMatch),
+    }
+}
+
+/// Same as [`parse_response`], for an MQTT5 message, handing back its
+/// properties along with the response so that both can be dispatched
+/// together.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{response, MessageContext, Response};
+///
+/// let context = MessageContext { correlation_data: Some(b"token-1"), ..Default::default() };
+/// let topic = "$aws/things/chloe/shadow/delete/accepted";
+/// let payload = br#"{"version":3,"timestamp":1469529572}"#;
+/// let (response, context) =
+///     response::parse_with_context::<serde_json::Value>(topic, payload, context).unwrap();
+/// assert!(matches!(response, Response::ShadowAccepted(..)));
+/// assert!(context.correlates(payload, "token-1"));
+/// ```
+pub fn parse_with_context<'a, T: serde::Deserialize<'a>>(
+    topic: &'a str,
+    payload: &'a [u8],
+    context: MessageContext<'a>,
+) -> Result<(Response<'a, T>, MessageContext<'a>), Error> {
+    Ok((parse_response(topic, payload)?, context))
+}
+
+fn parse_json<'a, T: serde::Deserialize<'a>>(payload: &'a [u8]) -> Result<T, Error> {
+    serde_json::from_slice(payload).map_err(|_| Error::PayloadParseFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use crate::response::{parse_response, Response};
+    use crate::{jobs, shadow};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn shadow_get_accepted() {
+        let topic = "$aws/things/chloe/shadow/name/common/get/accepted";
+        let payload =
+            br#"{"state":{"reported":{"color":"red"}},"version":3,"timestamp":1469529572}"#;
+        match parse_response::<Value>(topic, payload).unwrap() {
+            Response::ShadowAccepted(shadow, document) => {
+                assert_eq!(shadow.shadow_name, Some("common"));
+                assert_eq!(shadow.shadow_op, shadow::Topic::GetAccepted);
+                assert_eq!(document["state"]["reported"], json!({"color": "red"}));
+            }
+            response => panic!("{response:?}"),
+        }
+    }
+    #[test]
+    fn jobs_update_rejected() {
+        let topic = "$aws/things/chloe/jobs/example-job-01/update/rejected";
+        let payload = br#"{"code":"InvalidStateTransition","message":"Job is canceled","timestamp":1489096425,"executionState":{"status":"CANCELED","versionNumber":3}}"#;
+        match parse_response::<Value>(topic, payload).unwrap() {
+            Response::JobRejected(jobs, error) => {
+                assert_eq!(jobs.api, jobs::Topic::UpdateFailed);
+                assert_eq!(&jobs.id.unwrap()[..], "example-job-01");
+                assert!(error.is_invalid_transition());
+            }
+            response => panic!("{response:?}"),
+        }
+    }
+    #[test]
+    fn parse_with_context() {
+        let properties = [("trace-id", "42")];
+        let context = MessageContext {
+            user_properties: &properties,
+            ..Default::default()
+        };
+        let topic = "$aws/things/chloe/jobs/$next/get/accepted";
+        let payload = br#"{"clientToken":"token-1","timestamp":1489096425}"#;
+        let (response, context) =
+            crate::response::parse_with_context::<Value>(topic, payload, context).unwrap();
+        assert!(matches!(response, Response::JobAccepted(..)));
+        assert_eq!(context.user_property("trace-id"), Some("42"));
+        assert!(context.correlates(payload, "token-1"));
+    }
+    #[test]
+    fn not_a_response() {
+        for topic in [
+            "$aws/things/chloe/shadow/update",
+            "$aws/things/chloe/shadow/update/delta",
+            "$aws/things/chloe/jobs/notify",
+            "$aws/things/chloe/defender/metrics/json",
+            "$aws/things/chloe/tunnels/notify",
+        ] {
+            assert_eq!(
+                parse_response::<Value>(topic, b"{}"),
+                Err(Error::NoMatch),
+                "{topic}"
+            );
+        }
+        assert_eq!(
+            parse_response::<Value>("$aws/things/chloe/shadow/get/accepted", b"{"),
+            Err(Error::PayloadParseFailed)
+        );
+    }
+}
@@ -8,11 +8,13 @@ const OP_DELETE: &str = "delete";
 const OP_UPDATE: &str = "update";
 const SUFFIX_DOCUMENTS: &str = "/documents";
 const SUFFIX_DELTA: &str = "/delta";
+const NAME: &str = "name";
+const VERSION: &str = "version";
 /// A shadow topic string takes one of the two forms,
 /// in the case of an unnamed ("Classic") shadow.
 /// Or, in the case of a named shadow
 /// The shadow_name part is None when unnamed shadow.
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct ThingShadow<'a> {
     pub thing_name: &'a str,
     pub shadow_name: Option<&'a str>,
@@ -21,7 +23,7 @@ pub struct ThingShadow<'a> {
 
 /// Each of these values describes the type of a shadow message.
 /// https://docs.aws.amazon.com/iot/latest/developerguide/device-shadow-mqtt.html
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Topic {
     Get = 0,
     GetAccepted,
@@ -36,6 +38,95 @@ pub enum Topic {
     UpdateDelta,
 }
 
+/// Every shadow topic, in declaration order.
+pub const ALL_TOPICS: &[Topic] = &[
+    Get,
+    GetAccepted,
+    GetRejected,
+    Delete,
+    DeleteAccepted,
+    DeleteRejected,
+    Update,
+    UpdateAccepted,
+    UpdateRejected,
+    UpdateDocuments,
+    UpdateDelta,
+];
+
+impl Topic {
+    /// Whether the device publishes the topic, receives it as a response to
+    /// its request, or receives it unprompted.
+    pub fn direction(&self) -> TopicDirection {
+        match self {
+            Get | Delete | Update => TopicDirection::Request,
+            UpdateDocuments | UpdateDelta => TopicDirection::Notification,
+            _ => TopicDirection::Response,
+        }
+    }
+    /// The canonical name of the topic, i.e. the operation and suffix
+    /// levels of the topic string, like `"update/delta"`.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::shadow;
+    ///
+    /// assert_eq!(shadow::Topic::UpdateDelta.as_str(), "update/delta");
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Get => "get",
+            GetAccepted => "get/accepted",
+            GetRejected => "get/rejected",
+            Delete => "delete",
+            DeleteAccepted => "delete/accepted",
+            DeleteRejected => "delete/rejected",
+            Update => "update",
+            UpdateAccepted => "update/accepted",
+            UpdateRejected => "update/rejected",
+            UpdateDocuments => "update/documents",
+            UpdateDelta => "update/delta",
+        }
+    }
+}
+
+fn from_name(name: &str) -> Result<Topic, Error> {
+    match name.split_once('/') {
+        Some((op, suffix)) => find_message_type(op, Some(suffix)),
+        None => find_message_type(name, None),
+    }
+}
+
+/// The inverse of [`Topic::as_str`].
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let topic = shadow::Topic::UpdateDelta;
+/// assert_eq!(shadow::Topic::try_from(topic.as_str()), Ok(topic));
+/// ```
+impl TryFrom<&str> for Topic {
+    type Error = Error;
+
+    fn try_from(name: &str) -> Result<Self, Error> {
+        from_name(name)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Topic {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Topic {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_name(deserializer, from_name)
+    }
+}
+
 /// Assemble shadow topic string when Thing Name or Shadow Name is only known at run time.
 ///
 /// # Example
@@ -47,37 +138,655 @@ pub enum Topic {
 /// let topic = shadow::assemble_topic(shadow::Topic::Get, "chloe", None).unwrap();
 /// assert_eq!("$aws/things/chloe/shadow/get", topic.as_str())
 /// ```
-
+#[must_use = "this returns the topic without publishing or subscribing to it"]
 pub fn assemble_topic(
     topic_type: Topic,
     thing_name: &str,
     named: Option<&str>,
+) -> Result<ArrayString<SHADOW_TOPIC_MAX_LENGTH>, Error> {
+    assemble_topic_with(&TopicConfig::default(), topic_type, thing_name, named)
+}
+
+/// Same as [`assemble_topic`], with the thing prefix of `config`.
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_topic_with(
+    config: &TopicConfig,
+    topic_type: Topic,
+    thing_name: &str,
+    named: Option<&str>,
+) -> Result<ArrayString<SHADOW_TOPIC_MAX_LENGTH>, Error> {
+    assemble_into(config, topic_type, thing_name, named)
+}
+
+/// Same as [`assemble_topic`], into a topic of capacity `N` rather than
+/// `SHADOW_TOPIC_MAX_LENGTH`, for callers which know their names are short.
+///
+/// A topic longer than `N` fails with `Error::FAIL`; [`topic_len`] gives
+/// the length needed.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{shadow, Error};
+///
+/// let topic = shadow::assemble_topic_into::<40>(shadow::Topic::UpdateDelta, "chloe", None).unwrap();
+/// assert_eq!(topic.as_str(), "$aws/things/chloe/shadow/update/delta");
+///
+/// let topic = shadow::assemble_topic_into::<16>(shadow::Topic::UpdateDelta, "chloe", None);
+/// assert_eq!(topic, Err(Error::FAIL));
+/// ```
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_topic_into<const N: usize>(
+    topic_type: Topic,
+    thing_name: &str,
+    named: Option<&str>,
+) -> Result<ArrayString<N>, Error> {
+    assemble_into(&TopicConfig::default(), topic_type, thing_name, named)
+}
+
+/// Same as [`assemble_topic`], into a `String`.
+///
+/// The names are still validated, so this only lifts the limit on the
+/// capacity: the topic of [`assemble_topic_string_with`] can have a thing
+/// prefix of any length up to the MQTT limit.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let topic = shadow::assemble_topic_string(shadow::Topic::UpdateDelta, "chloe", Some("common")).unwrap();
+/// assert_eq!(topic, "$aws/things/chloe/shadow/name/common/update/delta");
+/// ```
+#[cfg(feature = "std")]
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_topic_string(
+    topic_type: Topic,
+    thing_name: &str,
+    named: Option<&str>,
+) -> Result<String, Error> {
+    assemble_into(&TopicConfig::default(), topic_type, thing_name, named)
+}
+
+/// Same as [`assemble_topic_with`], into a `String`, failing with
+/// `Error::MqttTopicTooLong` past `MQTT_TOPIC_LENGTH_MAX`.
+#[cfg(feature = "std")]
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_topic_string_with(
+    config: &TopicConfig,
+    topic_type: Topic,
+    thing_name: &str,
+    named: Option<&str>,
+) -> Result<String, Error> {
+    let topic: String = assemble_into(config, topic_type, thing_name, named)?;
+    is_valid_mqtt_topic(&topic)?;
+    Ok(topic)
+}
+
+fn assemble_into<B: TopicBuf>(
+    config: &TopicConfig,
+    topic_type: Topic,
+    thing_name: &str,
+    named: Option<&str>,
+) -> Result<B, Error> {
+    let mut s = assemble_root(config, thing_name, named)?;
+    push_str(&mut s, op(&topic_type))?;
+    push_str(&mut s, suffix(&topic_type))?;
+    Ok(s)
+}
+
+/// The length [`assemble_topic`] gives the topic, without building it.
+///
+/// The names are not validated.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let len = shadow::topic_len(shadow::Topic::UpdateDelta, "chloe", Some("common"));
+/// assert_eq!(len, "$aws/things/chloe/shadow/name/common/update/delta".len());
+/// ```
+pub fn topic_len(topic_type: Topic, thing_name: &str, named: Option<&str>) -> usize {
+    let root = match named {
+        None => SHADOW_API_BRIDGE.len(),
+        Some(shadow_name) => NAMED_SHADOW_API_BRIDGE.len() + shadow_name.len() + 1,
+    };
+    AWS_THINGS_PREFIX.len()
+        + thing_name.len()
+        + root
+        + op(&topic_type).len()
+        + suffix(&topic_type).len()
+}
+
+/// Populate the payload of a `get` request, `{}` or, with a client token,
+/// `{"clientToken":"..."}`.
+///
+/// The shadow service always returns the whole document; a `get` request
+/// can't select fields, and ignores anything but the client token.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// assert_eq!(&shadow::get_payload(None).unwrap()[..], "{}");
+/// let payload = shadow::get_payload(Some("token-1")).unwrap();
+/// assert_eq!(&payload[..], r#"{"clientToken":"token-1"}"#);
+/// ```
+pub fn get_payload(
+    client_token: Option<&str>,
+) -> Result<ArrayString<TOKEN_PAYLOAD_MAX_LENGTH>, Error> {
+    token_payload(client_token)
+}
+
+/// Populate both the topic and the payload of a `get` request.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let (topic, payload) = shadow::get_request("chloe", None, Some("token-1")).unwrap();
+/// assert_eq!(&topic[..], "$aws/things/chloe/shadow/get");
+/// assert_eq!(&payload[..], r#"{"clientToken":"token-1"}"#);
+/// ```
+#[must_use = "this returns the request without publishing it"]
+pub fn get_request(
+    thing_name: &str,
+    named: Option<&str>,
+    client_token: Option<&str>,
+) -> Result<
+    (
+        ArrayString<SHADOW_TOPIC_MAX_LENGTH>,
+        ArrayString<TOKEN_PAYLOAD_MAX_LENGTH>,
+    ),
+    Error,
+> {
+    let topic = assemble_topic(Get, thing_name, named)?;
+    Ok((topic, get_payload(client_token)?))
+}
+
+/// Populate the payload of a `delete` request, `{}` or, with a client token,
+/// `{"clientToken":"..."}`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// assert_eq!(&shadow::delete_payload(None).unwrap()[..], "{}");
+/// let payload = shadow::delete_payload(Some("token-1")).unwrap();
+/// assert_eq!(&payload[..], r#"{"clientToken":"token-1"}"#);
+/// ```
+pub fn delete_payload(
+    client_token: Option<&str>,
+) -> Result<ArrayString<TOKEN_PAYLOAD_MAX_LENGTH>, Error> {
+    token_payload(client_token)
+}
+
+/// Populate both the topic and the payload of a `delete` request.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let (topic, payload) = shadow::delete_request("chloe", Some("common"), None).unwrap();
+/// assert_eq!(&topic[..], "$aws/things/chloe/shadow/name/common/delete");
+/// assert_eq!(&payload[..], "{}");
+/// ```
+#[must_use = "this returns the request without publishing it"]
+pub fn delete_request(
+    thing_name: &str,
+    named: Option<&str>,
+    client_token: Option<&str>,
+) -> Result<
+    (
+        ArrayString<SHADOW_TOPIC_MAX_LENGTH>,
+        ArrayString<TOKEN_PAYLOAD_MAX_LENGTH>,
+    ),
+    Error,
+> {
+    let topic = assemble_topic(Delete, thing_name, named)?;
+    Ok((topic, delete_payload(client_token)?))
+}
+
+/// Populate the topic and the empty payload of a `get` request for each of
+/// the given shadows, `None` being the classic shadow, e.g. to fetch every
+/// shadow of a device at bring-up.
+///
+/// Fails with `Error::FAIL` when there are more than `K` shadows.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let requests = shadow::batch_get::<4>("chloe", &[None, Some("common")]).unwrap();
+/// assert_eq!(&requests[0].0[..], "$aws/things/chloe/shadow/get");
+/// assert_eq!(&requests[1].0[..], "$aws/things/chloe/shadow/name/common/get");
+/// assert_eq!(&requests[1].1[..], "{}");
+/// ```
+#[must_use = "this returns the requests without publishing them"]
+pub fn batch_get<const K: usize>(
+    thing_name: &str,
+    shadow_names: &[Option<&str>],
+) -> Result<
+    ArrayVec<
+        (
+            ArrayString<SHADOW_TOPIC_MAX_LENGTH>,
+            ArrayString<TOKEN_PAYLOAD_MAX_LENGTH>,
+        ),
+        K,
+    >,
+    Error,
+> {
+    if shadow_names.len() > K {
+        return Err(Error::FAIL);
+    }
+    let mut requests = ArrayVec::new();
+    for &named in shadow_names {
+        let topic = assemble_topic(Get, thing_name, named)?;
+        requests.push((topic, token_payload(None)?));
+    }
+    Ok(requests)
+}
+
+/// The steps to fetch a shadow, in the order they must run. See
+/// [`sync_plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncPlan {
+    /// Subscribe to these first, `get/accepted` then `get/rejected`.
+    pub subscribe_filters: [ArrayString<SHADOW_TOPIC_MAX_LENGTH>; 2],
+    /// Publish this `get` once both subscriptions are acknowledged.
+    pub publish_topic: ArrayString<SHADOW_TOPIC_MAX_LENGTH>,
+    pub publish_payload: ArrayString<TOKEN_PAYLOAD_MAX_LENGTH>,
+}
+
+/// Plan the fetch of a shadow, e.g. at start-up to sync with the cloud.
+///
+/// The shadow service answers a `get` once, right away, and doesn't keep the
+/// response for a client that subscribes later. So the device must
+/// subscribe to the responses, and wait for the SUBACK, before it publishes
+/// the `get`, or the response may be lost.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let plan = shadow::sync_plan("chloe", None).unwrap();
+/// assert_eq!(&plan.subscribe_filters[0][..], "$aws/things/chloe/shadow/get/accepted");
+/// assert_eq!(&plan.subscribe_filters[1][..], "$aws/things/chloe/shadow/get/rejected");
+/// assert_eq!(&plan.publish_topic[..], "$aws/things/chloe/shadow/get");
+/// assert_eq!(&plan.publish_payload[..], "{}");
+/// ```
+#[must_use = "this returns the plan without running it"]
+pub fn sync_plan(thing_name: &str, named: Option<&str>) -> Result<SyncPlan, Error> {
+    let publish_topic = assemble_topic(Get, thing_name, named)?;
+    let mut accepted = publish_topic;
+    let mut rejected = publish_topic;
+    push_str(&mut accepted, SUFFIX_ACCEPTED)?;
+    push_str(&mut rejected, SUFFIX_REJECTED)?;
+    Ok(SyncPlan {
+        subscribe_filters: [accepted, rejected],
+        publish_topic,
+        publish_payload: token_payload(None)?,
+    })
+}
+
+/// Assemble a subscription filter matching the given operation of every
+/// named shadow of a thing, with a `+` wildcard as the shadow name.
+///
+/// MQTT doesn't allow wildcards in the topic of a publish, so this can only
+/// be subscribed to. To fetch several named shadows, publish a `get` for
+/// each shadow name and receive the responses on this filter.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let topic = shadow::assemble_any_named(shadow::Topic::GetAccepted, "chloe").unwrap();
+/// assert_eq!(topic.as_str(), "$aws/things/chloe/shadow/name/+/get/accepted");
+/// ```
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_any_named(
+    topic_type: Topic,
+    thing_name: &str,
+) -> Result<ArrayString<SHADOW_TOPIC_MAX_LENGTH>, Error> {
+    assemble_any_named_with(&TopicConfig::default(), topic_type, thing_name)
+}
+
+/// Same as [`assemble_any_named`], with the thing prefix of `config`.
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn assemble_any_named_with(
+    config: &TopicConfig,
+    topic_type: Topic,
+    thing_name: &str,
 ) -> Result<ArrayString<SHADOW_TOPIC_MAX_LENGTH>, Error> {
     is_valid_thing_name(thing_name)?;
     let mut s = ArrayString::<SHADOW_TOPIC_MAX_LENGTH>::new();
-    s.push_str(AWS_THINGS_PREFIX);
-    s.push_str(thing_name);
+    push_str(&mut s, config.things_prefix)?;
+    push_str(&mut s, thing_name)?;
+    push_str(&mut s, NAMED_SHADOW_API_BRIDGE)?;
+    push_str(&mut s, "+/")?;
+    push_str(&mut s, op(&topic_type))?;
+    push_str(&mut s, suffix(&topic_type))?;
+    Ok(s)
+}
+
+/// Assemble the subscription filter for both the accepted and rejected
+/// `get` responses of every named shadow of a thing.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let topic = shadow::get_any_named_filter("chloe").unwrap();
+/// assert_eq!(topic.as_str(), "$aws/things/chloe/shadow/name/+/get/+");
+/// ```
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn get_any_named_filter(
+    thing_name: &str,
+) -> Result<ArrayString<SHADOW_TOPIC_MAX_LENGTH>, Error> {
+    get_any_named_filter_with(&TopicConfig::default(), thing_name)
+}
+
+/// Same as [`get_any_named_filter`], with the thing prefix of `config`.
+#[must_use = "this returns the topic without publishing or subscribing to it"]
+pub fn get_any_named_filter_with(
+    config: &TopicConfig,
+    thing_name: &str,
+) -> Result<ArrayString<SHADOW_TOPIC_MAX_LENGTH>, Error> {
+    let mut s = assemble_any_named_with(config, Get, thing_name)?;
+    push_str(&mut s, SUFFIX_WILDCARD)?;
+    Ok(s)
+}
+
+/// Everything up to the shadow operation, e.g. `$aws/things/chloe/shadow/`
+/// or `$aws/things/chloe/shadow/name/common/`.
+fn assemble_root<B: TopicBuf>(
+    config: &TopicConfig,
+    thing_name: &str,
+    named: Option<&str>,
+) -> Result<B, Error> {
+    is_valid_thing_name(thing_name)?;
+    let mut s = B::default();
+    push_str(&mut s, config.things_prefix)?;
+    push_str(&mut s, thing_name)?;
     match named {
         // Classic shadow topic
         None => {
-            s.push_str(SHADOW_API_BRIDGE);
-            s.push_str(op(&topic_type));
-            s.push_str(suffix(&topic_type));
+            push_str(&mut s, SHADOW_API_BRIDGE)?;
             Ok(s)
         }
         // Named shadow topic
         Some(shadow_name) => {
             is_valid_shadow_name(shadow_name)?;
-            s.push_str(NAMED_SHADOW_API_BRIDGE);
-            s.push_str(shadow_name);
-            s.push_str("/");
-            s.push_str(op(&topic_type));
-            s.push_str(suffix(&topic_type));
+            push_str(&mut s, NAMED_SHADOW_API_BRIDGE)?;
+            push_str(&mut s, shadow_name)?;
+            push_str(&mut s, "/")?;
             Ok(s)
         }
     }
 }
 
+/// Builds the classic shadow topics; named shadows need
+/// [`assemble_topic`] with the shadow name.
+impl TopicBuilder for Topic {
+    type Output = ArrayString<SHADOW_TOPIC_MAX_LENGTH>;
+
+    fn topic(&self, thing_name: &str) -> Result<Self::Output, Error> {
+        let mut s = assemble_root(&TopicConfig::default(), thing_name, None)?;
+        push_str(&mut s, op(self))?;
+        push_str(&mut s, suffix(self))?;
+        Ok(s)
+    }
+}
+
+impl<'a> TopicMatcher<'a> for ThingShadow<'a> {
+    fn match_topic(topic: &'a str) -> Result<Self, Error> {
+        match_topic(topic)
+    }
+}
+
+/// A summary for logs, like `shadow(thing=chloe, name=common, op=UpdateDelta)`.
+impl core::fmt::Display for ThingShadow<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "shadow(thing={}", self.thing_name)?;
+        if let Some(shadow_name) = self.shadow_name {
+            write!(f, ", name={shadow_name}")?;
+        }
+        write!(f, ", op={:?})", self.shadow_op)
+    }
+}
+
+impl From<&ThingShadow<'_>> for crate::TopicType {
+    fn from(shadow: &ThingShadow<'_>) -> Self {
+        shadow.subsystem()
+    }
+}
+
+/// The topics and payloads of one device shadow, with the thing name and
+/// the shadow name validated once at construction.
+///
+/// The topic methods can't fail once the client is built: validated names
+/// are short enough for every topic to fit. Only the payload methods, which
+/// take a client token, still return a `Result`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow::ShadowClient;
+///
+/// let client = ShadowClient::new("chloe", Some("common")).unwrap();
+/// assert_eq!(client.update_topic().as_str(), "$aws/things/chloe/shadow/name/common/update");
+/// let filters = client.subscribe_filters();
+/// assert_eq!(filters[1].as_str(), "$aws/things/chloe/shadow/name/common/update/+");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowClient<'a> {
+    thing_name: &'a str,
+    shadow_name: Option<&'a str>,
+    root: ArrayString<SHADOW_TOPIC_MAX_LENGTH>,
+}
+
+// The longest root followed by the longest operation fits in a topic, so
+// the ShadowClient topics never overflow.
+const _: () = assert!(
+    AWS_THINGS_PREFIX.len()
+        + THINGNAME_MAX_LENGTH
+        + NAMED_SHADOW_API_BRIDGE.len()
+        + SHADOW_NAME_LENGTH_MAX
+        + "/update/documents".len()
+        <= SHADOW_TOPIC_MAX_LENGTH
+);
+
+impl<'a> ShadowClient<'a> {
+    /// Validates the names, `None` being the classic shadow.
+    pub fn new(thing_name: &'a str, shadow_name: Option<&'a str>) -> Result<Self, Error> {
+        Self::new_with(&TopicConfig::default(), thing_name, shadow_name)
+    }
+    /// Same as [`ShadowClient::new`], with the thing prefix of `config`.
+    /// Fails with `Error::FAIL` if the prefix leaves no room for the
+    /// longest topic, since the topic methods can't fail.
+    pub fn new_with(
+        config: &TopicConfig,
+        thing_name: &'a str,
+        shadow_name: Option<&'a str>,
+    ) -> Result<Self, Error> {
+        let root: ArrayString<SHADOW_TOPIC_MAX_LENGTH> =
+            assemble_root(config, thing_name, shadow_name)?;
+        if root.remaining_capacity() < "update/documents".len() {
+            return Err(Error::FAIL);
+        }
+        Ok(ShadowClient {
+            thing_name,
+            shadow_name,
+            root,
+        })
+    }
+    pub fn thing_name(&self) -> &'a str {
+        self.thing_name
+    }
+    pub fn shadow_name(&self) -> Option<&'a str> {
+        self.shadow_name
+    }
+    /// Same as [`assemble_topic`] for this shadow.
+    #[must_use = "this returns the topic without publishing or subscribing to it"]
+    pub fn topic(&self, topic_type: Topic) -> ArrayString<SHADOW_TOPIC_MAX_LENGTH> {
+        let mut s = self.root;
+        s.push_str(op(&topic_type));
+        s.push_str(suffix(&topic_type));
+        s
+    }
+    #[must_use = "this returns the topic without publishing or subscribing to it"]
+    pub fn get_topic(&self) -> ArrayString<SHADOW_TOPIC_MAX_LENGTH> {
+        self.topic(Get)
+    }
+    #[must_use = "this returns the topic without publishing or subscribing to it"]
+    pub fn update_topic(&self) -> ArrayString<SHADOW_TOPIC_MAX_LENGTH> {
+        self.topic(Update)
+    }
+    #[must_use = "this returns the topic without publishing or subscribing to it"]
+    pub fn delete_topic(&self) -> ArrayString<SHADOW_TOPIC_MAX_LENGTH> {
+        self.topic(Delete)
+    }
+    /// The filters for the responses of `get`, `update` and `delete`, in
+    /// that order. `update/+` also delivers `delta` and `documents`.
+    #[must_use = "this returns the topics without subscribing to them"]
+    pub fn subscribe_filters(&self) -> [ArrayString<SHADOW_TOPIC_MAX_LENGTH>; 3] {
+        let mut filters = [self.root; 3];
+        for (filter, op) in filters.iter_mut().zip([OP_GET, OP_UPDATE, OP_DELETE]) {
+            filter.push_str(op);
+            filter.push_str(SUFFIX_WILDCARD);
+        }
+        filters
+    }
+    /// Same as [`get_payload`].
+    pub fn get_payload(
+        &self,
+        client_token: Option<&str>,
+    ) -> Result<ArrayString<TOKEN_PAYLOAD_MAX_LENGTH>, Error> {
+        get_payload(client_token)
+    }
+    /// Same as [`delete_payload`].
+    pub fn delete_payload(
+        &self,
+        client_token: Option<&str>,
+    ) -> Result<ArrayString<TOKEN_PAYLOAD_MAX_LENGTH>, Error> {
+        delete_payload(client_token)
+    }
+}
+
+/// The reserved named shadow of the software package catalog, which holds
+/// the versions of the packages installed on the device.
+pub const PACKAGE_SHADOW_NAME: &str = "$package";
+/// The maximum length of a software package name.
+pub const PACKAGE_NAME_MAX_LENGTH: usize = 128;
+/// The maximum length of a software package version name.
+pub const PACKAGE_VERSION_MAX_LENGTH: usize = 64;
+/// The maximum length of a [`report_installed_version`] payload.
+pub const PACKAGE_PAYLOAD_MAX_LENGTH: usize =
+    PACKAGE_NAME_MAX_LENGTH + PACKAGE_VERSION_MAX_LENGTH + 42;
+
+/// A [`ShadowClient`] for the `$package` shadow of a thing.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let client = shadow::package_shadow("chloe").unwrap();
+/// assert_eq!(client.update_topic().as_str(), "$aws/things/chloe/shadow/name/$package/update");
+/// ```
+pub fn package_shadow(thing_name: &str) -> Result<ShadowClient<'_>, Error> {
+    ShadowClient::new(thing_name, Some(PACKAGE_SHADOW_NAME))
+}
+
+/// Populate the `update` payload reporting `version` as the installed
+/// version of `package`, for the `$package` shadow.
+///
+/// Names follow the AWS pattern `[a-zA-Z0-9._-]+`, with at most
+/// `PACKAGE_NAME_MAX_LENGTH` and `PACKAGE_VERSION_MAX_LENGTH` bytes, and
+/// fail with `Error::FAIL` otherwise.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let payload = shadow::report_installed_version("firmware", "1.2.0").unwrap();
+/// assert_eq!(
+///     &payload[..],
+///     r#"{"state":{"reported":{"firmware":{"version":"1.2.0"}}}}"#
+/// );
+/// ```
+pub fn report_installed_version(
+    package: &str,
+    version: &str,
+) -> Result<ArrayString<PACKAGE_PAYLOAD_MAX_LENGTH>, Error> {
+    is_valid_package_part(package, PACKAGE_NAME_MAX_LENGTH)?;
+    is_valid_package_part(version, PACKAGE_VERSION_MAX_LENGTH)?;
+    let mut s = ArrayString::new();
+    push_str(&mut s, r#"{"state":{"reported":{""#)?;
+    push_str(&mut s, package)?;
+    push_str(&mut s, r#"":{"version":""#)?;
+    push_str(&mut s, version)?;
+    push_str(&mut s, r#""}}}}"#)?;
+    Ok(s)
+}
+
+fn is_valid_package_part(s: &str, max_len: usize) -> Result<(), Error> {
+    let valid = |b: &u8| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-');
+    if s.is_empty() || s.len() > max_len || !s.as_bytes().iter().all(valid) {
+        return Err(Error::FAIL);
+    }
+    Ok(())
+}
+
+impl ThingShadow<'_> {
+    /// The coarse topic type, as [`match_topic_type`](crate::match_topic_type)
+    /// would give it.
+    pub fn subsystem(&self) -> crate::TopicType {
+        match self.shadow_name {
+            Some(_) => crate::TopicType::NamedShadow,
+            None => crate::TopicType::Shadow,
+        }
+    }
+    /// Whether the topic is for a shadow name reserved by AWS IoT, i.e.
+    /// starting with `$`, like `$package`.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::shadow;
+    ///
+    /// let shadow = shadow::match_topic("$aws/things/chloe/shadow/name/$package/get/accepted").unwrap();
+    /// assert!(shadow.is_reserved());
+    /// ```
+    pub fn is_reserved(&self) -> bool {
+        self.shadow_name.is_some_and(|name| name.starts_with('$'))
+    }
+    /// Rebuild the subscription filter the matched topic belongs to, i.e.
+    /// the operation with its suffix replaced by a `+` wildcard.
+    /// Topics without a suffix map back to themselves.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::{shadow};
+    ///
+    /// let shadow = shadow::match_topic("$aws/things/chloe/shadow/update/delta").unwrap();
+    /// let filter = shadow.to_filter().unwrap();
+    /// assert_eq!(filter.as_str(), "$aws/things/chloe/shadow/update/+");
+    /// ```
+    #[must_use = "this returns the topic without publishing or subscribing to it"]
+    pub fn to_filter(&self) -> Result<ArrayString<SHADOW_TOPIC_MAX_LENGTH>, Error> {
+        self.to_filter_with(&TopicConfig::default())
+    }
+    /// Same as [`ThingShadow::to_filter`], with the thing prefix of `config`.
+    #[must_use = "this returns the topic without publishing or subscribing to it"]
+    pub fn to_filter_with(
+        &self,
+        config: &TopicConfig,
+    ) -> Result<ArrayString<SHADOW_TOPIC_MAX_LENGTH>, Error> {
+        let mut s = assemble_root(config, self.thing_name, self.shadow_name)?;
+        push_str(&mut s, op(&self.shadow_op))?;
+        if !suffix(&self.shadow_op).is_empty() {
+            push_str(&mut s, SUFFIX_WILDCARD)?;
+        }
+        Ok(s)
+    }
+}
+
 fn op(topic_type: &Topic) -> &str {
     match topic_type {
         Get | GetAccepted | GetRejected => OP_GET,
@@ -120,54 +829,478 @@ fn suffix(topic_type: &Topic) -> &str {
 /// assert_eq!(shadow.shadow_name.unwrap(), "common");
 /// assert_eq!(shadow.shadow_op, shadow::Topic::UpdateDelta);
 /// ```
-pub fn match_topic<'a>(topic: &'a str) -> Result<ThingShadow, Error> {
-    is_valid_mqtt_topic(topic)?;
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic(topic: &str) -> Result<ThingShadow<'_>, Error> {
+    match_topic_with(&TopicConfig::default(), topic)
+}
 
-    let s = is_valid_prefix(topic, AWS_THINGS_PREFIX)?;
+/// Same as [`match_topic`], with the thing prefix of `config`.
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_with<'a>(
+    config: &TopicConfig,
+    topic: &'a str,
+) -> Result<ThingShadow<'a>, Error> {
+    let result = parse_thing_prefix_with(config, topic)
+        .and_then(|(thing_name, s)| match_rest(thing_name, s));
+    log_match("shadow", topic, result)
+}
 
-    let mid = s.find('/').ok_or(Error::NoMatch);
-    let (thing_name, s) = s.split_at(mid?);
-    is_valid_thing_name(thing_name)?;
+/// Match the rest of a topic, following the thing name, see [`ThingTopic`].
+pub(crate) fn match_rest<'a>(thing_name: &'a str, s: &'a str) -> Result<ThingShadow<'a>, Error> {
+    let (shadow_name, op, suffix) = split_rest(s)?;
+    Ok(ThingShadow {
+        thing_name,
+        shadow_name,
+        shadow_op: find_message_type(op, suffix)?,
+    })
+}
+
+/// Split the rest of a shadow topic into its shadow name, operation and
+/// suffix, without checking the operation and the suffix.
+fn split_rest(s: &str) -> Result<(Option<&str>, &str, Option<&str>), Error> {
+    is_valid_levels(s)?;
 
     let s = is_valid_bridge(s, SHADOW_API_BRIDGE)?;
 
-    let v: ArrayVec<&str, 16> = s.split('/').collect();
+    let v = collect_levels::<16>(s)?;
     match v[..] {
         // Named shadow topic
-        [_named, shadow_name, op, suffix] => {
+        [NAME, shadow_name, op, suffix] => {
             is_valid_shadow_name(shadow_name)?;
-            Ok(ThingShadow {
-                thing_name,
-                shadow_name: Some(shadow_name),
-                shadow_op: find_message_type(op, Some(suffix))?,
-            })
+            Ok((Some(shadow_name), op, Some(suffix)))
         }
         // Named shadow topic without suffix
-        [_named, shadow_name, op] => {
+        [NAME, shadow_name, op] => {
             is_valid_shadow_name(shadow_name)?;
-            Ok(ThingShadow {
-                thing_name,
-                shadow_name: Some(shadow_name),
-                shadow_op: find_message_type(op, None)?,
-            })
+            Ok((Some(shadow_name), op, None))
         }
+        // "name" level with no shadow name or no operation after it
+        [NAME] | [NAME, _] => Err(Error::ShadownameParseFailed),
         // Classic shadow topic
-        [op, suffix] => Ok(ThingShadow {
+        [op, suffix] => Ok((None, op, Some(suffix))),
+        // Classic shadow topic without suffix
+        [op] => Ok((None, op, None)),
+        // Not shadow topic
+        _ => Err(Error::NoMatch),
+    }
+}
+
+/// A shadow topic matched by [`match_topic_lenient`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShadowMatch<'a> {
+    /// A topic this crate knows, as [`match_topic`] returns it.
+    Known(ThingShadow<'a>),
+    /// A well-formed shadow topic with an operation or a suffix this crate
+    /// doesn't know, e.g. one added to the service after this release.
+    Unknown(UnknownTopic<'a>),
+}
+
+/// The levels of a shadow topic whose operation or suffix isn't known.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownTopic<'a> {
+    pub thing_name: &'a str,
+    pub shadow_name: Option<&'a str>,
+    pub op: &'a str,
+    pub suffix: Option<&'a str>,
+}
+
+/// Same as [`match_topic`], but a well-formed shadow topic with an unknown
+/// operation or suffix is returned as `ShadowMatch::Unknown` rather than
+/// failing with `Error::MessageTypeParseFailed`, so that a device can log
+/// and skip messages added to the service after its release.
+///
+/// Topics with invalid names or the wrong number of levels still fail as
+/// with [`match_topic`].
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow::{self, ShadowMatch};
+///
+/// match shadow::match_topic_lenient("$aws/things/chloe/shadow/update/future-suffix").unwrap() {
+///     ShadowMatch::Unknown(topic) => {
+///         assert_eq!(topic.op, "update");
+///         assert_eq!(topic.suffix, Some("future-suffix"));
+///     }
+///     ShadowMatch::Known(_) => unreachable!(),
+/// }
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_lenient(topic: &str) -> Result<ShadowMatch<'_>, Error> {
+    log_match("shadow", topic, match_lenient(topic))
+}
+
+fn match_lenient(topic: &str) -> Result<ShadowMatch<'_>, Error> {
+    let (thing_name, rest) = parse_thing_prefix(topic)?;
+    let (shadow_name, op, suffix) = split_rest(rest)?;
+    let shadow = match find_message_type(op, suffix) {
+        Ok(shadow_op) => ShadowMatch::Known(ThingShadow {
             thing_name,
-            shadow_name: None,
-            shadow_op: find_message_type(op, Some(suffix))?,
+            shadow_name,
+            shadow_op,
         }),
-        // Classic shadow topic without suffix
-        [op] => Ok(ThingShadow {
+        Err(_) => ShadowMatch::Unknown(UnknownTopic {
             thing_name,
-            shadow_name: None,
-            shadow_op: find_message_type(op, None)?,
+            shadow_name,
+            op,
+            suffix,
         }),
-        // Not shadow topic
-        _ => Err(Error::NoMatch),
+    };
+    Ok(shadow)
+}
+
+/// Same as [`match_topic`], for a subscription filter of a backend which
+/// handles every thing, with `+` as the thing name. The thing name is then
+/// `"+"`. Only the thing name may be a wildcard.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let filter = shadow::match_filter("$aws/things/+/shadow/update/delta").unwrap();
+/// assert_eq!(filter.thing_name, "+");
+/// assert_eq!(filter.shadow_op, shadow::Topic::UpdateDelta);
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_filter(filter: &str) -> Result<ThingShadow<'_>, Error> {
+    let result =
+        parse_thing_filter(filter).and_then(|(thing_name, rest)| match_rest(thing_name, rest));
+    log_match("shadow", filter, result)
+}
+
+/// Same as [`match_topic`], for topics handed over as raw bytes by the MQTT
+/// client.
+///
+/// A topic which is not valid UTF-8 is rejected with `Error::MqttTopicFailed`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let shadow = shadow::match_topic_bytes(b"$aws/things/chloe/shadow/get/accepted").unwrap();
+/// assert_eq!(shadow.shadow_op, shadow::Topic::GetAccepted);
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_bytes(topic: &[u8]) -> Result<ThingShadow<'_>, Error> {
+    match topic_from_bytes(topic) {
+        Ok(topic) => match_topic(topic),
+        Err(e) => log_match("shadow", topic, Err(e)),
     }
 }
 
+/// Check that `topic` is a valid shadow topic, matching it fully as
+/// [`match_topic`] does, e.g. to filter incoming messages. Unlike
+/// [`match_topic_type`](crate::match_topic_type), which only looks at the
+/// subsystem, the names and the levels after it are validated as well.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{shadow, Error};
+///
+/// assert_eq!(shadow::validate_topic("$aws/things/chloe/shadow/name/common/update/delta"), Ok(()));
+/// assert_eq!(shadow::validate_topic("$aws/things/chloe/shadow/update/deltas"), Err(Error::MessageTypeParseFailed));
+/// ```
+pub fn validate_topic(topic: &str) -> Result<(), Error> {
+    match_topic(topic).map(|_| ())
+}
+
+/// Check whether `topic` is the given shadow topic, of the classic or of a
+/// named shadow, without matching it fully.
+///
+/// This is a fast path for message loops which only handle a few topics.
+/// Only the levels are checked, the names are not validated as
+/// [`match_topic`] does.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let topic = "$aws/things/chloe/shadow/name/common/update/documents";
+/// assert!(shadow::is_topic(topic, shadow::Topic::UpdateDocuments));
+/// assert!(!shadow::is_topic(topic, shadow::Topic::Update));
+/// ```
+pub fn is_topic(topic: &str, topic_type: Topic) -> bool {
+    let root = topic
+        .strip_prefix(AWS_THINGS_PREFIX)
+        .and_then(|s| s.strip_suffix(topic_type.as_str()))
+        .and_then(|s| s.strip_suffix('/'));
+    let Some(root) = root else {
+        return false;
+    };
+    // <thingName>/shadow or <thingName>/shadow/name/<shadowName>
+    match root.find('/') {
+        Some(mid) if mid > 0 => {
+            let bridge = &root[mid..];
+            match bridge.strip_prefix(NAMED_SHADOW_API_BRIDGE) {
+                Some(shadow_name) => !shadow_name.is_empty() && !shadow_name.contains('/'),
+                None => bridge == &SHADOW_API_BRIDGE[..SHADOW_API_BRIDGE.len() - 1],
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Check whether `topic` is an `update/delta` topic, see [`is_topic`].
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// assert!(shadow::is_delta_topic("$aws/things/chloe/shadow/update/delta"));
+/// assert!(!shadow::is_delta_topic("$aws/things/chloe/shadow/update/documents"));
+/// ```
+pub fn is_delta_topic(topic: &str) -> bool {
+    is_topic(topic, UpdateDelta)
+}
+
+/// Check whether `topic` is an `update/rejected` topic, see [`is_topic`].
+pub fn is_update_rejected(topic: &str) -> bool {
+    is_topic(topic, UpdateRejected)
+}
+
+/// Check whether `topic` is a `get/accepted` topic, see [`is_topic`].
+pub fn is_get_accepted(topic: &str) -> bool {
+    is_topic(topic, GetAccepted)
+}
+
+/// The shadow name in a shadow topic, `None` for the classic shadow,
+/// without matching the operation as [`match_topic`] does. This is enough
+/// to route messages by shadow.
+///
+/// Topics of other subsystems fail with `Error::NoMatch`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let topic = "$aws/things/chloe/shadow/name/common/update/accepted";
+/// assert_eq!(shadow::shadow_name_of(topic), Ok(Some("common")));
+/// assert_eq!(shadow::shadow_name_of("$aws/things/chloe/shadow/get"), Ok(None));
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn shadow_name_of(topic: &str) -> Result<Option<&str>, Error> {
+    let (_, rest) = parse_thing_prefix(topic)?;
+    let Some(rest) = rest.strip_prefix(NAMED_SHADOW_API_BRIDGE) else {
+        return match rest.strip_prefix(SHADOW_API_BRIDGE) {
+            // A `name` level with nothing after it.
+            Some(NAME) => Err(Error::ShadownameParseFailed),
+            Some(op) if !op.is_empty() => Ok(None),
+            _ => Err(Error::NoMatch),
+        };
+    };
+    // An operation has to follow the name.
+    match rest.split_once('/') {
+        Some((shadow_name, op)) if !op.is_empty() => {
+            is_valid_shadow_name(shadow_name)?;
+            Ok(Some(shadow_name))
+        }
+        _ => Err(Error::ShadownameParseFailed),
+    }
+}
+
+/// The top-level `version` of a shadow response or document, found by
+/// scanning the payload rather than parsing it, e.g. to drop a stale delta
+/// without deserializing it.
+///
+/// `None` if the payload has no `version` or isn't a JSON object.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let payload = br#"{"state":{"color":"green","version":1},"metadata":{},"version":10,"timestamp":1469529572}"#;
+/// assert_eq!(shadow::extract_version(payload), Some(10));
+/// assert_eq!(shadow::extract_version(br#"{"state":{}}"#), None);
+/// ```
+pub fn extract_version(payload: &[u8]) -> Option<u64> {
+    let value = json_field(payload, VERSION).ok()??;
+    if !value.first()?.is_ascii_digit() {
+        return None;
+    }
+    core::str::from_utf8(value).ok()?.parse().ok()
+}
+
+/// The top-level `clientToken` of a shadow response, found by scanning the
+/// payload rather than parsing it.
+///
+/// `None` if the payload has no `clientToken`, if it's not a string, or if
+/// it holds JSON escapes.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let payload = br#"{"state":{"reported":{"color":"red"}},"clientToken":"token-1","version":2}"#;
+/// assert_eq!(shadow::extract_client_token(payload), Some("token-1"));
+/// ```
+pub fn extract_client_token(payload: &[u8]) -> Option<&str> {
+    json_str_field(payload, CLIENT_TOKEN)
+}
+
+/// The state part of a shadow document.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub struct State<T> {
+    pub desired: Option<T>,
+    pub reported: Option<T>,
+}
+
+/// A full shadow document, as found in an `update/documents` message.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub struct StateDocument<T> {
+    pub state: State<T>,
+    pub version: u64,
+}
+
+/// The payload of an `update/documents` message, published by AWS IoT after
+/// every successful update of the shadow.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub struct DocumentsMessage<T> {
+    /// The shadow before the update; `None` for the first update of a
+    /// shadow, which has nothing before it.
+    pub previous: Option<StateDocument<T>>,
+    /// The shadow after the update.
+    pub current: StateDocument<T>,
+    /// When the message was generated, in seconds since the epoch.
+    pub timestamp: u64,
+}
+
+/// Parse the payload of an `update/documents` message, with `T` as the
+/// type of the desired and reported states.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let payload = br#"{
+///     "previous": null,
+///     "current": {"state": {"reported": {"color": "red"}}, "version": 1},
+///     "timestamp": 1469529572
+/// }"#;
+/// let documents = shadow::parse_documents::<serde_json::Value>(payload).unwrap();
+/// assert!(documents.previous.is_none());
+/// assert_eq!(documents.current.version, 1);
+/// ```
+#[cfg(feature = "serde")]
+pub fn parse_documents<'a, T: serde::Deserialize<'a>>(
+    payload: &'a [u8],
+) -> Result<DocumentsMessage<T>, Error> {
+    serde_json::from_slice(payload).map_err(|_| Error::PayloadParseFailed)
+}
+
+/// Apply `delta` to a local copy of a shadow state the way the shadow
+/// service merges an update: objects merge key by key, a `null` deletes the
+/// key, and any other value, arrays included, replaces the previous one.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+/// use serde_json::json;
+///
+/// let mut desired = json!({"color": "red", "lights": {"on": true, "level": 3}});
+/// shadow::merge_state(&mut desired, &json!({"color": null, "lights": {"level": 5}}));
+/// assert_eq!(desired, json!({"lights": {"on": true, "level": 5}}));
+/// ```
+#[cfg(feature = "serde")]
+pub fn merge_state(base: &mut serde_json::Value, delta: &serde_json::Value) {
+    use serde_json::Value;
+
+    match (base, delta) {
+        (Value::Object(base), Value::Object(delta)) => {
+            for (key, value) in delta {
+                if value.is_null() {
+                    base.remove(key);
+                } else {
+                    merge_state(base.entry(key.clone()).or_insert(Value::Null), value);
+                }
+            }
+        }
+        // A new object is merged into an empty one, so that its nulls are
+        // dropped rather than stored.
+        (base, Value::Object(_)) => {
+            *base = Value::Object(serde_json::Map::new());
+            merge_state(base, delta);
+        }
+        (base, delta) => *base = delta.clone(),
+    }
+}
+
+/// The payload of a `get/rejected`, `update/rejected` or `delete/rejected`
+/// message.
+///
+/// The shadow service answers with these codes:
+///
+/// | Code | Meaning                                                    |
+/// |------|------------------------------------------------------------|
+/// | 400  | Bad request, e.g. invalid JSON or a missing `state` node   |
+/// | 401  | Unauthorized                                               |
+/// | 403  | Forbidden                                                  |
+/// | 404  | The thing or the shadow was not found                      |
+/// | 409  | Version conflict, the shadow was updated in the meantime   |
+/// | 413  | The payload exceeds the maximum size                       |
+/// | 415  | Unsupported document encoding                              |
+/// | 429  | Too many requests in flight for the shadow                 |
+/// | 500  | Internal service failure                                   |
+///
+/// The strings are borrowed from the payload, so a message or token with
+/// JSON escapes in it fails to parse.
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, serde::Deserialize)]
+pub struct ServiceError<'a> {
+    pub code: u16,
+    pub message: &'a str,
+    #[serde(rename = "clientToken")]
+    pub client_token: Option<&'a str>,
+    pub timestamp: Option<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl ServiceError<'_> {
+    /// The update carried a `version` older than the shadow's; fetch the
+    /// shadow again and retry the update against the new version.
+    pub fn is_version_conflict(&self) -> bool {
+        self.code == 409
+    }
+    /// The shadow doesn't exist, e.g. on a `get` before the first update.
+    pub fn is_not_found(&self) -> bool {
+        self.code == 404
+    }
+    /// The code as a [`ServiceErrorCode`], `None` for an undocumented code.
+    ///
+    /// # Example
+    /// ```
+    /// use aws_iot_device_sdk::shadow;
+    ///
+    /// let error = shadow::parse_rejected(br#"{"code":429,"message":"Too Many Requests"}"#).unwrap();
+    /// assert!(error.error_code().is_some_and(|code| code.retryable()));
+    /// ```
+    pub fn error_code(&self) -> Option<ServiceErrorCode> {
+        ServiceErrorCode::from_u32(self.code.into())
+    }
+}
+
+/// The shadow rejected response, under the name used by the AWS
+/// documentation; `client_token` and `timestamp` are `None` when absent.
+#[cfg(feature = "serde")]
+pub type RejectedResponse<'a> = ServiceError<'a>;
+
+/// Parse the payload of a rejected shadow response.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::shadow;
+///
+/// let payload = br#"{"code":409,"message":"Version conflict","clientToken":"token-1","timestamp":1469529572}"#;
+/// let error = shadow::parse_rejected(payload).unwrap();
+/// assert!(error.is_version_conflict());
+/// assert_eq!(error.client_token, Some("token-1"));
+/// ```
+#[cfg(feature = "serde")]
+pub fn parse_rejected(payload: &[u8]) -> Result<ServiceError<'_>, Error> {
+    serde_json::from_slice(payload).map_err(|_| Error::PayloadParseFailed)
+}
+
 fn find_message_type(op: &str, suffix: Option<&str>) -> Result<Topic, Error> {
     match (op, suffix) {
         ("get", None) => Ok(Get),
@@ -187,8 +1320,20 @@ fn find_message_type(op: &str, suffix: Option<&str>) -> Result<Topic, Error> {
 
 #[cfg(test)]
 mod tests {
+    use crate::common::*;
     use crate::shadow;
     #[test]
+    fn shadow_match_topic_bytes() {
+        let topic = "$aws/things/chloe/shadow/name/common/update/delta";
+        let shadow = shadow::match_topic_bytes(topic.as_bytes()).unwrap();
+        assert_eq!(shadow.thing_name, "chloe");
+        assert_eq!(shadow.shadow_name, Some("common"));
+        assert_eq!(shadow.shadow_op, shadow::Topic::UpdateDelta);
+
+        let shadow = shadow::match_topic_bytes(b"$aws/things/chl\xffoe/shadow/get");
+        assert_eq!(shadow.err(), Some(Error::MqttTopicFailed));
+    }
+    #[test]
     fn assemble_named_topic_string() {
         let topic = shadow::assemble_topic(shadow::Topic::Get, "chloe", Some("common")).unwrap();
         assert_eq!("$aws/things/chloe/shadow/name/common/get", topic.as_str());
@@ -204,6 +1349,42 @@ mod tests {
         assert_eq!("$aws/things/chloe/shadow/get/accepted", topic.as_str());
     }
     #[test]
+    fn assemble_any_named_keeps_wildcard() {
+        let topic = shadow::assemble_any_named(shadow::Topic::GetRejected, "chloe").unwrap();
+        assert_eq!(
+            topic.as_str(),
+            "$aws/things/chloe/shadow/name/+/get/rejected"
+        );
+        let topic = shadow::get_any_named_filter("chloe").unwrap();
+        assert_eq!(topic.as_str(), "$aws/things/chloe/shadow/name/+/get/+");
+        let res = shadow::get_any_named_filter("+");
+        assert_eq!(res, Err(Error::ThingnameParseFailed));
+    }
+    #[test]
+    fn assemble_topic_rejects_slash_in_names() {
+        let res = shadow::assemble_topic(shadow::Topic::Get, "chloe/get", None);
+        assert_eq!(res, Err(Error::ThingnameParseFailed));
+        let res = shadow::assemble_topic(shadow::Topic::Get, "chloe", Some("common/get"));
+        assert_eq!(res, Err(Error::ShadownameParseFailed));
+    }
+    #[test]
+    fn assemble_topic_max_length_names() {
+        let thing_name = "t".repeat(THINGNAME_MAX_LENGTH);
+        let shadow_name = "s".repeat(SHADOW_NAME_LENGTH_MAX);
+        let topic = shadow::assemble_topic(
+            shadow::Topic::UpdateAccepted,
+            &thing_name,
+            Some(&shadow_name),
+        )
+        .unwrap();
+        assert!(topic.ends_with("/update/accepted"));
+        let shadow = shadow::match_topic(&topic).unwrap();
+        assert_eq!(
+            shadow.to_filter().unwrap().len(),
+            topic.len() - "accepted".len() + 1
+        );
+    }
+    #[test]
     fn match_classic_shadow_topic_string() {
         let topic = "$aws/things/chloe/shadow/get/accepted";
         let shadow = shadow::match_topic(topic).unwrap();
@@ -219,4 +1400,714 @@ mod tests {
         assert_eq!(shadow.shadow_name.unwrap(), "common");
         assert_eq!(shadow.shadow_op, shadow::Topic::GetRejected);
     }
+    #[test]
+    fn classic_shadow_to_filter() {
+        let shadow = shadow::match_topic("$aws/things/chloe/shadow/get/accepted").unwrap();
+        let filter = shadow.to_filter().unwrap();
+        assert_eq!(filter.as_str(), "$aws/things/chloe/shadow/get/+");
+        let shadow = shadow::match_topic("$aws/things/chloe/shadow/update").unwrap();
+        let filter = shadow.to_filter().unwrap();
+        assert_eq!(filter.as_str(), "$aws/things/chloe/shadow/update");
+    }
+    #[test]
+    fn named_shadow_to_filter() {
+        let topic = "$aws/things/chloe/shadow/name/common/update/documents";
+        let shadow = shadow::match_topic(topic).unwrap();
+        let filter = shadow.to_filter().unwrap();
+        assert_eq!(
+            filter.as_str(),
+            "$aws/things/chloe/shadow/name/common/update/+"
+        );
+    }
+    #[test]
+    fn topic_name_round_trip() {
+        for &topic in shadow::ALL_TOPICS {
+            assert_eq!(shadow::Topic::try_from(topic.as_str()), Ok(topic));
+        }
+        assert_eq!(
+            shadow::Topic::try_from(""),
+            Err(Error::MessageTypeParseFailed)
+        );
+        assert_eq!(
+            shadow::Topic::try_from("foo/accepted"),
+            Err(Error::MessageTypeParseFailed)
+        );
+        let topic = shadow::Topic::UpdateDelta;
+        assert_eq!(shadow::from_name(topic.as_str()), Ok(topic));
+        assert_eq!(shadow::from_name("get"), Ok(shadow::Topic::Get));
+        assert_eq!(
+            shadow::from_name("update/future"),
+            Err(Error::MessageTypeParseFailed)
+        );
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn topic_serde() {
+        let json = serde_json::to_string(&shadow::Topic::UpdateDelta).unwrap();
+        assert_eq!(json, "\"update/delta\"");
+        let topic: shadow::Topic = serde_json::from_str(&json).unwrap();
+        assert_eq!(topic, shadow::Topic::UpdateDelta);
+        assert!(serde_json::from_str::<shadow::Topic>("\"update/future\"").is_err());
+    }
+    #[cfg(feature = "serde")]
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Light {
+        color: String,
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_documents() {
+        let payload = br#"{
+            "previous": {
+                "state": {
+                    "desired": {"color": "red"},
+                    "reported": {"color": "red"}
+                },
+                "metadata": {
+                    "desired": {"color": {"timestamp": 12345}},
+                    "reported": {"color": {"timestamp": 12345}}
+                },
+                "version": 1
+            },
+            "current": {
+                "state": {
+                    "desired": {"color": "blue"},
+                    "reported": {"color": "red"}
+                },
+                "metadata": {
+                    "desired": {"color": {"timestamp": 12346}},
+                    "reported": {"color": {"timestamp": 12345}}
+                },
+                "version": 2
+            },
+            "timestamp": 1469529572
+        }"#;
+        let documents = shadow::parse_documents::<Light>(payload).unwrap();
+        let previous = documents.previous.unwrap();
+        assert_eq!(previous.version, 1);
+        assert_eq!(previous.state.desired.unwrap().color, "red");
+        assert_eq!(documents.current.version, 2);
+        assert_eq!(documents.current.state.desired.unwrap().color, "blue");
+        assert_eq!(documents.current.state.reported.unwrap().color, "red");
+        assert_eq!(documents.timestamp, 1469529572);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_first_documents() {
+        let payload = br#"{
+            "previous": null,
+            "current": {"state": {"desired": {"color": "blue"}}, "version": 1},
+            "timestamp": 1469529572
+        }"#;
+        let documents = shadow::parse_documents::<Light>(payload).unwrap();
+        assert_eq!(documents.previous, None);
+        assert_eq!(documents.current.state.reported, None);
+        let payload = br#"{"current": {"state": {}, "version": 1}, "timestamp": 1}"#;
+        let documents = shadow::parse_documents::<Light>(payload).unwrap();
+        assert_eq!(documents.previous, None);
+        assert_eq!(
+            shadow::parse_documents::<Light>(b"{}"),
+            Err(Error::PayloadParseFailed)
+        );
+    }
+    #[test]
+    fn match_named_shadow_topic_without_shadow_name() {
+        let topic = "$aws/things/chloe/shadow/name";
+        let err = shadow::match_topic(topic).unwrap_err();
+        assert_eq!(err, Error::ShadownameParseFailed);
+    }
+    #[test]
+    fn match_named_shadow_topic_without_op() {
+        let topic = "$aws/things/chloe/shadow/name/foo";
+        let err = shadow::match_topic(topic).unwrap_err();
+        assert_eq!(err, Error::ShadownameParseFailed);
+    }
+    #[test]
+    fn match_classic_shadow_topic_unknown_op() {
+        let topic = "$aws/things/chloe/shadow/foo/accepted";
+        let err = shadow::match_topic(topic).unwrap_err();
+        assert_eq!(err, Error::MessageTypeParseFailed);
+        let topic = "$aws/things/chloe/shadow/foo/get/accepted";
+        let err = shadow::match_topic(topic).unwrap_err();
+        assert_eq!(err, Error::NoMatch);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_rejected() {
+        let payload = br#"{"code":404,"message":"No shadow exists with name: 'chloe'"}"#;
+        let error = shadow::parse_rejected(payload).unwrap();
+        assert!(error.is_not_found());
+        assert!(!error.is_version_conflict());
+        assert_eq!(error.client_token, None);
+
+        let payload = br#"{"code":409,"message":"Version conflict","timestamp":1469529572}"#;
+        let error = shadow::parse_rejected(payload).unwrap();
+        assert!(error.is_version_conflict());
+        assert_eq!(error.timestamp, Some(1469529572));
+
+        let payload = br#"{"code":400,"message":"Missing required node: state"}"#;
+        let error = shadow::parse_rejected(payload).unwrap();
+        assert!(!error.is_not_found() && !error.is_version_conflict());
+
+        assert_eq!(
+            shadow::parse_rejected(br#"{"message":"no code"}"#),
+            Err(Error::PayloadParseFailed)
+        );
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parse_rejected_documented() {
+        // The error response document from the AWS IoT shadow guide.
+        let payload = br#"{
+            "code": 409,
+            "message": "Version conflict",
+            "timestamp": 1469529572,
+            "clientToken": "xyz"
+        }"#;
+        let error: shadow::RejectedResponse = shadow::parse_rejected(payload).unwrap();
+        assert_eq!(
+            error,
+            shadow::RejectedResponse {
+                code: 409,
+                message: "Version conflict",
+                client_token: Some("xyz"),
+                timestamp: Some(1469529572),
+            }
+        );
+    }
+    #[test]
+    fn compare_matches() {
+        let topic = "$aws/things/chloe/shadow/name/common/update/delta";
+        assert_eq!(shadow::match_topic(topic), shadow::match_topic(topic));
+        assert_eq!(
+            shadow::match_topic(topic).unwrap(),
+            shadow::ThingShadow {
+                thing_name: "chloe",
+                shadow_name: Some("common"),
+                shadow_op: shadow::Topic::UpdateDelta,
+            }
+        );
+    }
+    #[test]
+    fn delete() {
+        let topic = shadow::assemble_topic(shadow::Topic::Delete, "chloe", None).unwrap();
+        assert_eq!(&topic[..], "$aws/things/chloe/shadow/delete");
+        let topic = shadow::assemble_topic(shadow::Topic::DeleteAccepted, "chloe", None).unwrap();
+        assert_eq!(&topic[..], "$aws/things/chloe/shadow/delete/accepted");
+        let topic =
+            shadow::assemble_topic(shadow::Topic::DeleteRejected, "chloe", Some("common")).unwrap();
+        assert_eq!(
+            &topic[..],
+            "$aws/things/chloe/shadow/name/common/delete/rejected"
+        );
+
+        let (topic, payload) = shadow::delete_request("chloe", None, Some("token-1")).unwrap();
+        assert_eq!(&topic[..], "$aws/things/chloe/shadow/delete");
+        assert_eq!(&payload[..], r#"{"clientToken":"token-1"}"#);
+        assert_eq!(
+            shadow::delete_request("chloe", None, Some("")),
+            Err(Error::ClientTokenParseFailed)
+        );
+        assert_eq!(
+            shadow::delete_request("ch/loe", None, None),
+            Err(Error::ThingnameParseFailed)
+        );
+    }
+    #[test]
+    fn get_request() {
+        assert_eq!(&shadow::get_payload(None).unwrap()[..], "{}");
+        let (topic, payload) =
+            shadow::get_request("chloe", Some("common"), Some("token-1")).unwrap();
+        assert_eq!(&topic[..], "$aws/things/chloe/shadow/name/common/get");
+        assert_eq!(&payload[..], r#"{"clientToken":"token-1"}"#);
+        assert_eq!(
+            topic,
+            shadow::assemble_topic(shadow::Topic::Get, "chloe", Some("common")).unwrap()
+        );
+        let long = "t".repeat(CLIENT_TOKEN_MAX_LENGTH + 1);
+        assert_eq!(
+            shadow::get_payload(Some(&long)),
+            Err(Error::ClientTokenParseFailed)
+        );
+        assert_eq!(
+            shadow::get_request("chloe", Some("a/b"), None),
+            Err(Error::ShadownameParseFailed)
+        );
+    }
+    #[test]
+    fn package_shadow() {
+        let client = shadow::package_shadow("chloe").unwrap();
+        assert_eq!(client.shadow_name(), Some(shadow::PACKAGE_SHADOW_NAME));
+        assert_eq!(
+            client.update_topic().as_str(),
+            "$aws/things/chloe/shadow/name/$package/update"
+        );
+        let topic = "$aws/things/chloe/shadow/name/$package/update/accepted";
+        assert!(shadow::match_topic(topic).unwrap().is_reserved());
+        assert_eq!(
+            shadow::package_shadow("ch/loe"),
+            Err(Error::ThingnameParseFailed)
+        );
+
+        let payload = shadow::report_installed_version("samplePackage", "1.0.0-rc.1").unwrap();
+        assert_eq!(
+            &payload[..],
+            r#"{"state":{"reported":{"samplePackage":{"version":"1.0.0-rc.1"}}}}"#
+        );
+        let package = "p".repeat(shadow::PACKAGE_NAME_MAX_LENGTH);
+        let version = "v".repeat(shadow::PACKAGE_VERSION_MAX_LENGTH);
+        let payload = shadow::report_installed_version(&package, &version).unwrap();
+        assert_eq!(payload.len(), shadow::PACKAGE_PAYLOAD_MAX_LENGTH);
+
+        for (package, version) in [
+            ("", "1.0"),
+            ("firmware", ""),
+            ("fw\"", "1.0"),
+            ("firmware", "1.0 beta"),
+            (&package[..], "1.0+build"),
+        ] {
+            assert_eq!(
+                shadow::report_installed_version(package, version),
+                Err(Error::FAIL)
+            );
+        }
+        let long = "p".repeat(shadow::PACKAGE_NAME_MAX_LENGTH + 1);
+        assert_eq!(
+            shadow::report_installed_version(&long, "1.0"),
+            Err(Error::FAIL)
+        );
+    }
+    #[test]
+    fn reserved_shadow_name() {
+        let topic = "$aws/things/chloe/shadow/name/$package/get/accepted";
+        let shadow = shadow::match_topic(topic).unwrap();
+        assert_eq!(shadow.shadow_name, Some("$package"));
+        assert_eq!(shadow.shadow_op, shadow::Topic::GetAccepted);
+        assert!(shadow.is_reserved());
+        assert_eq!(
+            &shadow.to_filter().unwrap()[..],
+            "$aws/things/chloe/shadow/name/$package/get/+"
+        );
+
+        let shadow = shadow::match_topic("$aws/things/chloe/shadow/name/common/get").unwrap();
+        assert!(!shadow.is_reserved());
+        let shadow = shadow::match_topic("$aws/things/chloe/shadow/get").unwrap();
+        assert!(!shadow.is_reserved());
+
+        for name in ["$", "$$package", "pack$age"] {
+            assert_eq!(
+                shadow::assemble_topic(shadow::Topic::Get, "chloe", Some(name)),
+                Err(Error::ShadownameParseFailed)
+            );
+        }
+    }
+    #[test]
+    fn all_topics() {
+        use shadow::ALL_TOPICS;
+        // The number of variants, to bump along with the enum.
+        assert_eq!(ALL_TOPICS.len(), 11);
+        for (i, api) in ALL_TOPICS.iter().enumerate() {
+            assert_eq!(*api as usize, i);
+        }
+    }
+    #[test]
+    fn assemble_topic_into() {
+        use shadow::Topic::UpdateDelta;
+        let topic = "$aws/things/chloe/shadow/name/common/update/delta";
+        let built =
+            shadow::assemble_topic_into::<49>(UpdateDelta, "chloe", Some("common")).unwrap();
+        assert_eq!(built.as_str(), topic);
+        assert_eq!(built.capacity(), 49);
+        let built = shadow::assemble_topic_into::<64>(UpdateDelta, "chloe", Some("common"));
+        assert_eq!(built.unwrap().as_str(), topic);
+        // One byte short, and too short for even the thing prefix.
+        assert_eq!(
+            shadow::assemble_topic_into::<48>(UpdateDelta, "chloe", Some("common")),
+            Err(Error::FAIL)
+        );
+        assert_eq!(
+            shadow::assemble_topic_into::<8>(UpdateDelta, "chloe", None),
+            Err(Error::FAIL)
+        );
+        // Invalid names are still reported as such.
+        assert_eq!(
+            shadow::assemble_topic_into::<48>(UpdateDelta, "ch#loe", None),
+            Err(Error::ThingnameParseFailed)
+        );
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn string_topics() {
+        use shadow::Topic::Get;
+        for &topic_type in shadow::ALL_TOPICS {
+            for named in [None, Some("common"), Some("$package")] {
+                let topic = shadow::assemble_topic(topic_type, "chloe", named).unwrap();
+                let string = shadow::assemble_topic_string(topic_type, "chloe", named);
+                assert_eq!(string.unwrap(), &topic[..]);
+            }
+        }
+        let prefix = format!("{}/", "bridge".repeat(40));
+        let config = TopicConfig::new(&prefix).unwrap();
+        let long_name = "t".repeat(THINGNAME_MAX_LENGTH);
+        assert_eq!(
+            shadow::assemble_topic_with(&config, Get, &long_name, None),
+            Err(Error::FAIL)
+        );
+        let topic = shadow::assemble_topic_string_with(&config, Get, &long_name, None).unwrap();
+        assert_eq!(topic, format!("{prefix}{long_name}/shadow/get"));
+        let prefix = format!("{}/", "b".repeat(MQTT_TOPIC_LENGTH_MAX - 8));
+        let config = TopicConfig::new(&prefix).unwrap();
+        assert_eq!(
+            shadow::assemble_topic_string_with(&config, Get, "chloe", None),
+            Err(Error::MqttTopicTooLong)
+        );
+        assert_eq!(
+            shadow::assemble_topic_string(Get, "chloe", Some("com/mon")),
+            Err(Error::ShadownameParseFailed)
+        );
+    }
+    #[test]
+    fn match_topic_lenient() {
+        use shadow::{ShadowMatch, UnknownTopic};
+        let topic = "$aws/things/chloe/shadow/update/future-suffix";
+        assert_eq!(
+            shadow::match_topic(topic),
+            Err(Error::MessageTypeParseFailed)
+        );
+        assert_eq!(
+            shadow::match_topic_lenient(topic),
+            Ok(ShadowMatch::Unknown(UnknownTopic {
+                thing_name: "chloe",
+                shadow_name: None,
+                op: "update",
+                suffix: Some("future-suffix"),
+            }))
+        );
+        assert_eq!(
+            shadow::match_topic_lenient("$aws/things/chloe/shadow/name/common/sync"),
+            Ok(ShadowMatch::Unknown(UnknownTopic {
+                thing_name: "chloe",
+                shadow_name: Some("common"),
+                op: "sync",
+                suffix: None,
+            }))
+        );
+        let topic = "$aws/things/chloe/shadow/name/common/update/delta";
+        assert_eq!(
+            shadow::match_topic_lenient(topic),
+            Ok(ShadowMatch::Known(shadow::match_topic(topic).unwrap()))
+        );
+        // Malformed topics are still errors.
+        for (topic, err) in [
+            ("$aws/things/chloe/shadow/update/a/b", Error::NoMatch),
+            (
+                "$aws/things/chloe/shadow/name",
+                Error::ShadownameParseFailed,
+            ),
+            ("$aws/things/chloe/shadow//get", Error::MalformedTopic),
+            ("$aws/things/chloe/jobs/notify", Error::RootParseFailed),
+        ] {
+            let strict = shadow::match_topic(topic).err();
+            assert_eq!(strict, shadow::match_topic_lenient(topic).err(), "{topic}");
+            assert_eq!(shadow::match_topic_lenient(topic), Err(err), "{topic}");
+        }
+    }
+    #[test]
+    fn validate_topic() {
+        for topic in [
+            "$aws/things/chloe/shadow/get",
+            "$aws/things/chloe/shadow/update/documents",
+            "$aws/things/chloe/shadow/name/common/delete/accepted",
+            "$aws/things/chloe/shadow/name/$package/update",
+        ] {
+            assert_eq!(shadow::validate_topic(topic), Ok(()), "{topic}");
+        }
+        for (topic, err) in [
+            // The subsystem is right, the structure isn't.
+            (
+                "$aws/things/chloe/shadow/get/pending",
+                Error::MessageTypeParseFailed,
+            ),
+            (
+                "$aws/things/chloe/shadow/name/com+mon/get",
+                Error::ShadownameParseFailed,
+            ),
+            (
+                "$aws/things/chloe/shadow/name/common",
+                Error::ShadownameParseFailed,
+            ),
+            ("$aws/things/chloe/shadow/update/delta/more", Error::NoMatch),
+            ("$aws/things/chloe/jobs/notify", Error::RootParseFailed),
+            ("chloe/shadow/get", Error::NoMatch),
+        ] {
+            assert_eq!(shadow::validate_topic(topic), Err(err), "{topic}");
+        }
+        assert_eq!(
+            crate::match_topic_type("$aws/things/chloe/shadow/get/pending"),
+            Ok(crate::TopicType::Shadow)
+        );
+    }
+    #[test]
+    fn shadow_name_of() {
+        for topic in [
+            "$aws/things/chloe/shadow/name/common/update",
+            "$aws/things/chloe/shadow/name/common/get/accepted",
+            "$aws/things/chloe/shadow/name/$package/update/delta",
+        ] {
+            let name = shadow::match_topic(topic).unwrap().shadow_name;
+            assert_eq!(shadow::shadow_name_of(topic), Ok(name), "{topic}");
+            assert!(name.is_some());
+        }
+        for topic in [
+            "$aws/things/chloe/shadow/update",
+            "$aws/things/chloe/shadow/get/rejected",
+        ] {
+            assert_eq!(shadow::shadow_name_of(topic), Ok(None), "{topic}");
+        }
+        for topic in [
+            "$aws/things/chloe/jobs/notify",
+            "$aws/things/chloe/shadow",
+            "$aws/things/chloe/shadow/",
+            "$aws/things/chloe/shadows/get",
+        ] {
+            assert_eq!(
+                shadow::shadow_name_of(topic),
+                Err(Error::NoMatch),
+                "{topic}"
+            );
+        }
+        for topic in [
+            "$aws/things/chloe/shadow/name",
+            "$aws/things/chloe/shadow/name/common",
+            "$aws/things/chloe/shadow/name/common/",
+            "$aws/things/chloe/shadow/name/com#mon/get",
+        ] {
+            assert_eq!(
+                shadow::shadow_name_of(topic),
+                Err(Error::ShadownameParseFailed),
+                "{topic}"
+            );
+        }
+        assert!(shadow::shadow_name_of("chloe/shadow/get").is_err());
+    }
+    #[test]
+    fn topic_len_matches_assemble_topic() {
+        let long_name = "s".repeat(SHADOW_NAME_LENGTH_MAX);
+        for &api in shadow::ALL_TOPICS {
+            for named in [None, Some("common"), Some(&long_name[..])] {
+                let topic = shadow::assemble_topic(api, "chloe", named).unwrap();
+                assert_eq!(shadow::topic_len(api, "chloe", named), topic.len());
+            }
+        }
+    }
+    #[test]
+    fn is_topic_fast_path() {
+        for topic in [
+            "$aws/things/chloe/shadow/update/delta",
+            "$aws/things/chloe/shadow/name/common/update/delta",
+            "$aws/things/chloe/shadow/name/$package/update/delta",
+        ] {
+            assert!(shadow::is_delta_topic(topic), "{topic}");
+            assert_eq!(
+                shadow::match_topic(topic).unwrap().shadow_op,
+                shadow::Topic::UpdateDelta
+            );
+        }
+        for topic in [
+            "$aws/things/chloe/shadow/update",
+            "$aws/things/chloe/shadow/update/documents",
+            "$aws/things/chloe/shadowx/update/delta",
+            "$aws/things/chloe/jobs/update/delta",
+            "$aws/things/chloe/shadow/name/update/delta",
+            "$aws/things/chloe/shadow/name/a/b/update/delta",
+            "$aws/things//shadow/update/delta",
+            "$aws/things/shadow/update/delta",
+            "my/shadow/update/delta",
+            "update/delta",
+        ] {
+            assert!(!shadow::is_delta_topic(topic), "{topic}");
+        }
+        assert!(shadow::is_update_rejected(
+            "$aws/things/chloe/shadow/name/common/update/rejected"
+        ));
+        assert!(shadow::is_get_accepted(
+            "$aws/things/chloe/shadow/get/accepted"
+        ));
+        assert!(!shadow::is_get_accepted(
+            "$aws/things/chloe/shadow/get/rejected"
+        ));
+    }
+    #[test]
+    fn shadow_client() {
+        let client = shadow::ShadowClient::new("chloe", None).unwrap();
+        assert_eq!(client.thing_name(), "chloe");
+        assert_eq!(client.shadow_name(), None);
+        assert_eq!(client.get_topic().as_str(), "$aws/things/chloe/shadow/get");
+        assert_eq!(
+            client.delete_topic(),
+            shadow::assemble_topic(shadow::Topic::Delete, "chloe", None).unwrap()
+        );
+        let filters = client.subscribe_filters();
+        assert_eq!(filters[0].as_str(), "$aws/things/chloe/shadow/get/+");
+        assert_eq!(filters[2].as_str(), "$aws/things/chloe/shadow/delete/+");
+        assert_eq!(
+            &client.get_payload(Some("token-1")).unwrap()[..],
+            r#"{"clientToken":"token-1"}"#
+        );
+
+        let client = shadow::ShadowClient::new("chloe", Some("common")).unwrap();
+        assert_eq!(
+            client.topic(shadow::Topic::UpdateDelta).as_str(),
+            "$aws/things/chloe/shadow/name/common/update/delta"
+        );
+
+        assert_eq!(
+            shadow::ShadowClient::new("chl#oe", None),
+            Err(Error::ThingnameParseFailed)
+        );
+        assert_eq!(
+            shadow::ShadowClient::new("chloe", Some("com+mon")),
+            Err(Error::ShadownameParseFailed)
+        );
+        assert_eq!(
+            shadow::ShadowClient::new("$aws", None),
+            Err(Error::ReservedName)
+        );
+
+        // The longest valid names still give every topic.
+        let thing_name = "t".repeat(THINGNAME_MAX_LENGTH);
+        let shadow_name = "s".repeat(SHADOW_NAME_LENGTH_MAX);
+        let client = shadow::ShadowClient::new(&thing_name, Some(&shadow_name)).unwrap();
+        for &api in shadow::ALL_TOPICS {
+            let topic = shadow::assemble_topic(api, &thing_name, Some(&shadow_name)).unwrap();
+            assert_eq!(client.topic(api), topic);
+        }
+        assert!(client.subscribe_filters()[1].ends_with("/update/+"));
+    }
+    #[test]
+    fn batch_get() {
+        let requests =
+            shadow::batch_get::<3>("chloe", &[Some("common"), None, Some("$package")]).unwrap();
+        let topics: Vec<&str> = requests.iter().map(|(topic, _)| &topic[..]).collect();
+        assert_eq!(
+            topics,
+            [
+                "$aws/things/chloe/shadow/name/common/get",
+                "$aws/things/chloe/shadow/get",
+                "$aws/things/chloe/shadow/name/$package/get",
+            ]
+        );
+        assert!(requests.iter().all(|(_, payload)| &payload[..] == "{}"));
+
+        assert!(shadow::batch_get::<3>("chloe", &[]).unwrap().is_empty());
+        assert_eq!(
+            shadow::batch_get::<1>("chloe", &[None, Some("common")]),
+            Err(Error::FAIL)
+        );
+        assert_eq!(
+            shadow::batch_get::<2>("chloe", &[None, Some("com#mon")]),
+            Err(Error::ShadownameParseFailed)
+        );
+    }
+    #[test]
+    fn extract_version_and_client_token() {
+        // An update/accepted response.
+        let accepted = br#"{
+            "state": {"reported": {"color": "red", "version": 7}},
+            "metadata": {"reported": {"color": {"timestamp": 1469564492}}},
+            "version": 43,
+            "timestamp": 1469564492,
+            "clientToken": "token-1"
+        }"#;
+        assert_eq!(shadow::extract_version(accepted), Some(43));
+        assert_eq!(shadow::extract_client_token(accepted), Some("token-1"));
+
+        // A delta, the nested "version" of the state doesn't count.
+        let delta = br#"{"state":{"version":5},"metadata":{},"timestamp":1469564492}"#;
+        assert_eq!(shadow::extract_version(delta), None);
+        assert_eq!(shadow::extract_client_token(delta), None);
+
+        assert_eq!(shadow::extract_version(br#"{"version":"43"}"#), None);
+        assert_eq!(shadow::extract_version(br#"{"version":-1}"#), None);
+        assert_eq!(shadow::extract_version(br#"{"version":1.5}"#), None);
+        assert_eq!(shadow::extract_version(b"not json"), None);
+        assert_eq!(shadow::extract_client_token(br#"{"clientToken":7}"#), None);
+        assert_eq!(
+            shadow::extract_client_token(br#"{"clientToken":"a\"b"}"#),
+            None
+        );
+    }
+    #[test]
+    fn thing_shadow_display() {
+        let shadow =
+            shadow::match_topic("$aws/things/chloe/shadow/name/common/update/delta").unwrap();
+        assert_eq!(
+            shadow.to_string(),
+            "shadow(thing=chloe, name=common, op=UpdateDelta)"
+        );
+        let shadow = shadow::match_topic("$aws/things/chloe/shadow/get").unwrap();
+        assert_eq!(shadow.to_string(), "shadow(thing=chloe, op=Get)");
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn merge_state() {
+        use serde_json::json;
+        let mut state = json!({
+            "color": "red",
+            "lights": {"on": true, "level": 3, "zones": [1, 2]},
+            "fan": {"speed": 2}
+        });
+        shadow::merge_state(
+            &mut state,
+            &json!({
+                "color": null,
+                "lights": {"level": 5, "zones": [3]},
+                "fan": null,
+                "mode": {"eco": true, "timer": null}
+            }),
+        );
+        assert_eq!(
+            state,
+            json!({
+                "lights": {"on": true, "level": 5, "zones": [3]},
+                "mode": {"eco": true}
+            })
+        );
+
+        // Deleting a missing key is a no-op, a scalar replaces an object.
+        shadow::merge_state(&mut state, &json!({"missing": null, "lights": "off"}));
+        assert_eq!(state, json!({"lights": "off", "mode": {"eco": true}}));
+
+        let mut state = json!(null);
+        shadow::merge_state(&mut state, &json!({"color": "green"}));
+        assert_eq!(state, json!({"color": "green"}));
+    }
+    #[test]
+    fn thing_shadow_subsystem() {
+        use crate::TopicType;
+        let shadow = shadow::match_topic("$aws/things/chloe/shadow/name/common/get").unwrap();
+        assert_eq!(shadow.subsystem(), TopicType::NamedShadow);
+        let shadow = shadow::match_topic("$aws/things/chloe/shadow/get").unwrap();
+        assert_eq!(TopicType::from(&shadow), TopicType::Shadow);
+    }
+    #[test]
+    fn sync_plan() {
+        let plan = shadow::sync_plan("chloe", Some("common")).unwrap();
+        for (filter, api) in plan
+            .subscribe_filters
+            .iter()
+            .zip([shadow::Topic::GetAccepted, shadow::Topic::GetRejected])
+        {
+            assert_eq!(shadow::match_topic(filter).unwrap().shadow_op, api);
+        }
+        assert_eq!(
+            &plan.publish_topic[..],
+            "$aws/things/chloe/shadow/name/common/get"
+        );
+        assert_eq!(&plan.publish_payload[..], "{}");
+        assert_eq!(
+            shadow::sync_plan("chloe", Some("")).err(),
+            Some(Error::ShadownameParseFailed)
+        );
+    }
 }
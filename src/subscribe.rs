@@ -0,0 +1,184 @@
+use crate::common::*;
+use crate::{defender, jobs, shadow, TopicType};
+use arrayvec::{ArrayString, ArrayVec};
+
+/// The maximum length of a filter in a [`SubscribePlan`].
+pub const SUBSCRIBE_FILTER_MAX_LENGTH: usize = SHADOW_TOPIC_MAX_LENGTH;
+
+// Every subsystem's filters fit in a plan entry.
+const _: () = assert!(
+    JOBS_TOPIC_MAX_LENGTH <= SUBSCRIBE_FILTER_MAX_LENGTH
+        && DEFENDER_TOPIC_MAX_LENGTH <= SUBSCRIBE_FILTER_MAX_LENGTH
+);
+
+/// The MQTT quality of service of a subscription. AWS IoT Core doesn't
+/// support QoS 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QoS {
+    AtMostOnce = 0,
+    AtLeastOnce = 1,
+}
+
+/// The QoS of a level as [`recommended_qos`] gives it, failing with
+/// `Error::FAIL` for QoS 2 and above.
+impl TryFrom<u8> for QoS {
+    type Error = Error;
+
+    fn try_from(level: u8) -> Result<Self, Error> {
+        match level {
+            0 => Ok(QoS::AtMostOnce),
+            1 => Ok(QoS::AtLeastOnce),
+            _ => Err(Error::FAIL),
+        }
+    }
+}
+
+/// The subscriptions of a device across subsystems, with at most `N`
+/// filters, so that they can be made in a single SUBSCRIBE.
+///
+/// Every filter gets the QoS [`recommended_qos`] gives its subsystem:
+/// `QoS::AtLeastOnce` for the shadow and jobs, `QoS::AtMostOnce` for
+/// Device Defender.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::{PayloadFormat, QoS, SubscribePlan};
+///
+/// let mut plan = SubscribePlan::<16>::new();
+/// plan.add_shadow("chloe", None).unwrap();
+/// plan.add_jobs("chloe").unwrap();
+/// plan.add_defender("chloe", PayloadFormat::Json).unwrap();
+/// assert_eq!(plan.len(), 11);
+/// assert_eq!(&plan.as_slice()[0].0[..], "$aws/things/chloe/shadow/get/+");
+/// assert_eq!(plan.as_slice()[0].1, QoS::AtLeastOnce);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SubscribePlan<const N: usize> {
+    entries: ArrayVec<(ArrayString<SUBSCRIBE_FILTER_MAX_LENGTH>, QoS), N>,
+}
+
+impl<const N: usize> SubscribePlan<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// The shadow plan and the jobs and JSON defender plans of a thing.
+    pub fn for_device(thing_name: &str, named: Option<&str>) -> Result<Self, Error> {
+        let mut plan = Self::new();
+        plan.add_shadow(thing_name, named)?;
+        plan.add_jobs(thing_name)?;
+        plan.add_defender(thing_name, PayloadFormat::Json)?;
+        Ok(plan)
+    }
+    /// Add the `get`, `update` and `delete` filters of a shadow, see
+    /// [`ShadowClient::subscribe_filters`](shadow::ShadowClient::subscribe_filters).
+    pub fn add_shadow(&mut self, thing_name: &str, named: Option<&str>) -> Result<(), Error> {
+        let filters = shadow::ShadowClient::new(thing_name, named)?.subscribe_filters();
+        let topic_type = match named {
+            Some(_) => TopicType::NamedShadow,
+            None => TopicType::Shadow,
+        };
+        self.extend(&filters, topic_type)
+    }
+    /// Add the filters of [`jobs::response_filters`].
+    pub fn add_jobs(&mut self, thing_name: &str) -> Result<(), Error> {
+        self.extend(&jobs::response_filters(thing_name)?, TopicType::Jobs)
+    }
+    /// Add the filters of [`defender::response_topics`].
+    pub fn add_defender(&mut self, thing_name: &str, format: PayloadFormat) -> Result<(), Error> {
+        let (accepted, rejected) = defender::response_topics(thing_name, format)?;
+        self.extend(&[accepted, rejected], TopicType::Defender)
+    }
+    /// Add a filter of any other topic.
+    pub fn push(&mut self, filter: &str, qos: QoS) -> Result<(), Error> {
+        is_valid_mqtt_topic(filter)?;
+        let filter = ArrayString::from(filter).map_err(|_| Error::FAIL)?;
+        self.entries
+            .try_push((filter, qos))
+            .map_err(|_| Error::FAIL)
+    }
+    pub fn as_slice(&self) -> &[(ArrayString<SUBSCRIBE_FILTER_MAX_LENGTH>, QoS)] {
+        &self.entries
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Add all of `filters` or, when they don't all fit, none of them.
+    fn extend<const M: usize>(
+        &mut self,
+        filters: &[ArrayString<M>],
+        topic_type: TopicType,
+    ) -> Result<(), Error> {
+        if self.entries.remaining_capacity() < filters.len() {
+            return Err(Error::FAIL);
+        }
+        let qos = QoS::try_from(recommended_qos(&topic_type))?;
+        for filter in filters {
+            self.push(filter, qos)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use crate::subscribe::{QoS, SubscribePlan};
+
+    #[test]
+    fn device_bring_up() {
+        let mut plan = SubscribePlan::<12>::for_device("chloe", None).unwrap();
+        plan.push("chloe/commands/#", QoS::AtMostOnce).unwrap();
+        let filters: Vec<_> = plan.as_slice().iter().map(|(f, q)| (&f[..], *q)).collect();
+        assert_eq!(
+            filters,
+            [
+                ("$aws/things/chloe/shadow/get/+", QoS::AtLeastOnce),
+                ("$aws/things/chloe/shadow/update/+", QoS::AtLeastOnce),
+                ("$aws/things/chloe/shadow/delete/+", QoS::AtLeastOnce),
+                ("$aws/things/chloe/jobs/notify", QoS::AtLeastOnce),
+                ("$aws/things/chloe/jobs/notify-next", QoS::AtLeastOnce),
+                ("$aws/things/chloe/jobs/get/+", QoS::AtLeastOnce),
+                ("$aws/things/chloe/jobs/start-next/+", QoS::AtLeastOnce),
+                ("$aws/things/chloe/jobs/+/get/+", QoS::AtLeastOnce),
+                ("$aws/things/chloe/jobs/+/update/+", QoS::AtLeastOnce),
+                (
+                    "$aws/things/chloe/defender/metrics/json/accepted",
+                    QoS::AtMostOnce
+                ),
+                (
+                    "$aws/things/chloe/defender/metrics/json/rejected",
+                    QoS::AtMostOnce
+                ),
+                ("chloe/commands/#", QoS::AtMostOnce),
+            ]
+        );
+        assert_eq!(plan.push("chloe/more", QoS::AtMostOnce), Err(Error::FAIL));
+    }
+    #[test]
+    fn overflow_adds_nothing() {
+        let mut plan = SubscribePlan::<8>::new();
+        plan.add_shadow("chloe", Some("common")).unwrap();
+        // Six jobs filters don't fit in the five entries left.
+        assert_eq!(plan.add_jobs("chloe"), Err(Error::FAIL));
+        assert_eq!(plan.len(), 3);
+        plan.add_defender("chloe", PayloadFormat::Cbor).unwrap();
+        assert_eq!(plan.len(), 5);
+
+        assert_eq!(
+            SubscribePlan::<10>::for_device("chloe", None),
+            Err(Error::FAIL)
+        );
+        assert_eq!(
+            plan.add_shadow("ch#loe", None),
+            Err(Error::ThingnameParseFailed)
+        );
+        assert_eq!(plan.push("", QoS::AtMostOnce), Err(Error::MqttTopicFailed));
+        assert!(SubscribePlan::<1>::new().is_empty());
+        assert_eq!(QoS::try_from(1), Ok(QoS::AtLeastOnce));
+        assert_eq!(QoS::try_from(2), Err(Error::FAIL));
+    }
+}
@@ -1,6 +1,138 @@
 use crate::common::*;
+use arrayvec::ArrayString;
 
 const API_CHANGED: &str = "notify";
+const PROXY_SCHEME: &str = "wss://";
+const PROXY_ENDPOINT_PREFIX: &str = "data.tunneling.iot.";
+const PROXY_ENDPOINT_DOMAIN: &str = ".amazonaws.com";
+const PROXY_ENDPOINT_DOMAIN_CN: &str = ".amazonaws.com.cn";
+const PROXY_PATH: &str = ":443/tunnel?local-proxy-mode=";
+
+pub const REGION_MAX_LENGTH: usize = 32;
+// The host name around the longest region.
+pub const DATA_ENDPOINT_MAX_LENGTH: usize = REGION_MAX_LENGTH + 40;
+// The endpoint, path and mode around the longest region.
+pub const PROXY_URL_MAX_LENGTH: usize = REGION_MAX_LENGTH + 96;
+/// The HTTP header of the WebSocket upgrade request carrying the access token.
+pub const ACCESS_TOKEN_HEADER: &str = "access-token";
+
+/// Which end of the tunnel the local proxy runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientMode {
+    /// The end opening the connections, e.g. an operator's laptop.
+    Source,
+    /// The end forwarding them to a local service, i.e. the device.
+    Destination,
+}
+
+impl ClientMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClientMode::Source => "source",
+            ClientMode::Destination => "destination",
+        }
+    }
+}
+
+/// What the local proxy needs to connect to the tunneling service.
+///
+/// The access token goes into the [`ACCESS_TOKEN_HEADER`] header of the
+/// WebSocket upgrade request rather than into the URL, so that it doesn't
+/// leak into logs of URLs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectInfo<'a> {
+    pub url: ArrayString<PROXY_URL_MAX_LENGTH>,
+    pub access_token: &'a str,
+}
+
+/// Populate the host name of the secure tunneling proxy endpoint of a
+/// region, in the domain of its partition: `.amazonaws.com.cn` for the
+/// China regions, `.amazonaws.com` for the others, GovCloud included.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::tunneling;
+///
+/// let host = tunneling::data_endpoint("cn-northwest-1").unwrap();
+/// assert_eq!(&host[..], "data.tunneling.iot.cn-northwest-1.amazonaws.com.cn");
+/// ```
+pub fn data_endpoint(region: &str) -> Result<ArrayString<DATA_ENDPOINT_MAX_LENGTH>, Error> {
+    is_valid_region(region)?;
+    let mut s = ArrayString::<DATA_ENDPOINT_MAX_LENGTH>::new();
+    push_str(&mut s, PROXY_ENDPOINT_PREFIX)?;
+    push_str(&mut s, region)?;
+    // The China regions live in their own partition and domain.
+    if region.starts_with("cn-") {
+        push_str(&mut s, PROXY_ENDPOINT_DOMAIN_CN)?;
+    } else {
+        push_str(&mut s, PROXY_ENDPOINT_DOMAIN)?;
+    }
+    Ok(s)
+}
+
+/// Populate the URL of the secure tunneling proxy endpoint of a region,
+/// see [`data_endpoint`].
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::tunneling::{self, ClientMode};
+///
+/// let url = tunneling::proxy_url("us-east-1", ClientMode::Destination).unwrap();
+/// assert_eq!(
+///     &url[..],
+///     "wss://data.tunneling.iot.us-east-1.amazonaws.com:443/tunnel?local-proxy-mode=destination"
+/// );
+/// ```
+pub fn proxy_url(
+    region: &str,
+    mode: ClientMode,
+) -> Result<ArrayString<PROXY_URL_MAX_LENGTH>, Error> {
+    let mut s = ArrayString::<PROXY_URL_MAX_LENGTH>::new();
+    push_str(&mut s, PROXY_SCHEME)?;
+    push_str(&mut s, &data_endpoint(region)?)?;
+    push_str(&mut s, PROXY_PATH)?;
+    push_str(&mut s, mode.as_str())?;
+    Ok(s)
+}
+
+/// Gather the proxy URL and the `clientAccessToken` of a tunnel
+/// notification into what the local proxy connects with.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::tunneling::{self, ClientMode};
+///
+/// let info = tunneling::connect_info("eu-west-1", "AQGAAXi...", ClientMode::Destination).unwrap();
+/// assert!(info.url.starts_with("wss://data.tunneling.iot.eu-west-1.amazonaws.com"));
+/// assert_eq!(info.access_token, "AQGAAXi...");
+/// ```
+pub fn connect_info<'a>(
+    region: &str,
+    access_token: &'a str,
+    mode: ClientMode,
+) -> Result<ConnectInfo<'a>, Error> {
+    // The token must fit into an HTTP header value as is.
+    if access_token.is_empty() || !access_token.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(Error::FAIL);
+    }
+    Ok(ConnectInfo {
+        url: proxy_url(region, mode)?,
+        access_token,
+    })
+}
+
+/// Regions look like `us-east-1` or `us-gov-west-1`.
+fn is_valid_region(region: &str) -> Result<(), Error> {
+    if region.is_empty()
+        || region.len() > REGION_MAX_LENGTH
+        || !region
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+    {
+        return Err(Error::RegionParseFailed);
+    }
+    Ok(())
+}
 
 /// Check if the given topic is one of the Device Defender topics.
 ///
@@ -12,15 +144,21 @@ const API_CHANGED: &str = "notify";
 /// assert_eq!(tunnels, Ok(()));
 ///
 /// ```
+#[must_use = "this returns the result of the match without acting on it"]
 pub fn match_topic(topic: &str) -> Result<(), Error> {
-    // $aws/things/thing-name/tunnels/notify
-    is_valid_mqtt_topic(topic)?;
+    match_topic_with(&TopicConfig::default(), topic)
+}
 
-    let s = is_valid_prefix(topic, AWS_THINGS_PREFIX)?;
+/// Same as [`match_topic`], with the thing prefix of `config`.
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_with(config: &TopicConfig, topic: &str) -> Result<(), Error> {
+    log_match("tunneling", topic, match_notify(config, topic))
+}
 
-    let mid = s.find('/').ok_or(Error::FAIL);
-    let (thing_name, mut s) = s.split_at(mid?);
-    is_valid_thing_name(thing_name)?;
+fn match_notify(config: &TopicConfig, topic: &str) -> Result<(), Error> {
+    // $aws/things/thing-name/tunnels/notify
+    let (_, mut s) = parse_thing_prefix_with(config, topic)?;
+    is_valid_levels(s)?;
 
     s = is_valid_bridge(s, TUNNELS_API_BRIDGE)?;
 
@@ -30,12 +168,107 @@ pub fn match_topic(topic: &str) -> Result<(), Error> {
     Err(Error::NoMatch)
 }
 
+/// Same as [`match_topic`], for topics handed over as raw bytes by the MQTT
+/// client.
+///
+/// A topic which is not valid UTF-8 is rejected with `Error::MqttTopicFailed`.
+///
+/// # Example
+/// ```
+/// use aws_iot_device_sdk::tunneling;
+///
+/// let tunnels = tunneling::match_topic_bytes(b"$aws/things/chloe/tunnels/notify");
+/// assert_eq!(tunnels, Ok(()));
+/// ```
+#[must_use = "this returns the result of the match without acting on it"]
+pub fn match_topic_bytes(topic: &[u8]) -> Result<(), Error> {
+    match topic_from_bytes(topic) {
+        Ok(topic) => match_topic(topic),
+        Err(e) => log_match("tunneling", topic, Err(e)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::tunneling;
     #[test]
+    fn tunnels_match_topic_bytes() {
+        let tunnels = tunneling::match_topic_bytes(b"$aws/things/chloe/tunnels/notify");
+        assert_eq!(tunnels, Ok(()));
+
+        let tunnels = tunneling::match_topic_bytes(b"$aws/things/chloe/tunnels/\xfe");
+        assert_eq!(tunnels, Err(crate::Error::MqttTopicFailed));
+    }
+    #[test]
     fn tunnels_match_topic() {
         let tunnels = tunneling::match_topic("$aws/things/chloe/tunnels/notify");
         assert_eq!(tunnels, Ok(()));
     }
+    #[test]
+    fn data_endpoint() {
+        for (region, host) in [
+            ("us-east-1", "data.tunneling.iot.us-east-1.amazonaws.com"),
+            (
+                "eu-central-1",
+                "data.tunneling.iot.eu-central-1.amazonaws.com",
+            ),
+            (
+                "ap-southeast-2",
+                "data.tunneling.iot.ap-southeast-2.amazonaws.com",
+            ),
+            (
+                "us-gov-west-1",
+                "data.tunneling.iot.us-gov-west-1.amazonaws.com",
+            ),
+            (
+                "us-gov-east-1",
+                "data.tunneling.iot.us-gov-east-1.amazonaws.com",
+            ),
+            (
+                "cn-north-1",
+                "data.tunneling.iot.cn-north-1.amazonaws.com.cn",
+            ),
+            (
+                "cn-northwest-1",
+                "data.tunneling.iot.cn-northwest-1.amazonaws.com.cn",
+            ),
+        ] {
+            assert_eq!(&tunneling::data_endpoint(region).unwrap()[..], host);
+        }
+        let region = "cn-".repeat(tunneling::REGION_MAX_LENGTH / 3);
+        assert!(tunneling::data_endpoint(&region).is_ok());
+        assert_eq!(
+            tunneling::data_endpoint("eu west 1"),
+            Err(crate::Error::RegionParseFailed)
+        );
+    }
+    #[test]
+    fn proxy_url() {
+        use tunneling::ClientMode;
+
+        let url = tunneling::proxy_url("us-gov-west-1", ClientMode::Source).unwrap();
+        assert_eq!(
+            &url[..],
+            "wss://data.tunneling.iot.us-gov-west-1.amazonaws.com:443/tunnel?local-proxy-mode=source"
+        );
+        let url = tunneling::proxy_url("cn-north-1", ClientMode::Destination).unwrap();
+        assert_eq!(
+            &url[..],
+            "wss://data.tunneling.iot.cn-north-1.amazonaws.com.cn:443/tunnel?local-proxy-mode=destination"
+        );
+        let region = "a".repeat(tunneling::REGION_MAX_LENGTH);
+        assert!(tunneling::proxy_url(&region, ClientMode::Destination).is_ok());
+
+        let region = "a".repeat(tunneling::REGION_MAX_LENGTH + 1);
+        for region in ["", "US-EAST-1", "us-east-1.evil.com/", &region] {
+            assert_eq!(
+                tunneling::proxy_url(region, ClientMode::Source),
+                Err(crate::Error::RegionParseFailed)
+            );
+        }
+        assert_eq!(
+            tunneling::connect_info("us-east-1", "tok en", ClientMode::Source),
+            Err(crate::Error::FAIL)
+        );
+    }
 }
@@ -0,0 +1,248 @@
+//! A device session end to end: subscribe, then route the messages AWS IoT
+//! sends to the device through the matchers and parsers, the way a device
+//! firmware would.
+#![cfg(feature = "serde")]
+
+use aws_iot_device_sdk::{
+    defender, jobs, match_topic_type, shadow, Error, PayloadFormat, SubscribePlan, TopicType,
+};
+use serde_json::{json, Value};
+
+const THING: &str = "chloe";
+
+// Payloads as documented in the AWS IoT developer guide.
+const SHADOW_DELTA: &[u8] = br#"{
+    "version": 3,
+    "timestamp": 1469529572,
+    "state": {"color": "green", "lights": {"level": 5}},
+    "metadata": {
+        "color": {"timestamp": 1469529572},
+        "lights": {"level": {"timestamp": 1469529572}}
+    }
+}"#;
+const STALE_SHADOW_DELTA: &[u8] =
+    br#"{"version":2,"timestamp":1469529500,"state":{"color":"blue"},"metadata":{"color":{"timestamp":1469529500}}}"#;
+const JOB_NOTIFY_NEXT: &[u8] = br#"{
+    "timestamp": 1517016948,
+    "execution": {
+        "jobId": "ota-1",
+        "status": "QUEUED",
+        "queuedAt": 1517016947,
+        "lastUpdatedAt": 1517016947,
+        "versionNumber": 1,
+        "executionNumber": 1,
+        "jobDocument": {"operation": "install", "version": "2.1.0"}
+    }
+}"#;
+const JOB_UPDATE_ACCEPTED: &[u8] = br#"{"timestamp":1517016950,"clientToken":"token-1"}"#;
+const DEFENDER_REJECTED: &[u8] = br#"{
+    "thingName": "chloe",
+    "reportId": 1530304554,
+    "status": "REJECTED",
+    "statusDetails": {
+        "ErrorCode": "InvalidPayload",
+        "ErrorMessage": "Malformed metrics"
+    },
+    "timestamp": 1530304555
+}"#;
+
+#[derive(Debug, serde::Deserialize)]
+struct InstallJob<'a> {
+    operation: &'a str,
+    version: &'a str,
+}
+
+/// What the device knows, and the messages it would publish.
+#[derive(Default)]
+struct Device {
+    reported: Value,
+    shadow_version: u64,
+    current_job: Option<String>,
+    installing: Option<String>,
+    rejected_reports: Vec<u64>,
+    outbox: Vec<(String, String)>,
+}
+
+impl Device {
+    fn handle(&mut self, topic: &str, payload: &[u8]) -> Result<(), Error> {
+        match match_topic_type(topic)? {
+            TopicType::Shadow | TopicType::NamedShadow => self.on_shadow(topic, payload),
+            TopicType::Jobs => self.on_jobs(topic, payload),
+            TopicType::Defender => self.on_defender(topic, payload),
+            _ => Err(Error::NoMatch),
+        }
+    }
+
+    fn on_shadow(&mut self, topic: &str, payload: &[u8]) -> Result<(), Error> {
+        let matched = shadow::match_topic(topic)?;
+        if matched.shadow_op != shadow::Topic::UpdateDelta {
+            return Ok(());
+        }
+        // Drop deltas older than the state the device already has.
+        let version = shadow::extract_version(payload).ok_or(Error::PayloadParseFailed)?;
+        if version <= self.shadow_version {
+            return Ok(());
+        }
+        let delta: Value =
+            serde_json::from_slice(payload).map_err(|_| Error::PayloadParseFailed)?;
+        shadow::merge_state(&mut self.reported, &delta["state"]);
+        self.shadow_version = version;
+
+        let client = shadow::ShadowClient::new(THING, matched.shadow_name)?;
+        let update = json!({"state": {"reported": self.reported}});
+        self.publish(client.update_topic().as_str(), update.to_string());
+        Ok(())
+    }
+
+    fn on_jobs(&mut self, topic: &str, payload: &[u8]) -> Result<(), Error> {
+        let matched = jobs::match_topic(topic)?;
+        match matched.api {
+            jobs::Topic::NextJobChanged => {
+                let Some(execution) = jobs::parse_start_next::<InstallJob>(payload)? else {
+                    return Ok(());
+                };
+                assert_eq!(execution.job_document.operation, "install");
+                self.installing = Some(execution.job_document.version.to_string());
+                self.current_job = Some(execution.job_id.to_string());
+
+                let mut request = jobs::UpdateRequest::new(jobs::JobStatus::InProgress);
+                request.expected_version = execution.version_number;
+                let update = jobs::update(THING, execution.job_id)?;
+                self.publish(&update, request.to_json::<128>()?.to_string());
+                Ok(())
+            }
+            jobs::Topic::UpdateSuccess => {
+                let id = matched.id.ok_or(Error::JobsIdParseFailed)?;
+                assert_eq!(self.current_job.as_deref(), Some(&id[..]));
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn on_defender(&mut self, topic: &str, payload: &[u8]) -> Result<(), Error> {
+        let matched = defender::match_topic(topic)?;
+        if matched.api == defender::Topic::JsonReportRejected {
+            let response = defender::parse_json_response(payload)?;
+            self.rejected_reports.push(response.report_id);
+        }
+        Ok(())
+    }
+
+    fn publish(&mut self, topic: &str, payload: String) {
+        self.outbox.push((topic.to_string(), payload));
+    }
+}
+
+/// MQTT filter matching, for the `+` and `#` wildcards of the plan.
+fn filter_matches(filter: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split('/');
+    for level in filter.split('/') {
+        match (level, topic_levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (level, Some(topic_level)) if level == topic_level => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+fn subscribed(plan: &SubscribePlan<16>, topic: &str) -> bool {
+    plan.as_slice()
+        .iter()
+        .any(|(filter, _)| filter_matches(filter, topic))
+}
+
+#[test]
+fn device_session() {
+    let plan = SubscribePlan::<16>::for_device(THING, None).unwrap();
+    let incoming: [(&str, &[u8]); 5] = [
+        ("$aws/things/chloe/shadow/update/delta", SHADOW_DELTA),
+        ("$aws/things/chloe/shadow/update/delta", STALE_SHADOW_DELTA),
+        ("$aws/things/chloe/jobs/notify-next", JOB_NOTIFY_NEXT),
+        (
+            "$aws/things/chloe/jobs/ota-1/update/accepted",
+            JOB_UPDATE_ACCEPTED,
+        ),
+        (
+            "$aws/things/chloe/defender/metrics/json/rejected",
+            DEFENDER_REJECTED,
+        ),
+    ];
+
+    let mut device = Device {
+        reported: json!({"color": "red", "lights": {"on": true, "level": 3}}),
+        ..Default::default()
+    };
+    for (topic, payload) in incoming {
+        assert!(subscribed(&plan, topic), "{topic} is not subscribed to");
+        device.handle(topic, payload).unwrap();
+    }
+
+    // The delta was applied and reported back once, the stale one dropped.
+    assert_eq!(device.shadow_version, 3);
+    assert_eq!(
+        device.reported,
+        json!({"color": "green", "lights": {"on": true, "level": 5}})
+    );
+    // The job was started with the version the notification carried.
+    assert_eq!(device.current_job.as_deref(), Some("ota-1"));
+    assert_eq!(device.installing.as_deref(), Some("2.1.0"));
+    assert_eq!(device.rejected_reports, [1530304554]);
+
+    let outbox: Vec<(&str, Value)> = device
+        .outbox
+        .iter()
+        .map(|(topic, payload)| (&topic[..], serde_json::from_str(payload).unwrap()))
+        .collect();
+    assert_eq!(
+        outbox,
+        [
+            (
+                "$aws/things/chloe/shadow/update",
+                json!({"state": {"reported": {"color": "green", "lights": {"on": true, "level": 5}}}})
+            ),
+            (
+                "$aws/things/chloe/jobs/ota-1/update",
+                json!({"status": "IN_PROGRESS", "expectedVersion": 1})
+            ),
+        ]
+    );
+    // What the device publishes is routed as a request, not as a response.
+    for (topic, _) in &outbox {
+        assert!(!subscribed(&plan, topic), "{topic} would echo back");
+    }
+}
+
+#[test]
+fn device_session_bad_messages() {
+    let plan = SubscribePlan::<16>::for_device(THING, None).unwrap();
+    let mut device = Device::default();
+
+    // A CBOR report response on the JSON topic.
+    let topic = "$aws/things/chloe/defender/metrics/json/rejected";
+    assert!(subscribed(&plan, topic));
+    assert_eq!(
+        device.handle(topic, &[0xa1, 0x61, 0x61, 0x01]),
+        Err(Error::FormatMismatch)
+    );
+    // A truncated job notification.
+    assert_eq!(
+        device.handle("$aws/things/chloe/jobs/notify-next", &JOB_NOTIFY_NEXT[..40]),
+        Err(Error::PayloadParseFailed)
+    );
+    // An application topic, and a shadow topic of an unknown operation.
+    assert_eq!(device.handle("chloe/commands", b"{}"), Err(Error::NoMatch));
+    assert_eq!(
+        device.handle("$aws/things/chloe/shadow/update/future-suffix", b"{}"),
+        Err(Error::MessageTypeParseFailed)
+    );
+    assert!(device.outbox.is_empty());
+    assert!(device.rejected_reports.is_empty());
+
+    for format in [PayloadFormat::Json, PayloadFormat::Cbor] {
+        let publish = defender::publish_topic(THING, format).unwrap();
+        assert!(!subscribed(&plan, &publish));
+    }
+}